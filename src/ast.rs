@@ -0,0 +1,423 @@
+// ast.rs
+// the tree the parser builds out of the token stream. the checker walks it to find type errors
+// and the interpreter walks it to run the program.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::bigint::BigInt;
+use crate::format::FormatSpec;
+use crate::ints::IntKind;
+
+/// A type as it was written in the source, e.g. `int`, `str[]` or `Box<int>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    Named(String, Vec<TypeExpr>),
+    List(Box<TypeExpr>),
+    Optional(Box<TypeExpr>),
+    // `(int, str)`
+    Tuple(Vec<TypeExpr>),
+}
+
+impl TypeExpr {
+    pub fn named(name: &str) -> Self {
+        TypeExpr::Named(name.to_string(), Vec::new())
+    }
+}
+
+impl std::fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeExpr::Named(name, args) if args.is_empty() => write!(f, "{}", name),
+            TypeExpr::Named(name, args) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+            TypeExpr::List(inner) => write!(f, "{}[]", inner),
+            TypeExpr::Optional(inner) => write!(f, "{}?", inner),
+            TypeExpr::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                write!(f, "({})", items.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    WrappingAdd,
+    WrappingSub,
+    WrappingMul,
+    SaturatingAdd,
+    SaturatingSub,
+    SaturatingMul,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+    Coalesce,
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::WrappingAdd => "+%",
+            BinaryOp::WrappingSub => "-%",
+            BinaryOp::WrappingMul => "*%",
+            BinaryOp::SaturatingAdd => "+|",
+            BinaryOp::SaturatingSub => "-|",
+            BinaryOp::SaturatingMul => "*|",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Pow => "**",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Coalesce => "??",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl BinaryOp {
+    /// Whether the operator computes a number, as opposed to comparing or combining bools.
+    pub fn is_arithmetic(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Add
+                | BinaryOp::Sub
+                | BinaryOp::Mul
+                | BinaryOp::Div
+                | BinaryOp::Mod
+                | BinaryOp::WrappingAdd
+                | BinaryOp::WrappingSub
+                | BinaryOp::WrappingMul
+                | BinaryOp::SaturatingAdd
+                | BinaryOp::SaturatingSub
+                | BinaryOp::SaturatingMul
+                | BinaryOp::BitAnd
+                | BinaryOp::BitOr
+                | BinaryOp::BitXor
+                | BinaryOp::Shl
+                | BinaryOp::Shr
+                | BinaryOp::Pow
+        )
+    }
+
+    /// Whether the right side is a shift amount or an exponent rather than a value of the left
+    /// side's type.
+    pub fn is_shift_or_power(self) -> bool {
+        matches!(self, BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Pow)
+    }
+
+    /// The prelude trait a struct implements to use the operator, and the trait's method.
+    pub fn overload(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            BinaryOp::Add => Some(("Add", "add")),
+            BinaryOp::Sub => Some(("Sub", "sub")),
+            BinaryOp::Mul => Some(("Mul", "mul")),
+            BinaryOp::Div => Some(("Div", "div")),
+            BinaryOp::Mod => Some(("Rem", "rem")),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    // `~`, flips every bit of an integer
+    BitNot,
+}
+
+impl UnaryOp {
+    /// Like `BinaryOp::overload`, only `-` can be overloaded.
+    pub fn overload(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            UnaryOp::Neg => Some(("Neg", "neg")),
+            _ => None,
+        }
+    }
+}
+
+/// A piece of an interpolated string like `"sum = {a + b:>4}"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Value(Expr, FormatSpec),
+}
+
+/// The type the checker gave an integer literal without a suffix. Every copy of the literal
+/// shares it, so the interpreter builds the value the checker saw even from a cloned function.
+#[derive(Debug, Clone, Default)]
+pub struct LiteralType(Rc<Cell<Option<Numeric>>>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Numeric {
+    Int(IntKind),
+    Big,
+}
+
+impl LiteralType {
+    pub fn get(&self) -> Option<Numeric> {
+        self.0.get()
+    }
+
+    pub fn set(&self, ty: Numeric) {
+        self.0.set(Some(ty));
+    }
+}
+
+// two literals are the same whatever they were checked as
+impl PartialEq for LiteralType {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    // an integer literal without a suffix, an `int` unless it's expected to be a sized type or a
+    // bigint
+    Integer(i128, LiteralType),
+    // `255u8`, an integer literal of a sized type
+    SizedInteger(i128, IntKind),
+    // `123n`, a bigint literal
+    BigInteger(BigInt),
+    Float(f64),
+    Str(String),
+    Interpolated(Vec<StringPart>),
+    Bool(bool),
+    None,
+    Identifier(String),
+    List(Vec<Expr>),
+    // `(1, "a")`, `(x,)` with one item
+    Tuple(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    Unary(UnaryOp, Box<Expr>),
+    // `value as type`, between the number types
+    Cast(Box<Expr>, TypeExpr),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call {
+        name: String,
+        type_args: Vec<TypeExpr>,
+        args: Vec<Expr>,
+    },
+    // `port: 80` in a call's arguments, passing an argument by its parameter's name
+    NamedArg(String, Box<Expr>),
+    // `receiver.method(args)`, a builtin method of the receiver's type or else
+    // `method(receiver, args)`
+    MethodCall {
+        receiver: Box<Expr>,
+        method: String,
+        type_args: Vec<TypeExpr>,
+        args: Vec<Expr>,
+    },
+    Index(Box<Expr>, Box<Expr>),
+    // `expr?`, unwraps an ok or a value, returning an err or none from the enclosing function
+    Propagate(Box<Expr>),
+    // `point.x`, or `pair.0` for the items of a tuple
+    Field(Box<Expr>, String),
+    // `start..end`, `start..=end`, optionally followed by `step n`
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+        step: Option<Box<Expr>>,
+    },
+    StructLiteral {
+        name: String,
+        type_args: Vec<TypeExpr>,
+        fields: Vec<(String, Expr)>,
+    },
+    // `if cond { a } else { b }` used as a value. a branch's value is its last statement when
+    // that's an expression, `else if` is an else branch holding just another `If`
+    If {
+        cond: Box<Expr>,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>,
+    },
+    // `{ statements; value }`, a block whose value is its last expression
+    Block(Vec<Stmt>),
+}
+
+/// A name without the module prefix the loader gave it, `floor` for `math.floor`. A method call
+/// in a module has its name prefixed in case it's one of the module's functions, but the builtin
+/// methods go by the plain name.
+pub fn unqualified(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    // a variadic parameter's type is the list its arguments are collected into
+    pub ty: TypeExpr,
+    pub mutable: bool,
+    // `port:int = 80`, used when the call leaves the argument out
+    pub default: Option<Expr>,
+    // `xs: ...int`, takes any number of trailing arguments
+    pub variadic: bool,
+}
+
+/// A type parameter with the traits it must implement, e.g. `T: Show + Eq`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDecl {
+    pub name: String,
+    pub type_params: Vec<TypeParam>,
+    pub params: Vec<Param>,
+    pub ret: TypeExpr,
+    pub body: Vec<Stmt>,
+    // `pub`, visible to modules that import this one
+    pub public: bool,
+    // `sin(x:float):float;` in a standard module, with no body because the interpreter runs it
+    pub native: bool,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDecl {
+    pub name: String,
+    pub type_params: Vec<TypeParam>,
+    pub fields: Vec<(String, TypeExpr)>,
+    pub public: bool,
+    pub line: usize,
+}
+
+/// A method a trait requires. The first parameter is always `self`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitMethod {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret: TypeExpr,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitDecl {
+    pub name: String,
+    pub type_params: Vec<TypeParam>,
+    pub methods: Vec<TraitMethod>,
+    pub public: bool,
+    pub line: usize,
+}
+
+/// `impl<T> Trait<Args> for Type { ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplDecl {
+    pub trait_name: String,
+    // the type arguments of a generic trait, e.g. `int` in `Iterator<int>`
+    pub trait_args: Vec<TypeExpr>,
+    pub type_params: Vec<TypeParam>,
+    pub target: TypeExpr,
+    pub methods: Vec<FunctionDecl>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    /// `name:type = value`, or `name := value` with no type to infer it from the value.
+    Let {
+        name: String,
+        ty: Option<TypeExpr>,
+        value: Expr,
+        mutable: bool,
+    },
+    /// `const NAME:type = value;`, only at the top level and worked out before the program runs.
+    Const {
+        name: String,
+        ty: TypeExpr,
+        value: Expr,
+        public: bool,
+    },
+    /// `(a, b) := pair;` or `(a, b):(int, str) = pair;`, one variable per item of a tuple.
+    Destructure {
+        names: Vec<String>,
+        ty: Option<TypeExpr>,
+        value: Expr,
+        mutable: bool,
+    },
+    Assign {
+        target: Expr,
+        value: Expr,
+    },
+    Print(Vec<Expr>),
+    If {
+        cond: Expr,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    /// `for x in items { }`, or `for (k, v) in map { }` with two names. more than one name
+    /// also takes apart a list of tuples, `for (name, age) in people { }`.
+    For {
+        names: Vec<String>,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+    Return(Option<Expr>),
+    /// `try { ... } catch (e) { ... }`, `e` is bound to the runtime error's message.
+    Try {
+        body: Vec<Stmt>,
+        error_name: Option<String>,
+        handler: Vec<Stmt>,
+    },
+    Expr(Expr),
+    Block(Vec<Stmt>),
+    Function(FunctionDecl),
+    Struct(StructDecl),
+    Trait(TraitDecl),
+    Impl(ImplDecl),
+    /// `import "util.cu";` or `import util;`, holding the path of the file to load.
+    Import(String),
+}
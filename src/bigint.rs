@@ -0,0 +1,341 @@
+// bigint.rs
+// integers that never overflow, for the `bigint` type. the number is a sign and a list of base 2^32
+// digits, lowest first, with no zero digits on the end. zero is never negative.
+
+use std::cmp::Ordering;
+
+use crate::ast::BinaryOp;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> Self {
+        BigInt { negative: false, digits: Vec::new() }
+    }
+
+    pub fn from_i128(n: i128) -> Self {
+        let mut magnitude = n.unsigned_abs();
+        let mut digits = Vec::new();
+        while magnitude > 0 {
+            digits.push(magnitude as u32);
+            magnitude >>= 32;
+        }
+        BigInt { negative: n < 0, digits }
+    }
+
+    /// The number as an i128, if it fits in one.
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.digits.len() > 4 {
+            return None;
+        }
+        let magnitude = self.digits.iter().rev().fold(0u128, |acc, d| (acc << 32) | *d as u128);
+        if self.negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.digits.iter().rev().fold(0.0, |acc, d| acc * 4294967296.0 + *d as f64);
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Parses decimal digits with an optional sign, like `-1234`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, body) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        if body.is_empty() || !body.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut digits = Vec::new();
+        // nine decimal digits at a time always fit in a u32
+        for chunk in body.as_bytes().chunks(9) {
+            let value = chunk.iter().fold(0u32, |acc, b| acc * 10 + (b - b'0') as u32);
+            mul_small(&mut digits, 10u32.pow(chunk.len() as u32), value);
+        }
+        Some(BigInt { negative, digits }.normalize())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn pow(&self, exponent: u32) -> Self {
+        let mut result = BigInt::from_i128(1);
+        let mut base = self.clone();
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// Division rounding toward zero and the remainder that goes with it, which has the sign of
+    /// `self`, the same as for `int`. `None` when dividing by zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_digits(&self.digits, &other.digits);
+        let quotient = BigInt { negative: self.negative != other.negative, digits: quotient };
+        let remainder = BigInt { negative: self.negative, digits: remainder };
+        Some((quotient.normalize(), remainder.normalize()))
+    }
+
+    fn normalize(mut self) -> Self {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+        if self.digits.is_empty() {
+            self.negative = false;
+        }
+        self
+    }
+}
+
+// digits = digits * factor + carry
+fn mul_small(digits: &mut Vec<u32>, factor: u32, carry: u32) {
+    let mut carry = carry as u64;
+    for digit in digits.iter_mut() {
+        let product = *digit as u64 * factor as u64 + carry;
+        *digit = product as u32;
+        carry = product >> 32;
+    }
+    if carry > 0 {
+        digits.push(carry as u32);
+    }
+}
+
+fn compare_digits(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+// a - b where a >= b
+fn sub_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut difference = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+    while result.last() == Some(&0) {
+        result.pop();
+    }
+    result
+}
+
+fn mul_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let cell = result[i + j] as u64 + *x as u64 * *y as u64 + carry;
+            result[i + j] = cell as u32;
+            carry = cell >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    result
+}
+
+fn div_rem_digits(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = b {
+        // one digit divisors are common (printing divides by 10^9) and need no shifting
+        let mut quotient = vec![0u32; a.len()];
+        let mut remainder = 0u64;
+        for i in (0..a.len()).rev() {
+            let current = (remainder << 32) | a[i] as u64;
+            quotient[i] = (current / *divisor as u64) as u32;
+            remainder = current % *divisor as u64;
+        }
+        return (quotient, vec![remainder as u32]);
+    }
+    // schoolbook long division, one bit at a time
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        let incoming = (a[bit / 32] >> (bit % 32)) & 1;
+        mul_small(&mut remainder, 2, incoming);
+        if compare_digits(&remainder, b) != Ordering::Less {
+            remainder = sub_digits(&remainder, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quotient, remainder)
+}
+
+/// Applies an arithmetic operator to two bigints. Only division by zero can go wrong.
+pub fn arithmetic(op: BinaryOp, a: &BigInt, b: &BigInt) -> Result<BigInt, String> {
+    match op {
+        BinaryOp::Add => Ok(a + b),
+        BinaryOp::Sub => Ok(a - b),
+        BinaryOp::Mul => Ok(a * b),
+        BinaryOp::Div | BinaryOp::Mod => match a.div_rem(b) {
+            Some((quotient, _)) if op == BinaryOp::Div => Ok(quotient),
+            Some((_, remainder)) => Ok(remainder),
+            None => Err("division by zero".to_string()),
+        },
+        BinaryOp::Pow => match b.to_i128().map(u32::try_from) {
+            Some(Ok(exponent)) => Ok(a.pow(exponent)),
+            _ if *b < BigInt::zero() => Err(format!("cannot raise {} to the negative power {}", a, b)),
+            _ => Err(format!("the exponent {} is too big", b)),
+        },
+        _ => Err(format!("`{}` cannot be applied to bigints", op)),
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_digits(&self.digits, &other.digits),
+            (true, true) => compare_digits(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::ops::Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt { negative: !self.negative, digits: self.digits.clone() }.normalize()
+    }
+}
+
+impl std::ops::Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt { negative: self.negative, digits: add_digits(&self.digits, &other.digits) };
+        }
+        // different signs, so the bigger magnitude wins and keeps its sign
+        match compare_digits(&self.digits, &other.digits) {
+            Ordering::Less => BigInt {
+                negative: other.negative,
+                digits: sub_digits(&other.digits, &self.digits),
+            },
+            _ => BigInt { negative: self.negative, digits: sub_digits(&self.digits, &other.digits) },
+        }
+        .normalize()
+    }
+}
+
+impl std::ops::Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl std::ops::Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let digits = mul_digits(&self.digits, &other.digits);
+        BigInt { negative: self.negative != other.negative, digits }.normalize()
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut chunks = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let (quotient, remainder) = div_rem_digits(&digits, &[1_000_000_000]);
+            chunks.push(remainder[0]);
+            digits = quotient;
+            while digits.last() == Some(&0) {
+                digits.pop();
+            }
+        }
+        let mut text = String::new();
+        if self.negative {
+            text.push('-');
+        }
+        text.push_str(&chunks.pop().unwrap_or(0).to_string());
+        for chunk in chunks.iter().rev() {
+            text.push_str(&format!("{:09}", chunk));
+        }
+        f.pad(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic_matches_i128_and_goes_past_it() {
+        let cases = [(0i128, 7i128), (-12345678901234, 987654321), (i64::MAX as i128, -3), (-7, -2)];
+        for (a, b) in cases {
+            let (x, y) = (BigInt::from_i128(a), BigInt::from_i128(b));
+            assert_eq!((&x + &y).to_i128(), Some(a + b));
+            assert_eq!((&x - &y).to_i128(), Some(a - b));
+            assert_eq!((&x * &y).to_i128(), Some(a * b));
+            let (q, r) = x.div_rem(&y).unwrap();
+            assert_eq!((q.to_i128(), r.to_i128()), (Some(a / b), Some(a % b)));
+            assert_eq!(x.cmp(&y), a.cmp(&b));
+        }
+        let factorial = (1..=30).fold(BigInt::from_i128(1), |acc, n| &acc * &BigInt::from_i128(n));
+        assert_eq!(factorial.to_string(), "265252859812191058636308480000000");
+        let huge = big("2").pow(200);
+        assert_eq!(huge.to_string(), "1606938044258990275541962092341162602522202993782792835301376");
+        let (q, r) = huge.div_rem(&big("-340282366920938463463374607431768211457")).unwrap();
+        assert_eq!(&(&q * &big("-340282366920938463463374607431768211457")) + &r, huge);
+        assert_eq!(big("-0").to_string(), "0");
+        assert!(BigInt::parse("12a").is_none());
+        assert!(huge.to_i128().is_none());
+    }
+}
@@ -0,0 +1,3242 @@
+// checker.rs
+// walks the parsed program before anything runs and reports type errors.
+// generic functions and structs are checked once with their type parameters left abstract,
+// and every use of them gets fresh type variables that are solved by unification.
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::bigint::{self, BigInt};
+use crate::format::{parse_template, FormatSpec, Piece};
+use crate::ints::{self, IntKind};
+use crate::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    // `int`, which is also `i32`
+    Int,
+    // the other sized integers, `i8` to `u64`
+    Sized(IntKind),
+    // integers without a limit, `123n`
+    BigInt,
+    Float,
+    Str,
+    Bool,
+    Void,
+    List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    Optional(Box<Type>),
+    // `Result<T, E>`, built with `ok(v)` and `err(e)`
+    Result(Box<Type>, Box<Type>),
+    // `0..10`, `0..=10 step 2`
+    Range,
+    // `(int, str)`
+    Tuple(Vec<Type>),
+    Struct(String, Vec<Type>),
+    // a type parameter of the function or struct currently being checked
+    Param(String),
+    // an inference variable, solved through `Checker::substitution`
+    Var(usize),
+    // stands in for an expression that already produced a diagnostic
+    Error,
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Sized(kind) => write!(f, "{}", kind),
+            Type::BigInt => write!(f, "bigint"),
+            Type::Float => write!(f, "float"),
+            Type::Str => write!(f, "str"),
+            Type::Bool => write!(f, "bool"),
+            Type::Void => write!(f, "void"),
+            Type::List(inner) => write!(f, "{}[]", inner),
+            Type::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            Type::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                write!(f, "({})", items.join(", "))
+            }
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Result(value, error) => write!(f, "Result<{}, {}>", value, error),
+            Type::Range => write!(f, "range"),
+            Type::Struct(name, args) if args.is_empty() => write!(f, "{}", name),
+            Type::Struct(name, args) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+            Type::Param(name) => write!(f, "{}", name),
+            Type::Var(_) => write!(f, "_"),
+            Type::Error => write!(f, "<error>"),
+        }
+    }
+}
+
+// a type variable that has to be solved by the end of the current statement
+struct PendingInference {
+    var: usize,
+    message: String,
+    line: usize,
+}
+
+// a variable in scope. narrowing `x:int?` to `int` inside `if (x != none)` adds a binding for
+// `x` to the inner scope that remembers the type it was declared with.
+#[derive(Clone)]
+struct Binding {
+    ty: Type,
+    narrowed_from: Option<Type>,
+    mutable: bool,
+    // where it was declared, so assignment errors can point there
+    line: usize,
+    constant: bool,
+    // the variable of a `for` loop, which can't be made `mut`
+    loop_variable: bool,
+}
+
+// a type that has to implement a trait, checked once the statement's inference is done
+struct PendingBound {
+    ty: Type,
+    trait_name: String,
+    context: String,
+    line: usize,
+}
+
+pub struct Checker {
+    structs: HashMap<String, StructDecl>,
+    functions: HashMap<String, FunctionDecl>,
+    traits: HashMap<String, TraitDecl>,
+    impls: Vec<ImplDecl>,
+    scopes: Vec<HashMap<String, Binding>>,
+    type_params: Vec<TypeParam>,
+    self_type: Option<Type>,
+    return_type: Option<Type>,
+    substitution: Vec<Option<Type>>,
+    pending: Vec<PendingInference>,
+    pending_bounds: Vec<PendingBound>,
+    diagnostics: Vec<Diagnostic>,
+    // the values of the `const` items declared so far
+    constants: HashMap<String, Value>,
+    // every variable declared, with its line and type
+    variables: Vec<(usize, String, Type)>,
+}
+
+const BUILTINS: [&str; 17] = [
+    "len", "push", "sort", "ok", "err", "is_ok", "is_err", "unwrap", "unwrap_err", "unwrap_or",
+    "format", "pow", "parse_bigint", "to_str", "input", "read_line", "read_all",
+];
+
+const OPERATOR_TRAITS: [&str; 6] = ["Add", "Sub", "Mul", "Div", "Rem", "Neg"];
+
+// the traits every program can use. `Show` is what `print` calls and every type has a default
+// implementation of it; `Eq` backs `==` and `!=`; `Ord` backs `<`, `>`, `<=`, `>=` and `sort`,
+// with `cmp` returning a negative number, zero or a positive number. `for` loops over anything
+// that implements `Iterator`, calling `next` until it returns none. `Add` to `Rem` give a struct
+// `+`, `-`, `*`, `/` and `%`, and `Neg` gives it `-x`.
+pub const PRELUDE: &str = "
+trait Show { show(self):str }
+trait Eq { eq(self, other:Self):bool }
+trait Ord { cmp(self, other:Self):int }
+trait Iterator<T> { next(mut self):T? }
+trait Add { add(self, other:Self):Self }
+trait Sub { sub(self, other:Self):Self }
+trait Mul { mul(self, other:Self):Self }
+trait Div { div(self, other:Self):Self }
+trait Rem { rem(self, other:Self):Self }
+trait Neg { neg(self):Self }
+";
+
+/// Type checks a whole program, returning every diagnostic found.
+pub fn check(program: &[Stmt]) -> Result<(), Vec<Diagnostic>> {
+    variable_types(program).map(|_| ())
+}
+
+/// Checks the program and returns the type of every variable it declares, by line, for
+/// `cube check --show-types`.
+pub fn variable_types(program: &[Stmt]) -> Result<Vec<(usize, String, Type)>, Vec<Diagnostic>> {
+    let mut checker = Checker::new();
+    checker.check_program(program);
+    if !checker.diagnostics.is_empty() {
+        return Err(checker.diagnostics);
+    }
+    let mut variables: Vec<(usize, String, Type)> = std::mem::take(&mut checker.variables)
+        .into_iter()
+        .map(|(line, name, ty)| (line, name, checker.prune(&ty)))
+        .collect();
+    variables.sort_by_key(|(line, _, _)| *line);
+    Ok(variables)
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Checker {
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            traits: HashMap::new(),
+            impls: Vec::new(),
+            scopes: vec![HashMap::new()],
+            type_params: Vec::new(),
+            self_type: None,
+            return_type: None,
+            substitution: Vec::new(),
+            pending: Vec::new(),
+            pending_bounds: Vec::new(),
+            diagnostics: Vec::new(),
+            constants: HashMap::new(),
+            variables: Vec::new(),
+        }
+    }
+
+    // the receiver of a method call can be checked twice, its errors are only reported once
+    fn error(&mut self, message: impl Into<String>, line: usize) {
+        let message = message.into();
+        if !self.diagnostics.iter().any(|d| d.message == message && d.line == line) {
+            self.diagnostics.push(Diagnostic::new(message, line));
+        }
+    }
+
+    pub fn check_program(&mut self, program: &[Stmt]) {
+        for stmt in crate::parser::parse_source(PRELUDE).expect("the prelude parses") {
+            if let StmtKind::Trait(decl) = stmt.kind {
+                self.traits.insert(decl.name.clone(), decl);
+            }
+        }
+        // functions, structs, traits and impls can be used before the line they're declared on
+        for stmt in program {
+            match &stmt.kind {
+                StmtKind::Struct(decl) => {
+                    if self.structs.contains_key(&decl.name) {
+                        self.error(format!("struct `{}` is declared twice", decl.name), decl.line);
+                    }
+                    self.structs.insert(decl.name.clone(), decl.clone());
+                }
+                StmtKind::Function(decl) => {
+                    if BUILTINS.contains(&decl.name.as_str()) {
+                        self.error(
+                            format!("`{}` is a builtin function and cannot be redeclared", decl.name),
+                            decl.line,
+                        );
+                    } else if self.functions.contains_key(&decl.name) {
+                        self.error(format!("function `{}` is declared twice", decl.name), decl.line);
+                    }
+                    self.functions.insert(decl.name.clone(), decl.clone());
+                }
+                StmtKind::Trait(decl) => {
+                    if self.traits.contains_key(&decl.name) {
+                        self.error(format!("trait `{}` is declared twice", decl.name), decl.line);
+                    }
+                    self.traits.insert(decl.name.clone(), decl.clone());
+                }
+                StmtKind::Impl(decl) => self.impls.push(decl.clone()),
+                _ => {}
+            }
+        }
+
+        let mut traits: Vec<TraitDecl> = self.traits.values().cloned().collect();
+        traits.sort_by_key(|t| t.line);
+        for decl in &traits {
+            self.check_trait(decl);
+        }
+
+        let mut structs: Vec<StructDecl> = self.structs.values().cloned().collect();
+        structs.sort_by_key(|s| s.line);
+        for decl in &structs {
+            self.type_params = decl.type_params.clone();
+            self.check_bounds_exist(&decl.type_params, decl.line);
+            for (_, ty) in &decl.fields {
+                self.resolve_type(ty, decl.line);
+            }
+        }
+        self.type_params.clear();
+
+        // constants are worked out before anything else, in the order they're declared
+        for stmt in program {
+            if let StmtKind::Const {
+                name, ty, value, ..
+            } = &stmt.kind
+            {
+                self.check_const(name, ty, value, stmt.line);
+            }
+        }
+
+        for stmt in program {
+            if !matches!(
+                stmt.kind,
+                StmtKind::Function(_)
+                    | StmtKind::Struct(_)
+                    | StmtKind::Trait(_)
+                    | StmtKind::Impl(_)
+                    | StmtKind::Const { .. }
+            ) {
+                self.check_stmt(stmt);
+            }
+        }
+
+        for stmt in program {
+            match &stmt.kind {
+                StmtKind::Function(decl) => self.check_function(decl),
+                StmtKind::Impl(decl) => self.check_impl(decl),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_bounds_exist(&mut self, type_params: &[TypeParam], line: usize) {
+        for param in type_params {
+            for bound in &param.bounds {
+                if !self.traits.contains_key(bound) {
+                    self.error(format!("unknown trait `{}` in bound of `{}`", bound, param.name), line);
+                }
+            }
+        }
+    }
+
+    // trait methods are called like functions, so their names must not clash with one. the
+    // operator traits are left out, their methods are meant to be reached through the operator
+    // and a program may well have its own `add` function
+    fn check_trait(&mut self, decl: &TraitDecl) {
+        self.self_type = Some(Type::Param("Self".to_string()));
+        self.type_params = decl.type_params.clone();
+        let operator = OPERATOR_TRAITS.contains(&decl.name.as_str());
+        for method in &decl.methods {
+            if !operator && (BUILTINS.contains(&method.name.as_str()) || self.functions.contains_key(&method.name)) {
+                self.error(
+                    format!(
+                        "method `{}` of trait `{}` clashes with a function of the same name",
+                        method.name, decl.name
+                    ),
+                    method.line,
+                );
+            }
+            for param in &method.params {
+                self.resolve_type(&param.ty, method.line);
+            }
+            self.resolve_type(&method.ret, method.line);
+        }
+        self.self_type = None;
+        self.type_params.clear();
+    }
+
+    fn check_impl(&mut self, decl: &ImplDecl) {
+        let trait_decl = match self.traits.get(&decl.trait_name) {
+            Some(trait_decl) => trait_decl.clone(),
+            None => {
+                self.error(format!("unknown trait `{}`", decl.trait_name), decl.line);
+                return;
+            }
+        };
+        self.type_params = decl.type_params.clone();
+        self.check_bounds_exist(&decl.type_params, decl.line);
+        let target = self.resolve_type(&decl.target, decl.line);
+        let trait_args: Vec<Type> = decl
+            .trait_args
+            .iter()
+            .map(|arg| self.resolve_type(arg, decl.line))
+            .collect();
+        self.type_params.clear();
+        if trait_args.len() != trait_decl.type_params.len() {
+            self.error(
+                format!(
+                    "trait `{}` takes {} type argument(s) but {} were given",
+                    decl.trait_name,
+                    trait_decl.type_params.len(),
+                    trait_args.len()
+                ),
+                decl.line,
+            );
+            return;
+        }
+        // the trait's signatures are resolved with `Self` and its own type parameters left
+        // abstract, then filled in with this impl's target and type arguments all at once
+        let mut trait_mapping: HashMap<String, Type> = trait_decl
+            .type_params
+            .iter()
+            .map(|p| p.name.clone())
+            .zip(trait_args)
+            .collect();
+        trait_mapping.insert("Self".to_string(), target.clone());
+        let base = match type_name(&target) {
+            Some(base) => base,
+            None => {
+                self.error(
+                    format!("traits can only be implemented for named types, not {}", target),
+                    decl.line,
+                );
+                return;
+            }
+        };
+        if matches!(
+            target,
+            Type::Int | Type::Sized(_) | Type::BigInt | Type::Float | Type::Str | Type::Bool | Type::Range
+        )
+            && builtin_implements(&target, &decl.trait_name)
+        {
+            self.error(
+                format!("{} already implements `{}`", target, decl.trait_name),
+                decl.line,
+            );
+        }
+        let first = self
+            .impls
+            .iter()
+            .filter(|other| {
+                other.trait_name == decl.trait_name
+                    && matches!(&other.target, TypeExpr::Named(name, _) if *name == base)
+            })
+            .map(|other| other.line)
+            .min();
+        if first != Some(decl.line) {
+            self.error(
+                format!("`{}` is implemented for `{}` more than once", decl.trait_name, base),
+                decl.line,
+            );
+        }
+
+        for required in &trait_decl.methods {
+            let method = match decl.methods.iter().find(|m| m.name == required.name) {
+                Some(method) => method,
+                None => {
+                    self.error(
+                        format!(
+                            "missing method `{}` in impl of `{}` for {}",
+                            required.name, decl.trait_name, target
+                        ),
+                        decl.line,
+                    );
+                    continue;
+                }
+            };
+            self.self_type = Some(Type::Param("Self".to_string()));
+            self.type_params = trait_decl.type_params.clone();
+            let expected: Vec<Type> = required
+                .params
+                .iter()
+                .map(|p| Self::substitute(&self.resolve_type(&p.ty, required.line), &trait_mapping))
+                .collect();
+            let expected_ret = Self::substitute(&self.resolve_type(&required.ret, required.line), &trait_mapping);
+            self.self_type = Some(target.clone());
+            self.type_params = decl.type_params.clone();
+            let found: Vec<Type> = method
+                .params
+                .iter()
+                .map(|p| self.resolve_type(&p.ty, method.line))
+                .collect();
+            let found_ret = self.resolve_type(&method.ret, method.line);
+            let self_first = method.params.first().map(|p| p.name.as_str()) == Some("self");
+            if !self_first || expected != found || expected_ret != found_ret {
+                let expected: Vec<String> = expected.iter().map(|t| t.to_string()).collect();
+                self.error(
+                    format!(
+                        "method `{}` does not match trait `{}`, expected ({}):{}",
+                        method.name,
+                        decl.trait_name,
+                        expected.join(", "),
+                        expected_ret
+                    ),
+                    method.line,
+                );
+            }
+            self.type_params.clear();
+            self.self_type = None;
+        }
+        for method in &decl.methods {
+            if !trait_decl.methods.iter().any(|m| m.name == method.name) {
+                self.error(
+                    format!("method `{}` is not a member of trait `{}`", method.name, decl.trait_name),
+                    method.line,
+                );
+            }
+            self.self_type = Some(target.clone());
+            let mut function = method.clone();
+            let mut type_params = decl.type_params.clone();
+            type_params.extend(function.type_params);
+            function.type_params = type_params;
+            self.check_function(&function);
+            self.self_type = None;
+        }
+    }
+
+    fn check_function(&mut self, decl: &FunctionDecl) {
+        self.type_params = decl.type_params.clone();
+        self.check_bounds_exist(&decl.type_params, decl.line);
+        let globals = self.scopes.split_off(1);
+        self.scopes.push(HashMap::new());
+        let mut defaulted: Option<&str> = None;
+        for (i, param) in decl.params.iter().enumerate() {
+            let ty = self.resolve_type(&param.ty, decl.line);
+            if ty == Type::Void {
+                self.error(format!("parameter `{}` cannot have type void", param.name), decl.line);
+            }
+            // defaults are checked with only the parameters before them in scope
+            if let Some(default) = &param.default {
+                let found = self.check_expr(default, Some(&ty));
+                self.flush_pending();
+                self.expect_type(&ty, &found, &format!("default value of `{}`", param.name), default.line);
+                defaulted = Some(&param.name);
+            } else if let (Some(previous), false) = (defaulted, param.variadic) {
+                self.error(
+                    format!(
+                        "parameter `{}` needs a default value, it comes after `{}` which has one",
+                        param.name, previous
+                    ),
+                    decl.line,
+                );
+            }
+            if param.variadic && i + 1 != decl.params.len() {
+                self.error(format!("the variadic parameter `{}` must be the last one", param.name), decl.line);
+            }
+            self.scopes.last_mut().unwrap().insert(
+                param.name.clone(),
+                Binding {
+                    ty,
+                    narrowed_from: None,
+                    mutable: param.mutable,
+                    line: decl.line,
+                    constant: false,
+                    loop_variable: false,
+                },
+            );
+        }
+        let ret = self.resolve_type(&decl.ret, decl.line);
+        self.return_type = Some(ret.clone());
+        for stmt in &decl.body {
+            self.check_stmt(stmt);
+        }
+        if ret != Type::Void && !decl.native && !always_returns(&decl.body) {
+            self.error(
+                format!("function `{}` may finish without returning a {}", decl.name, ret),
+                decl.line,
+            );
+        }
+        self.return_type = None;
+        self.scopes.truncate(1);
+        self.scopes.extend(globals);
+        self.type_params.clear();
+    }
+
+    fn resolve_type(&mut self, ty: &TypeExpr, line: usize) -> Type {
+        match ty {
+            TypeExpr::List(inner) => Type::List(Box::new(self.resolve_type(inner, line))),
+            TypeExpr::Tuple(items) => Type::Tuple(items.iter().map(|i| self.resolve_type(i, line)).collect()),
+            TypeExpr::Optional(inner) => match self.resolve_type(inner, line) {
+                Type::Void => {
+                    self.error("`void?` is not a type", line);
+                    Type::Error
+                }
+                inner @ Type::Optional(_) => inner,
+                inner => Type::Optional(Box::new(inner)),
+            },
+            TypeExpr::Named(name, args) => {
+                let resolved: Vec<Type> = args.iter().map(|a| self.resolve_type(a, line)).collect();
+                let primitive = match name.as_str() {
+                    "int" => Some(Type::Int),
+                    "float" => Some(Type::Float),
+                    "str" => Some(Type::Str),
+                    "bool" => Some(Type::Bool),
+                    "void" => Some(Type::Void),
+                    "range" => Some(Type::Range),
+                    "bigint" => Some(Type::BigInt),
+                    _ => IntKind::from_name(name).map(integer_type),
+                };
+                if let Some(primitive) = primitive {
+                    if !args.is_empty() {
+                        self.error(format!("type `{}` does not take type arguments", name), line);
+                    }
+                    return primitive;
+                }
+                if name == "map" {
+                    if resolved.len() != 2 {
+                        self.error("`map` takes a key and a value type, e.g. `map<str, int>`", line);
+                        return Type::Error;
+                    }
+                    return Type::Map(Box::new(resolved[0].clone()), Box::new(resolved[1].clone()));
+                }
+                if name == "Result" && !self.structs.contains_key(name) {
+                    if resolved.len() != 2 {
+                        self.error("`Result` takes a value and an error type, e.g. `Result<int, str>`", line);
+                        return Type::Error;
+                    }
+                    return Type::Result(Box::new(resolved[0].clone()), Box::new(resolved[1].clone()));
+                }
+                if name == "Self" {
+                    return match self.self_type.clone() {
+                        Some(ty) => ty,
+                        None => {
+                            self.error("`Self` can only be used inside traits and impls", line);
+                            Type::Error
+                        }
+                    };
+                }
+                if self.type_params.iter().any(|p| &p.name == name) {
+                    if !args.is_empty() {
+                        self.error(format!("type parameter `{}` does not take type arguments", name), line);
+                    }
+                    return Type::Param(name.clone());
+                }
+                match self.structs.get(name) {
+                    Some(decl) if decl.type_params.len() == resolved.len() => {
+                        Type::Struct(name.clone(), resolved)
+                    }
+                    Some(decl) => {
+                        let expected = decl.type_params.len();
+                        self.error(
+                            format!(
+                                "struct `{}` takes {} type argument(s) but {} were given",
+                                name,
+                                expected,
+                                resolved.len()
+                            ),
+                            line,
+                        );
+                        Type::Error
+                    }
+                    None => {
+                        self.error(format!("unknown type `{}`", name), line);
+                        Type::Error
+                    }
+                }
+            }
+        }
+    }
+
+    fn fresh_var(&mut self) -> Type {
+        self.substitution.push(None);
+        Type::Var(self.substitution.len() - 1)
+    }
+
+    // follows solved variables all the way down
+    fn prune(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(v) => match &self.substitution[*v] {
+                Some(solved) => self.prune(solved),
+                None => ty.clone(),
+            },
+            Type::List(inner) => Type::List(Box::new(self.prune(inner))),
+            Type::Optional(inner) => Type::Optional(Box::new(self.prune(inner))),
+            Type::Map(key, value) => Type::Map(Box::new(self.prune(key)), Box::new(self.prune(value))),
+            Type::Result(value, error) => {
+                Type::Result(Box::new(self.prune(value)), Box::new(self.prune(error)))
+            }
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|i| self.prune(i)).collect()),
+            Type::Struct(name, args) => {
+                Type::Struct(name.clone(), args.iter().map(|a| self.prune(a)).collect())
+            }
+            _ => ty.clone(),
+        }
+    }
+
+    // prunes every variable inside the type too, not just the outermost one
+    fn resolve_vars(&self, ty: &Type) -> Type {
+        match self.prune(ty) {
+            Type::List(inner) => Type::List(Box::new(self.resolve_vars(&inner))),
+            Type::Optional(inner) => Type::Optional(Box::new(self.resolve_vars(&inner))),
+            Type::Map(key, value) => {
+                Type::Map(Box::new(self.resolve_vars(&key)), Box::new(self.resolve_vars(&value)))
+            }
+            Type::Result(value, error) => Type::Result(
+                Box::new(self.resolve_vars(&value)),
+                Box::new(self.resolve_vars(&error)),
+            ),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|i| self.resolve_vars(i)).collect()),
+            Type::Struct(name, args) => {
+                Type::Struct(name, args.iter().map(|a| self.resolve_vars(a)).collect())
+            }
+            ty => ty,
+        }
+    }
+
+    fn contains_var(ty: &Type) -> bool {
+        match ty {
+            Type::Var(_) => true,
+            Type::List(inner) | Type::Optional(inner) => Self::contains_var(inner),
+            Type::Map(key, value) | Type::Result(key, value) => {
+                Self::contains_var(key) || Self::contains_var(value)
+            }
+            Type::Struct(_, args) | Type::Tuple(args) => args.iter().any(Self::contains_var),
+            _ => false,
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Type) -> bool {
+        match self.prune(ty) {
+            Type::Var(v) => v == var,
+            Type::List(inner) | Type::Optional(inner) => self.occurs(var, &inner),
+            Type::Map(key, value) | Type::Result(key, value) => {
+                self.occurs(var, &key) || self.occurs(var, &value)
+            }
+            Type::Struct(_, args) | Type::Tuple(args) => args.iter().any(|a| self.occurs(var, a)),
+            _ => false,
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let a = self.prune(a);
+        let b = self.prune(b);
+        match (&a, &b) {
+            (Type::Error, _) | (_, Type::Error) => true,
+            (Type::Var(x), Type::Var(y)) if x == y => true,
+            (Type::Var(x), other) | (other, Type::Var(x)) => {
+                if self.occurs(*x, other) {
+                    return false;
+                }
+                self.substitution[*x] = Some(other.clone());
+                true
+            }
+            (Type::List(x), Type::List(y)) => self.unify(x, y),
+            (Type::Optional(x), Type::Optional(y)) => self.unify(x, y),
+            (Type::Map(k1, v1), Type::Map(k2, v2)) | (Type::Result(k1, v1), Type::Result(k2, v2)) => {
+                self.unify(k1, k2) && self.unify(v1, v2)
+            }
+            (Type::Struct(n1, a1), Type::Struct(n2, a2)) => {
+                n1 == n2 && a1.len() == a2.len() && a1.iter().zip(a2).all(|(x, y)| self.unify(x, y))
+            }
+            (Type::Tuple(x), Type::Tuple(y)) => {
+                x.len() == y.len() && x.iter().zip(y).all(|(a, b)| self.unify(a, b))
+            }
+            _ => a == b,
+        }
+    }
+
+    fn substitute(ty: &Type, mapping: &HashMap<String, Type>) -> Type {
+        match ty {
+            Type::Param(name) => mapping.get(name).cloned().unwrap_or_else(|| ty.clone()),
+            Type::List(inner) => Type::List(Box::new(Self::substitute(inner, mapping))),
+            Type::Optional(inner) => Type::Optional(Box::new(Self::substitute(inner, mapping))),
+            Type::Map(key, value) => Type::Map(
+                Box::new(Self::substitute(key, mapping)),
+                Box::new(Self::substitute(value, mapping)),
+            ),
+            Type::Result(value, error) => Type::Result(
+                Box::new(Self::substitute(value, mapping)),
+                Box::new(Self::substitute(error, mapping)),
+            ),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|i| Self::substitute(i, mapping)).collect()),
+            Type::Struct(name, args) => Type::Struct(
+                name.clone(),
+                args.iter().map(|a| Self::substitute(a, mapping)).collect(),
+            ),
+            _ => ty.clone(),
+        }
+    }
+
+    // binds each type parameter to an explicit type argument or a fresh variable that must be
+    // inferred by the end of the statement
+    fn instantiate(
+        &mut self,
+        type_params: &[TypeParam],
+        type_args: &[TypeExpr],
+        what: &str,
+        example: &str,
+        line: usize,
+    ) -> HashMap<String, Type> {
+        let mut mapping = HashMap::new();
+        if !type_args.is_empty() && type_args.len() != type_params.len() {
+            self.error(
+                format!(
+                    "{} takes {} type argument(s) but {} were given",
+                    what,
+                    type_params.len(),
+                    type_args.len()
+                ),
+                line,
+            );
+        }
+        for (i, param) in type_params.iter().enumerate() {
+            let ty = match type_args.get(i) {
+                Some(arg) if type_args.len() == type_params.len() => self.resolve_type(arg, line),
+                _ => {
+                    let var = self.fresh_var();
+                    if let Type::Var(v) = var {
+                        self.pending.push(PendingInference {
+                            var: v,
+                            message: format!(
+                                "cannot infer type parameter `{}` of {}; declare the type it is assigned to or spell the type arguments out, e.g. `{}`",
+                                param.name, what, example
+                            ),
+                            line,
+                        });
+                    }
+                    var
+                }
+            };
+            for bound in &param.bounds {
+                self.pending_bounds.push(PendingBound {
+                    ty: ty.clone(),
+                    trait_name: bound.clone(),
+                    context: format!("type parameter `{}` of {}", param.name, what),
+                    line,
+                });
+            }
+            mapping.insert(param.name.clone(), ty);
+        }
+        mapping
+    }
+
+    // reports every type variable from this statement that nothing managed to pin down
+    fn flush_pending(&mut self) {
+        for pending in std::mem::take(&mut self.pending) {
+            let solved = self.prune(&Type::Var(pending.var));
+            if Self::contains_var(&solved) {
+                self.error(pending.message, pending.line);
+                self.substitution[pending.var] = Some(Type::Error);
+            }
+        }
+        for bound in std::mem::take(&mut self.pending_bounds) {
+            let ty = self.prune(&bound.ty);
+            if !self.implements(&ty, &bound.trait_name) {
+                self.error(
+                    format!(
+                        "{} does not implement `{}`, which {} requires",
+                        ty, bound.trait_name, bound.context
+                    ),
+                    bound.line,
+                );
+            }
+        }
+    }
+
+    /// Whether `ty` implements `trait_name`, either built in, through an `impl` block or
+    /// because it is a type parameter bounded by the trait.
+    fn implements(&mut self, ty: &Type, trait_name: &str) -> bool {
+        let ty = self.prune(ty);
+        match &ty {
+            Type::Error | Type::Var(_) => return true,
+            _ if trait_name == "Show" => return true,
+            Type::Param(name) => {
+                return self
+                    .type_params
+                    .iter()
+                    .any(|p| &p.name == name && p.bounds.iter().any(|b| b == trait_name))
+            }
+            Type::List(inner) if trait_name == "Eq" || trait_name == "Ord" => {
+                return self.implements(inner, trait_name)
+            }
+            Type::Optional(inner) if trait_name == "Eq" => return self.implements(inner, trait_name),
+            Type::Tuple(items) if trait_name == "Eq" || trait_name == "Ord" => {
+                return items.iter().all(|item| self.implements(item, trait_name))
+            }
+            Type::Map(key, value) | Type::Result(key, value) if trait_name == "Eq" => {
+                return self.implements(key, trait_name) && self.implements(value, trait_name)
+            }
+            _ if builtin_implements(&ty, trait_name) => return true,
+            _ => {}
+        }
+        self.impl_trait_args(&ty, trait_name).is_some()
+    }
+
+    /// The type arguments `ty`'s impl of `trait_name` gives the trait, e.g. `[int]` for
+    /// `impl Iterator<int> for Counter`, or `None` if no impl block fits `ty`.
+    fn impl_trait_args(&mut self, ty: &Type, trait_name: &str) -> Option<Vec<Type>> {
+        let base = type_name(ty)?;
+        let candidates: Vec<ImplDecl> = self
+            .impls
+            .iter()
+            .filter(|i| {
+                i.trait_name == trait_name && matches!(&i.target, TypeExpr::Named(name, _) if *name == base)
+            })
+            .cloned()
+            .collect();
+        for candidate in candidates {
+            // instantiate the impl's own type parameters and see whether its target fits
+            let saved = std::mem::replace(&mut self.type_params, candidate.type_params.clone());
+            let target = self.resolve_type(&candidate.target, candidate.line);
+            let trait_args: Vec<Type> = candidate
+                .trait_args
+                .iter()
+                .map(|arg| self.resolve_type(arg, candidate.line))
+                .collect();
+            self.type_params = saved;
+            let mut mapping = HashMap::new();
+            for param in &candidate.type_params {
+                let var = self.fresh_var();
+                mapping.insert(param.name.clone(), var);
+            }
+            let target = Self::substitute(&target, &mapping);
+            let snapshot = self.substitution.clone();
+            if self.unify(&target, ty) {
+                let bounds_hold = candidate.type_params.iter().all(|param| {
+                    let arg = mapping[&param.name].clone();
+                    param.bounds.iter().all(|bound| self.implements(&arg, bound))
+                });
+                let trait_args: Vec<Type> = trait_args
+                    .iter()
+                    .map(|arg| self.resolve_vars(&Self::substitute(arg, &mapping)))
+                    .collect();
+                self.substitution = snapshot;
+                if bounds_hold {
+                    return Some(trait_args);
+                }
+            } else {
+                self.substitution = snapshot;
+            }
+        }
+        None
+    }
+
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).map(|binding| binding.ty.clone()))
+    }
+
+    // the type a variable was declared with, ignoring any narrowing
+    fn declared_type(&self, name: &str) -> Option<Type> {
+        self.scopes.iter().rev().find_map(|scope| {
+            scope
+                .get(name)
+                .map(|binding| binding.narrowed_from.clone().unwrap_or_else(|| binding.ty.clone()))
+        })
+    }
+
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, mutable: bool, line: usize) {
+        self.scopes.last_mut().unwrap().insert(
+            name.to_string(),
+            Binding {
+                ty,
+                narrowed_from: None,
+                mutable,
+                line,
+                constant: false,
+                loop_variable: false,
+            },
+        );
+    }
+
+    fn narrow(&mut self, name: &str, ty: Type) {
+        if let Some(declared) = self.declared_type(name) {
+            let binding = self.binding(name).unwrap();
+            let narrowed = Binding {
+                ty,
+                narrowed_from: Some(declared),
+                ..binding.clone()
+            };
+            self.scopes.last_mut().unwrap().insert(name.to_string(), narrowed);
+        }
+    }
+
+    // drops every narrowing of `name` between here and its declaration, used once it may be
+    // `none` again. one made in the scope the variable was declared in took the declaration's
+    // place, so that one is put back the way it was declared
+    fn forget_narrowing(&mut self, name: &str) {
+        for i in (0..self.scopes.len()).rev() {
+            let Some(binding) = self.scopes[i].get(name) else { continue };
+            let Some(declared) = binding.narrowed_from.clone() else { break };
+            let line = binding.line;
+            let outer = self.scopes[..i].iter().rev().find_map(|scope| scope.get(name));
+            if outer.is_some_and(|outer| outer.line == line) {
+                self.scopes[i].remove(name);
+            } else {
+                let binding = self.scopes[i].get_mut(name).unwrap();
+                binding.ty = declared;
+                binding.narrowed_from = None;
+                break;
+            }
+        }
+    }
+
+    // a call can run a function that assigns a `mut` global, so after one a global narrowed to
+    // not `none` may be `none` again
+    fn forget_global_narrowings(&mut self) {
+        let globals: Vec<(String, usize)> = self.scopes[0]
+            .iter()
+            .filter(|(_, binding)| binding.mutable)
+            .map(|(name, binding)| (name.clone(), binding.line))
+            .collect();
+        for (name, line) in globals {
+            // unless a local with the same name hides it
+            let narrowed = self.binding(&name).is_some_and(|b| b.line == line && b.narrowed_from.is_some());
+            if narrowed {
+                self.forget_narrowing(&name);
+            }
+        }
+    }
+
+    fn check_block(&mut self, statements: &[Stmt]) {
+        self.check_narrowed_block(statements, Vec::new());
+    }
+
+    fn check_narrowed_block(&mut self, statements: &[Stmt], narrowings: Vec<(String, Type)>) {
+        self.scopes.push(HashMap::new());
+        for (name, ty) in narrowings {
+            self.narrow(&name, ty);
+        }
+        for stmt in statements {
+            self.check_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    // like check_narrowed_block, but the last statement is the block's value when it's an
+    // expression
+    fn check_value_block(
+        &mut self,
+        statements: &[Stmt],
+        expected: Option<&Type>,
+        narrowings: Vec<(String, Type)>,
+    ) -> Type {
+        self.scopes.push(HashMap::new());
+        for (name, ty) in narrowings {
+            self.narrow(&name, ty);
+        }
+        let mut ty = Type::Void;
+        for (i, stmt) in statements.iter().enumerate() {
+            match &stmt.kind {
+                StmtKind::Expr(value) if i + 1 == statements.len() => ty = self.check_expr(value, expected),
+                _ => self.check_stmt(stmt),
+            }
+        }
+        self.scopes.pop();
+        ty
+    }
+
+    // both branches must have the same type, which is the type of the whole `if`. a branch that
+    // always returns never gives a value, so the other branch decides
+    fn check_if_expression(
+        &mut self,
+        cond: &Expr,
+        then_branch: &[Stmt],
+        else_branch: Option<&[Stmt]>,
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        let found = self.check_expr(cond, Some(&Type::Bool));
+        self.expect_type(&Type::Bool, &found, "if condition", cond.line);
+        let when_true = self.narrowings(cond, true);
+        let when_false = self.narrowings(cond, false);
+        let Some(else_branch) = else_branch else {
+            self.check_value_block(then_branch, None, when_true);
+            if expected.is_some_and(|ty| *ty != Type::Void) {
+                self.error("an `if` used as a value needs an `else`", line);
+                return Type::Error;
+            }
+            return Type::Void;
+        };
+        let then_ty = self.check_value_block(then_branch, expected, when_true);
+        let else_ty = self.check_value_block(else_branch, expected.or(Some(&then_ty)), when_false);
+        if always_returns(then_branch) {
+            return else_ty;
+        }
+        if always_returns(else_branch) {
+            return then_ty;
+        }
+        // `if c { 1 } else { none }` is an int?, so either side may be the optional one
+        if self.unify_coercing(&then_ty, &else_ty) {
+            then_ty
+        } else if self.unify_coercing(&else_ty, &then_ty) {
+            else_ty
+        } else {
+            let (then_ty, else_ty) = (self.prune(&then_ty), self.prune(&else_ty));
+            self.error(
+                format!("`if` and `else` have different types: {} and {}", then_ty, else_ty),
+                line,
+            );
+            Type::Error
+        }
+    }
+
+    /// The variables known not to be `none` when `cond` evaluates to `truthy`.
+    fn narrowings(&mut self, cond: &Expr, truthy: bool) -> Vec<(String, Type)> {
+        match &cond.kind {
+            ExprKind::Binary(op @ (BinaryOp::Eq | BinaryOp::Ne), left, right) => {
+                // `x != none` narrows when true, `x == none` when false
+                if (*op == BinaryOp::Ne) != truthy {
+                    return Vec::new();
+                }
+                let name = match (&left.kind, &right.kind) {
+                    (ExprKind::Identifier(name), ExprKind::None)
+                    | (ExprKind::None, ExprKind::Identifier(name)) => name,
+                    _ => return Vec::new(),
+                };
+                match self.lookup(name).map(|ty| self.prune(&ty)) {
+                    Some(Type::Optional(inner)) => vec![(name.clone(), *inner)],
+                    _ => Vec::new(),
+                }
+            }
+            ExprKind::Binary(BinaryOp::And, left, right) if truthy => {
+                let mut narrowings = self.narrowings(left, true);
+                narrowings.extend(self.narrowings(right, true));
+                narrowings
+            }
+            ExprKind::Binary(BinaryOp::Or, left, right) if !truthy => {
+                let mut narrowings = self.narrowings(left, false);
+                narrowings.extend(self.narrowings(right, false));
+                narrowings
+            }
+            ExprKind::Unary(UnaryOp::Not, inner) => self.narrowings(inner, !truthy),
+            _ => Vec::new(),
+        }
+    }
+
+    // unifies, letting a plain value stand in where an optional one is expected
+    fn unify_coercing(&mut self, expected: &Type, found: &Type) -> bool {
+        let snapshot = self.substitution.clone();
+        if self.unify(expected, found) {
+            return true;
+        }
+        self.substitution = snapshot;
+        match (self.prune(expected), self.prune(found)) {
+            (Type::Optional(inner), found) if !matches!(found, Type::Optional(_) | Type::Var(_)) => {
+                self.unify(&inner, &found)
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_type(&mut self, expected: &Type, found: &Type, context: &str, line: usize) {
+        if !self.unify_coercing(expected, found) {
+            let expected = self.prune(expected);
+            let found = self.prune(found);
+            let hint = match (&expected, &found) {
+                (expected, Type::Optional(inner)) if **inner == *expected => {
+                    "; the value may be `none`, check it with `!= none` or give a default with `??`"
+                }
+                _ => "",
+            };
+            self.error(
+                format!("mismatched types: {} expected {}, found {}{}", context, expected, found, hint),
+                line,
+            );
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        let line = stmt.line;
+        match &stmt.kind {
+            StmtKind::Let {
+                name,
+                ty,
+                value,
+                mutable,
+            } => {
+                let declared = match ty {
+                    Some(ty) => {
+                        let declared = self.resolve_type(ty, line);
+                        let found = self.check_expr(value, Some(&declared));
+                        self.expect_type(&declared, &found, &format!("`{}`", name), line);
+                        declared
+                    }
+                    // `x := value`, the value's type is the variable's type once the statement's
+                    // inference is done
+                    None => {
+                        let found = self.check_expr(value, None);
+                        self.flush_pending();
+                        let found = self.prune(&found);
+                        if Self::contains_var(&found) {
+                            self.error(
+                                format!(
+                                    "cannot infer the type of `{}`, the value's type is only known as {}; write it out, e.g. `{}:type = ...`",
+                                    name, found, name
+                                ),
+                                line,
+                            );
+                        }
+                        found
+                    }
+                };
+                if declared == Type::Void {
+                    self.error(format!("variable `{}` cannot have type void", name), line);
+                }
+                self.variables.push((line, name.clone(), declared.clone()));
+                self.declare(name, declared, *mutable, line);
+            }
+            StmtKind::Destructure {
+                names,
+                ty,
+                value,
+                mutable,
+            } => {
+                let found = match ty {
+                    Some(ty) => {
+                        let declared = self.resolve_type(ty, line);
+                        let found = self.check_expr(value, Some(&declared));
+                        self.expect_type(&declared, &found, "the tuple", line);
+                        declared
+                    }
+                    None => {
+                        let found = self.check_expr(value, None);
+                        self.flush_pending();
+                        found
+                    }
+                };
+                let items = self.tuple_items(&found, names.len(), line);
+                for (name, ty) in names.iter().zip(items) {
+                    self.variables.push((line, name.clone(), ty.clone()));
+                    self.declare(name, ty, *mutable, line);
+                }
+            }
+            StmtKind::Assign { target, value } => {
+                // only tuple items have numbers for names
+                if matches!(&target.kind, ExprKind::Field(_, field) if field.parse::<usize>().is_ok()) {
+                    self.error("the items of a tuple cannot be changed, build a new tuple instead", line);
+                } else {
+                    self.check_mutable(target, "assign to", line);
+                }
+                let target_ty = match &target.kind {
+                    ExprKind::Identifier(name) if self.declared_type(name).is_some() => {
+                        self.declared_type(name).unwrap()
+                    }
+                    _ => self.check_expr(target, None),
+                };
+                let found = self.check_expr(value, Some(&target_ty));
+                self.expect_type(&target_ty, &found, "assignment", line);
+                if let ExprKind::Identifier(name) = &target.kind {
+                    match (self.prune(&target_ty), self.prune(&found)) {
+                        (Type::Optional(inner), found) if !matches!(found, Type::Optional(_)) => {
+                            self.narrow(name, *inner)
+                        }
+                        (Type::Optional(_), _) => self.forget_narrowing(name),
+                        _ => {}
+                    }
+                }
+            }
+            StmtKind::Print(args) => {
+                for arg in args {
+                    let ty = self.check_expr(arg, None);
+                    if ty == Type::Void {
+                        self.error("cannot print a void value", arg.line);
+                    }
+                    if self.runs_methods(&ty) {
+                        self.forget_global_narrowings();
+                    }
+                }
+            }
+            StmtKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                let found = self.check_expr(cond, Some(&Type::Bool));
+                self.expect_type(&Type::Bool, &found, "if condition", cond.line);
+                let when_true = self.narrowings(cond, true);
+                let when_false = self.narrowings(cond, false);
+                self.check_narrowed_block(then_branch, when_true.clone());
+                if let Some(else_branch) = else_branch {
+                    self.check_narrowed_block(else_branch, when_false.clone());
+                }
+                // `if (x == none) { return; }` leaves x narrowed for the rest of the block
+                let else_returns = else_branch.as_deref().is_some_and(always_returns);
+                if always_returns(then_branch) {
+                    for (name, ty) in when_false {
+                        self.narrow(&name, ty);
+                    }
+                } else if else_returns {
+                    for (name, ty) in when_true {
+                        self.narrow(&name, ty);
+                    }
+                }
+            }
+            StmtKind::While { cond, body } => {
+                // anything the loop body may set back to none can't stay narrowed in the loop
+                for name in assigned_names(body) {
+                    self.forget_narrowing(&name);
+                }
+                let found = self.check_expr(cond, Some(&Type::Bool));
+                self.expect_type(&Type::Bool, &found, "while condition", cond.line);
+                let when_true = self.narrowings(cond, true);
+                self.check_narrowed_block(body, when_true);
+            }
+            StmtKind::For {
+                names,
+                iterable,
+                body,
+            } => {
+                for name in assigned_names(body) {
+                    self.forget_narrowing(&name);
+                }
+                let found = self.check_expr(iterable, None);
+                // `next(mut self)` changes the iterator as the loop goes
+                let found = self.prune(&found);
+                if matches!(found, Type::Struct(..) | Type::Param(_)) && self.implements(&found, "Iterator") {
+                    self.check_mutable(iterable, "loop over", iterable.line);
+                    self.forget_global_narrowings();
+                }
+                let items = self.item_types(&found, names.len(), iterable.line);
+                self.scopes.push(HashMap::new());
+                for (name, ty) in names.iter().zip(items) {
+                    self.variables.push((line, name.clone(), ty.clone()));
+                    self.declare(name, ty, false, line);
+                    self.scopes.last_mut().unwrap().get_mut(name).unwrap().loop_variable = true;
+                }
+                self.check_block(body);
+                self.scopes.pop();
+            }
+            StmtKind::Return(value) => match self.return_type.clone() {
+                None => self.error("`return` outside of a function", line),
+                Some(ret) => {
+                    let found = match value {
+                        Some(value) => self.check_expr(value, Some(&ret)),
+                        None => Type::Void,
+                    };
+                    self.expect_type(&ret, &found, "return value", line);
+                }
+            },
+            StmtKind::Try {
+                body,
+                error_name,
+                handler,
+            } => {
+                self.check_block(body);
+                self.scopes.push(HashMap::new());
+                if let Some(name) = error_name {
+                    self.declare(name, Type::Str, false, line);
+                }
+                for stmt in handler {
+                    self.check_stmt(stmt);
+                }
+                self.scopes.pop();
+            }
+            StmtKind::Expr(expr) => {
+                self.check_expr(expr, None);
+            }
+            StmtKind::Block(statements) => self.check_block(statements),
+            StmtKind::Function(decl) => {
+                self.error(format!("function `{}` must be declared at the top level", decl.name), line)
+            }
+            StmtKind::Struct(decl) => {
+                self.error(format!("struct `{}` must be declared at the top level", decl.name), line)
+            }
+            StmtKind::Trait(decl) => {
+                self.error(format!("trait `{}` must be declared at the top level", decl.name), line)
+            }
+            StmtKind::Impl(decl) => {
+                self.error(format!("impl of `{}` must be at the top level", decl.trait_name), line)
+            }
+            StmtKind::Const { name, .. } => {
+                self.error(format!("constant `{}` must be declared at the top level", name), line)
+            }
+            // the module loader has already replaced imports with the modules they name
+            StmtKind::Import(_) => {}
+        }
+        self.flush_pending();
+    }
+
+    // what a `for` loop with `count` names binds them to on each turn. ranges give ints, lists
+    // their items, strings one character at a time and maps their keys, or keys and values
+    fn item_types(&mut self, iterable: &Type, count: usize, line: usize) -> Vec<Type> {
+        let iterable = self.prune(iterable);
+        let items = match &iterable {
+            Type::Error => return vec![Type::Error; count],
+            Type::Range => vec![Type::Int],
+            Type::List(item) => vec![(**item).clone()],
+            Type::Str => vec![Type::Str],
+            Type::Map(key, value) => vec![(**key).clone(), (**value).clone()],
+            _ if self.implements(&iterable, "Iterator") => {
+                match self.impl_trait_args(&iterable, "Iterator") {
+                    Some(args) => args,
+                    None => vec![self.fresh_var()],
+                }
+            }
+            _ => {
+                self.error(
+                    format!(
+                        "cannot loop over {}, it is not a range, list, map or str and does not implement `Iterator`",
+                        iterable
+                    ),
+                    line,
+                );
+                return vec![Type::Error; count];
+            }
+        };
+        // `for (a, b) in pairs` takes apart each tuple of a list
+        if count > 1 && items.len() == 1 && matches!(self.prune(&items[0]), Type::Tuple(_)) {
+            return self.tuple_items(&items[0], count, line);
+        }
+        if count > items.len() {
+            self.error(
+                format!(
+                    "only maps and lists of tuples can be looped over as `(a, b)`, not {}",
+                    iterable
+                ),
+                line,
+            );
+            return vec![Type::Error; count];
+        }
+        items.into_iter().take(count).collect()
+    }
+
+    // the types of the items of a tuple being taken apart into `count` names
+    fn tuple_items(&mut self, ty: &Type, count: usize, line: usize) -> Vec<Type> {
+        match self.prune(ty) {
+            Type::Tuple(items) if items.len() == count => items,
+            Type::Tuple(items) => {
+                self.error(
+                    format!(
+                        "cannot take apart a tuple of {} items into {} names",
+                        items.len(),
+                        count
+                    ),
+                    line,
+                );
+                vec![Type::Error; count]
+            }
+            Type::Error => vec![Type::Error; count],
+            other => {
+                self.error(format!("cannot take apart {}, it is not a tuple", other), line);
+                vec![Type::Error; count]
+            }
+        }
+    }
+
+    // changing a variable, or a field or element of one, needs it to be declared `mut`
+    fn check_mutable(&mut self, target: &Expr, action: &str, line: usize) {
+        let mut root = target;
+        while let ExprKind::Field(base, _) | ExprKind::Index(base, _) = &root.kind {
+            root = base;
+        }
+        let name = match &root.kind {
+            ExprKind::Identifier(name) => name,
+            _ => return,
+        };
+        let binding = match self.binding(name) {
+            Some(binding) if !binding.mutable => binding.clone(),
+            _ => return,
+        };
+        let (message, note) = if binding.constant {
+            (
+                format!("cannot {} constant `{}`", action, name),
+                format!("`{}` is declared as a constant here", name),
+            )
+        } else if binding.loop_variable {
+            (
+                format!("cannot {} loop variable `{}`", action, name),
+                format!("loop variables cannot be changed, copy `{}` into a `mut` local first", name),
+            )
+        } else {
+            (
+                format!("cannot {} `{}` because it is not declared `mut`", action, name),
+                format!("`{}` is declared here, write `mut {}` to allow changing it", name, name),
+            )
+        };
+        self.diagnostics
+            .push(Diagnostic::new(message, line).with_note(note, binding.line));
+    }
+
+    // `const NAME:type = value` is checked like a declaration and then worked out right away
+    fn check_const(&mut self, name: &str, ty: &TypeExpr, value: &Expr, line: usize) {
+        let declared = self.resolve_type(ty, line);
+        let found = self.check_expr(value, Some(&declared));
+        self.expect_type(&declared, &found, &format!("`{}`", name), line);
+        self.flush_pending();
+        if self.constants.contains_key(name) {
+            self.error(format!("constant `{}` is declared twice", name), line);
+        }
+        match self.const_value(value, &declared) {
+            Ok(result) => {
+                // `const MAX:u8 = 200 + 55;` is worked out as ints, then has to fit in a u8
+                let declared_kind = int_kind(&self.prune(&declared));
+                let result = match (declared_kind, result.as_integer()) {
+                    _ if self.prune(&declared) == Type::BigInt => {
+                        result.as_bigint().map(Value::Big).unwrap_or(result)
+                    }
+                    (Some(kind), Some((_, n))) if kind.fits(n) => Value::integer(kind, n),
+                    (Some(kind), Some((_, n))) => {
+                        self.error(format!("the value of constant `{}` is {}, which does not fit in {}", name, n, kind), value.line);
+                        result
+                    }
+                    _ => result,
+                };
+                self.constants.insert(name.to_string(), result);
+            }
+            Err(reason) => self.error(
+                format!("the value of constant `{}` must be known before the program runs: {}", name, reason),
+                value.line,
+            ),
+        }
+        self.scopes[0].insert(
+            name.to_string(),
+            Binding {
+                ty: declared,
+                narrowed_from: None,
+                mutable: false,
+                line,
+                constant: true,
+                loop_variable: false,
+            },
+        );
+    }
+
+    /// Evaluates a constant's initializer. Only literals, earlier constants and operators on
+    /// numbers, strings and bools are allowed. Literals in arithmetic take the declared type, as
+    /// they do when the program runs.
+    fn const_value(&self, expr: &Expr, declared: &Type) -> Result<Value, String> {
+        let value = match &expr.kind {
+            ExprKind::Integer(i, _) => match self.prune(declared) {
+                Type::Sized(kind) if kind.fits(*i) => Value::integer(kind, *i),
+                Type::BigInt => Value::Big(BigInt::from_i128(*i)),
+                _ => Value::literal(*i),
+            },
+            ExprKind::SizedInteger(i, kind) => Value::integer(*kind, *i),
+            ExprKind::BigInteger(n) => Value::Big(n.clone()),
+            ExprKind::Float(x) => Value::Float(*x),
+            ExprKind::Str(s) => Value::Str(s.clone()),
+            ExprKind::Bool(b) => Value::Bool(*b),
+            ExprKind::Identifier(name) => match self.constants.get(name) {
+                Some(value) => value.clone(),
+                None => return Err(format!("`{}` is not a constant", name)),
+            },
+            ExprKind::Unary(op, operand) => match (op, self.const_value(operand, declared)?) {
+                (UnaryOp::Neg, value @ (Value::Int(_) | Value::Sized(..))) => {
+                    let (kind, n) = value.as_integer().unwrap();
+                    Value::integer(kind, ints::arithmetic(BinaryOp::Sub, kind, 0, n)?)
+                }
+                (UnaryOp::Neg, Value::Float(x)) => Value::Float(-x),
+                (UnaryOp::Neg, Value::Big(n)) => Value::Big(-&n),
+                (UnaryOp::BitNot, value @ (Value::Int(_) | Value::Sized(..))) => {
+                    let (kind, n) = value.as_integer().unwrap();
+                    Value::integer(kind, kind.not(n))
+                }
+                (UnaryOp::Not, Value::Bool(b)) => Value::Bool(!b),
+                _ => return Err("invalid operand".to_string()),
+            },
+            ExprKind::Binary(op, left, right) => {
+                let operand_type = if op.is_arithmetic() { declared } else { &Type::Error };
+                let left = self.const_value(left, operand_type)?;
+                let amount_type = if op.is_shift_or_power() { &Type::Error } else { operand_type };
+                let right = self.const_value(right, amount_type)?;
+                match (op, left, right) {
+                    (BinaryOp::Eq, a, b) => Value::Bool(a == b),
+                    (BinaryOp::Ne, a, b) => Value::Bool(a != b),
+                    (BinaryOp::And, Value::Bool(a), Value::Bool(b)) => Value::Bool(a && b),
+                    (BinaryOp::Or, Value::Bool(a), Value::Bool(b)) => Value::Bool(a || b),
+                    (op, left @ Value::Big(_), right) | (op, left, right @ Value::Big(_)) => {
+                        let (a, b) = match (left.as_bigint(), right.as_bigint()) {
+                            (Some(a), Some(b)) => (a, b),
+                            _ => return Err(format!("`{}` cannot be worked out here", op)),
+                        };
+                        if op.is_arithmetic() {
+                            Value::Big(bigint::arithmetic(*op, &a, &b)?)
+                        } else {
+                            Value::Bool(const_compare(*op, a.partial_cmp(&b)))
+                        }
+                    }
+                    // a shift or a power keeps the type of its left side
+                    (op, left, right) if op.is_shift_or_power() && Value::integers(&left, &right).is_some() => {
+                        let ((kind, a), (_, b)) = (left.as_integer().unwrap(), right.as_integer().unwrap());
+                        Value::integer(kind, ints::arithmetic(*op, kind, a, b)?)
+                    }
+                    (BinaryOp::Pow, Value::Float(a), Value::Int(b)) => Value::Float(a.powi(b)),
+                    (op, left, right) if Value::integers(&left, &right).is_some() => {
+                        let (kind, a, b) = Value::integers(&left, &right).unwrap();
+                        if op.is_arithmetic() {
+                            Value::integer(kind, ints::arithmetic(*op, kind, a, b)?)
+                        } else {
+                            Value::Bool(const_compare(*op, a.partial_cmp(&b)))
+                        }
+                    }
+                    (op, Value::Float(a), Value::Float(b)) => match op {
+                        BinaryOp::Add => Value::Float(a + b),
+                        BinaryOp::Sub => Value::Float(a - b),
+                        BinaryOp::Mul => Value::Float(a * b),
+                        BinaryOp::Div => Value::Float(a / b),
+                        BinaryOp::Mod => Value::Float(a % b),
+                        BinaryOp::Pow => Value::Float(a.powf(b)),
+                        _ => Value::Bool(const_compare(*op, a.partial_cmp(&b))),
+                    },
+                    (BinaryOp::Add, Value::Str(a), Value::Str(b)) => Value::Str(a + &b),
+                    (op, Value::Str(a), Value::Str(b)) => {
+                        Value::Bool(const_compare(*op, a.partial_cmp(&b)))
+                    }
+                    _ => return Err(format!("`{}` cannot be worked out here", op)),
+                }
+            }
+            _ => return Err("only literals, other constants and operators can be used".to_string()),
+        };
+        Ok(value)
+    }
+
+    fn check_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> Type {
+        let line = expr.line;
+        let ty = match &expr.kind {
+            // an unsuffixed literal takes the sized type it's expected to have, or the one inside
+            // an optional, and the literal keeps it for the interpreter
+            ExprKind::Integer(int, literal) => {
+                let expected = match expected.map(|e| self.prune(e)) {
+                    Some(Type::Optional(inner)) => Some(self.prune(&inner)),
+                    expected => expected,
+                };
+                let (ty, numeric) = match expected {
+                    Some(Type::Sized(kind)) => (self.check_literal(*int, kind, line), Numeric::Int(kind)),
+                    Some(Type::BigInt) => (Type::BigInt, Numeric::Big),
+                    _ => (self.check_literal(*int, IntKind::I32, line), Numeric::Int(IntKind::I32)),
+                };
+                literal.set(numeric);
+                ty
+            }
+            ExprKind::SizedInteger(int, kind) => self.check_literal(*int, *kind, line),
+            ExprKind::BigInteger(_) => Type::BigInt,
+            ExprKind::Float(_) => Type::Float,
+            ExprKind::Str(_) => Type::Str,
+            ExprKind::Interpolated(parts) => {
+                for part in parts {
+                    if let StringPart::Value(value, spec) = part {
+                        let ty = self.check_expr(value, None);
+                        self.check_format_spec(spec, &ty, value.line);
+                        if self.runs_methods(&ty) {
+                            self.forget_global_narrowings();
+                        }
+                    }
+                }
+                Type::Str
+            }
+            ExprKind::Bool(_) => Type::Bool,
+            ExprKind::None => match expected.map(|e| self.prune(e)) {
+                Some(optional @ Type::Optional(_)) => optional,
+                _ => Type::Optional(Box::new(self.fresh_var())),
+            },
+            ExprKind::Identifier(name) => match self.lookup(name) {
+                Some(ty) => ty,
+                None => {
+                    self.error(format!("undefined variable `{}`", name), line);
+                    Type::Error
+                }
+            },
+            ExprKind::List(items) => {
+                let elem = match expected.map(|e| self.prune(e)) {
+                    Some(Type::List(elem)) => *elem,
+                    _ => self.fresh_var(),
+                };
+                if let (true, Type::Var(v)) = (items.is_empty(), &elem) {
+                    self.pending.push(PendingInference {
+                        var: *v,
+                        message: "cannot infer the element type of an empty list; declare it, e.g. `xs:int[] = []`".to_string(),
+                        line,
+                    });
+                }
+                for item in items {
+                    let found = self.check_expr(item, Some(&elem));
+                    self.expect_type(&elem, &found, "list item", item.line);
+                }
+                Type::List(Box::new(elem))
+            }
+            ExprKind::Tuple(items) => {
+                let expected_items = match expected.map(|e| self.prune(e)) {
+                    Some(Type::Tuple(types)) if types.len() == items.len() => types,
+                    _ => Vec::new(),
+                };
+                let mut types = Vec::new();
+                for (i, item) in items.iter().enumerate() {
+                    let found = self.check_expr(item, expected_items.get(i));
+                    if found == Type::Void {
+                        self.error("a tuple cannot hold a void value", item.line);
+                    }
+                    types.push(found);
+                }
+                Type::Tuple(types)
+            }
+            ExprKind::Map(entries) => {
+                let (key, value) = match expected.map(|e| self.prune(e)) {
+                    Some(Type::Map(key, value)) => (*key, *value),
+                    _ => (self.fresh_var(), self.fresh_var()),
+                };
+                if entries.is_empty() {
+                    for var in [&key, &value] {
+                        if let Type::Var(v) = var {
+                            self.pending.push(PendingInference {
+                                var: *v,
+                                message: "cannot infer the type of an empty map; declare it, e.g. `m:map<str, int> = [:]`".to_string(),
+                                line,
+                            });
+                        }
+                    }
+                }
+                for (k, v) in entries {
+                    let found = self.check_expr(k, Some(&key));
+                    self.expect_type(&key, &found, "map key", k.line);
+                    let found = self.check_expr(v, Some(&value));
+                    self.expect_type(&value, &found, "map value", v.line);
+                }
+                Type::Map(Box::new(key), Box::new(value))
+            }
+            ExprKind::Unary(op, operand) => {
+                // `x:u8 = ~0` makes the literal a u8
+                let found = self.check_expr(operand, if *op == UnaryOp::Not { None } else { expected });
+                let found = self.prune(&found);
+                match (op, &found) {
+                    (_, Type::Error) => Type::Error,
+                    (UnaryOp::Neg, Type::Int | Type::BigInt | Type::Float) => found,
+                    (UnaryOp::Neg, Type::Sized(kind)) if kind.signed() => found,
+                    (UnaryOp::Not, Type::Bool) => Type::Bool,
+                    (UnaryOp::BitNot, Type::Int | Type::Sized(_)) => found,
+                    (UnaryOp::Neg, Type::Struct(..) | Type::Param(_)) if self.implements(&found, "Neg") => {
+                        self.forget_global_narrowings();
+                        found
+                    }
+                    (UnaryOp::Neg, Type::Struct(..) | Type::Param(_)) => {
+                        self.error(
+                            format!("cannot negate a value of type {}, it does not implement `Neg`", found),
+                            line,
+                        );
+                        Type::Error
+                    }
+                    (UnaryOp::Neg, _) => {
+                        self.error(format!("cannot negate a value of type {}", found), line);
+                        Type::Error
+                    }
+                    (UnaryOp::Not, _) => {
+                        self.error(format!("`!` expects a bool, found {}", found), line);
+                        Type::Error
+                    }
+                    (UnaryOp::BitNot, _) => {
+                        self.error(format!("`~` expects an integer, found {}", found), line);
+                        Type::Error
+                    }
+                }
+            }
+            ExprKind::Cast(operand, ty) => {
+                let from = self.check_expr(operand, None);
+                let from = self.prune(&from);
+                let to = self.resolve_type(ty, line);
+                let numeric = |ty: &Type| {
+                    matches!(ty, Type::Int | Type::Sized(_) | Type::BigInt | Type::Float | Type::Error)
+                };
+                if !numeric(&from) || !numeric(&to) {
+                    self.error(
+                        format!("cannot cast {} to {}, `as` only converts between number types", from, to),
+                        line,
+                    );
+                    Type::Error
+                } else {
+                    to
+                }
+            }
+            ExprKind::Binary(op, left, right) => self.check_binary(*op, left, right, expected, line),
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                type_args,
+                args,
+            } => {
+                let ty = self.check_dot_call(receiver, method, type_args, args, expected, line);
+                self.forget_global_narrowings();
+                ty
+            }
+            ExprKind::Call {
+                name,
+                type_args,
+                args,
+            } => {
+                let ty = self.check_call(name, type_args, args, expected, line);
+                self.forget_global_narrowings();
+                ty
+            }
+            ExprKind::Index(base, index) => {
+                let base_ty = self.check_expr(base, None);
+                match self.prune(&base_ty) {
+                    Type::List(elem) => {
+                        let found = self.check_expr(index, Some(&Type::Int));
+                        self.expect_type(&Type::Int, &found, "list index", line);
+                        *elem
+                    }
+                    Type::Str => {
+                        let found = self.check_expr(index, Some(&Type::Int));
+                        self.expect_type(&Type::Int, &found, "string index", line);
+                        Type::Str
+                    }
+                    Type::Map(key, value) => {
+                        let found = self.check_expr(index, Some(&key));
+                        self.expect_type(&key, &found, "map key", line);
+                        *value
+                    }
+                    Type::Error => Type::Error,
+                    other => {
+                        self.error(format!("cannot index into a value of type {}", other), line);
+                        Type::Error
+                    }
+                }
+            }
+            ExprKind::Propagate(inner) => self.check_propagate(inner, line),
+            ExprKind::NamedArg(name, value) => {
+                self.error(
+                    format!("`{}:` can only name an argument of a function declared in the program", name),
+                    line,
+                );
+                self.check_expr(value, None);
+                Type::Error
+            }
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => self.check_if_expression(cond, then_branch, else_branch.as_deref(), expected, line),
+            ExprKind::Block(statements) => self.check_value_block(statements, expected, Vec::new()),
+            ExprKind::Range {
+                start, end, step, ..
+            } => {
+                for (bound, context) in [(start, "start of range"), (end, "end of range")] {
+                    let found = self.check_expr(bound, Some(&Type::Int));
+                    self.expect_type(&Type::Int, &found, context, bound.line);
+                }
+                if let Some(step) = step {
+                    let found = self.check_expr(step, Some(&Type::Int));
+                    self.expect_type(&Type::Int, &found, "range step", step.line);
+                }
+                Type::Range
+            }
+            ExprKind::Field(base, field) => {
+                let base_ty = self.check_expr(base, None);
+                match self.prune(&base_ty) {
+                    Type::Struct(name, args) => match self.field_type(&name, &args, field) {
+                        Some(ty) => ty,
+                        None => {
+                            self.error(format!("struct `{}` has no field `{}`", name, field), line);
+                            Type::Error
+                        }
+                    },
+                    Type::Tuple(items) if field.parse::<usize>().is_ok() => {
+                        match items.get(field.parse::<usize>().unwrap()) {
+                            Some(item) => item.clone(),
+                            None => {
+                                self.error(
+                                    format!("a tuple of {} items has no item {}", items.len(), field),
+                                    line,
+                                );
+                                Type::Error
+                            }
+                        }
+                    }
+                    Type::Error => Type::Error,
+                    other => {
+                        self.error(format!("type {} has no field `{}`", other, field), line);
+                        Type::Error
+                    }
+                }
+            }
+            ExprKind::StructLiteral {
+                name,
+                type_args,
+                fields,
+            } => self.check_struct_literal(name, type_args, fields, expected, line),
+        };
+        self.prune(&ty)
+    }
+
+    fn field_type(&mut self, name: &str, args: &[Type], field: &str) -> Option<Type> {
+        let decl = self.structs.get(name)?.clone();
+        let (_, ty) = decl.fields.iter().find(|(f, _)| f == field)?;
+        let saved = std::mem::replace(&mut self.type_params, decl.type_params.clone());
+        let ty = self.resolve_type(ty, decl.line);
+        self.type_params = saved;
+        let mapping: HashMap<String, Type> = decl
+            .type_params
+            .iter()
+            .map(|p| p.name.clone())
+            .zip(args.iter().cloned())
+            .collect();
+        Some(Self::substitute(&ty, &mapping))
+    }
+
+    fn check_struct_literal(
+        &mut self,
+        name: &str,
+        type_args: &[TypeExpr],
+        fields: &[(String, Expr)],
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        let decl = match self.structs.get(name) {
+            Some(decl) => decl.clone(),
+            None => {
+                self.error(format!("unknown struct `{}`", name), line);
+                for (_, value) in fields {
+                    self.check_expr(value, None);
+                }
+                return Type::Error;
+            }
+        };
+        let mapping = self.instantiate(
+            &decl.type_params,
+            type_args,
+            &format!("struct `{}`", name),
+            &format!("{}<{}> {{ ... }}", name, param_names(&decl.type_params)),
+            line,
+        );
+        let args: Vec<Type> = decl.type_params.iter().map(|p| mapping[&p.name].clone()).collect();
+        let ty = Type::Struct(name.to_string(), args.clone());
+        let mut seen: Vec<&str> = Vec::new();
+        for (field, value) in fields {
+            if seen.contains(&field.as_str()) {
+                self.error(format!("field `{}` is given twice", field), value.line);
+            }
+            seen.push(field);
+            match self.field_type(name, &args, field) {
+                Some(field_ty) => {
+                    let found = self.check_expr(value, Some(&field_ty));
+                    self.expect_type(&field_ty, &found, &format!("field `{}`", field), value.line);
+                }
+                None => {
+                    self.error(format!("struct `{}` has no field `{}`", name, field), value.line);
+                    self.check_expr(value, None);
+                }
+            }
+        }
+        for (field, _) in &decl.fields {
+            if !seen.contains(&field.as_str()) {
+                self.error(format!("missing field `{}` in `{}`", field, name), line);
+            }
+        }
+        if let Some(expected) = expected {
+            // a hint only; the caller reports the mismatch if there is one
+            let snapshot = self.substitution.clone();
+            if !self.unify(expected, &ty) {
+                self.substitution = snapshot;
+            }
+        }
+        ty
+    }
+
+    fn check_call(
+        &mut self,
+        name: &str,
+        type_args: &[TypeExpr],
+        args: &[Expr],
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        if let Some(ty) = self.check_builtin(name, args, expected, line) {
+            return ty;
+        }
+        let decl = match self.functions.get(name) {
+            Some(decl) => decl.clone(),
+            None if self.traits.values().any(|t| t.methods.iter().any(|m| m.name == name)) => {
+                return self.check_method_call(name, args, line);
+            }
+            None => {
+                self.error(format!("undefined function `{}`", name), line);
+                for arg in args {
+                    self.check_expr(arg, None);
+                }
+                return Type::Error;
+            }
+        };
+        let mapping = self.instantiate(
+            &decl.type_params,
+            type_args,
+            &format!("`{}`", name),
+            &format!("{}<{}>(...)", name, param_names(&decl.type_params)),
+            line,
+        );
+        // the interpreter doesn't keep types, so a native that makes a value of a type none of its
+        // arguments have, like `json.parse<T>`, needs that type written out in full
+        if decl.native {
+            let unbound = decl
+                .type_params
+                .iter()
+                .any(|tp| !decl.params.iter().any(|p| mentions(&p.ty, &tp.name)));
+            if unbound && type_args.is_empty() {
+                self.error(
+                    format!("`{}` needs its type written out, e.g. `{}<Config>(...)`", name, name),
+                    line,
+                );
+            } else if unbound && type_args.iter().any(|arg| self.type_params.iter().any(|tp| mentions(arg, &tp.name))) {
+                self.error(
+                    format!("`{}` cannot be given a type parameter, the type has to be known when it runs", name),
+                    line,
+                );
+            }
+        }
+        let saved = std::mem::replace(&mut self.type_params, decl.type_params.clone());
+        let params: Vec<Type> = decl
+            .params
+            .iter()
+            .map(|p| self.resolve_type(&p.ty, decl.line))
+            .collect();
+        let ret = self.resolve_type(&decl.ret, decl.line);
+        self.type_params = saved;
+
+        let ret = Self::substitute(&ret, &mapping);
+        // where the result goes says what type parameters it has before the arguments are
+        // looked at, so in `a:u8 = id(200)` the literal is a u8. if it doesn't fit the
+        // arguments decide and the mismatch is reported below
+        if let Some(expected) = expected {
+            let snapshot = self.substitution.clone();
+            if !self.unify(expected, &ret) {
+                self.substitution = snapshot;
+            }
+        }
+        for (arg, index) in self.match_args(&decl, args, line) {
+            let Some(i) = index else {
+                self.check_expr(arg, None);
+                continue;
+            };
+            let param = &decl.params[i];
+            // each argument of a variadic parameter is one item of its list
+            let param_ty = match Self::substitute(&params[i], &mapping) {
+                Type::List(item) if param.variadic => *item,
+                ty => ty,
+            };
+            let found = self.check_expr(arg, Some(&param_ty));
+            self.expect_type(&param_ty, &found, &format!("argument `{}` of `{}`", param.name, name), arg.line);
+            // a native changes a `mut` parameter in place, like `sort` does, and so does any
+            // function with a `mut` list, map or struct, which it shares with the caller
+            if decl.native && param.mutable {
+                self.check_mutable(arg, unqualified(name), arg.line);
+            } else if param.mutable && self.is_shared(&param_ty) {
+                self.check_mutable(arg, &format!("let `{}` change", name), arg.line);
+            }
+        }
+        // whatever the arguments left open can still be inferred from where the result goes
+        if let Some(expected) = expected {
+            let snapshot = self.substitution.clone();
+            if !self.unify(expected, &ret) {
+                self.substitution = snapshot;
+            }
+        }
+        ret
+    }
+
+    // pairs each argument, with the name taken off a named one, with the index of its parameter.
+    // positional arguments fill the parameters in order and the leftovers go to a variadic one;
+    // a parameter left out needs a default
+    fn match_args<'a>(&mut self, decl: &FunctionDecl, args: &'a [Expr], line: usize) -> Vec<(&'a Expr, Option<usize>)> {
+        let variadic = decl.params.last().is_some_and(|p| p.variadic);
+        let fixed = decl.params.len() - variadic as usize;
+        let mut given = vec![false; decl.params.len()];
+        let mut matched = Vec::new();
+        let mut positional = 0;
+        let mut named = false;
+        for arg in args {
+            let index = match &arg.kind {
+                ExprKind::NamedArg(param, value) => {
+                    named = true;
+                    let index = decl.params.iter().position(|p| p.name == *param);
+                    match index {
+                        Some(i) if decl.params[i].variadic => self.error(
+                            format!("the variadic parameter `{}` of `{}` cannot be named", param, decl.name),
+                            arg.line,
+                        ),
+                        Some(i) if given[i] => self.error(
+                            format!("argument `{}` of `{}` is given more than once", param, decl.name),
+                            arg.line,
+                        ),
+                        Some(i) => given[i] = true,
+                        None => self.error(format!("function `{}` has no parameter `{}`", decl.name, param), arg.line),
+                    }
+                    matched.push((&**value, index.filter(|&i| !decl.params[i].variadic)));
+                    continue;
+                }
+                _ if named => {
+                    self.error("positional arguments must come before named ones", arg.line);
+                    None
+                }
+                _ if positional < fixed => Some(positional),
+                _ if variadic => Some(fixed),
+                _ => None,
+            };
+            positional += 1;
+            if let Some(i) = index {
+                given[i] = true;
+            }
+            matched.push((arg, index));
+        }
+        let missing: Vec<&Param> = decl.params[..fixed]
+            .iter()
+            .zip(&given)
+            .filter(|(p, given)| !**given && p.default.is_none())
+            .map(|(p, _)| p)
+            .collect();
+        // a function without defaults, variadics or named arguments counts its arguments like before
+        let plain = !named && decl.params.iter().all(|p| p.default.is_none() && !p.variadic);
+        if (positional > fixed && !variadic) || (plain && !missing.is_empty()) {
+            self.error(
+                format!(
+                    "function `{}` takes {} argument(s) but {} were given",
+                    decl.name,
+                    decl.params.len(),
+                    args.len()
+                ),
+                line,
+            );
+        } else {
+            for param in missing {
+                self.error(format!("missing argument `{}` of `{}`", param.name, decl.name), line);
+            }
+        }
+        matched
+    }
+
+    // `receiver.method(args)`. the builtin methods of the receiver's type come first, anything else
+    // is `method(receiver, args)`: a function, a trait method or a builtin function like `len`
+    fn check_dot_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        type_args: &[TypeExpr],
+        args: &[Expr],
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        let receiver_ty = self.check_expr(receiver, None);
+        let receiver_ty = self.prune(&receiver_ty);
+        if receiver_ty == Type::Error {
+            for arg in args {
+                self.check_expr(arg, None);
+            }
+            return Type::Error;
+        }
+        if let Some(ty) = self.check_builtin_method(receiver, &receiver_ty, unqualified(method), args, line) {
+            return ty;
+        }
+        let callable = BUILTINS.contains(&method)
+            || self.functions.contains_key(method)
+            || self.traits.values().any(|t| t.methods.iter().any(|m| m.name == method));
+        if !callable {
+            self.error(format!("{} has no method `{}`", receiver_ty, method), line);
+            for arg in args {
+                self.check_expr(arg, None);
+            }
+            return Type::Error;
+        }
+        let mut all_args = vec![receiver.clone()];
+        all_args.extend(args.iter().cloned());
+        self.check_call(method, type_args, &all_args, expected, line)
+    }
+
+    // the methods every `str`, number, list and map has. `None` when `method` isn't one of them
+    fn check_builtin_method(
+        &mut self,
+        receiver: &Expr,
+        receiver_ty: &Type,
+        method: &str,
+        args: &[Expr],
+        line: usize,
+    ) -> Option<Type> {
+        let ty = receiver_ty.clone();
+        // the parameter types, the return type and whether the method changes the receiver
+        let (params, ret, mutates) = match (receiver_ty, method) {
+            (Type::Str, "trim" | "trim_start" | "trim_end" | "to_upper" | "to_lower") => (vec![], Type::Str, false),
+            (Type::Str, "split") => (vec![Type::Str], Type::List(Box::new(Type::Str)), false),
+            (Type::Str, "contains" | "starts_with" | "ends_with") => (vec![Type::Str], Type::Bool, false),
+            (Type::Str, "byte_len") => (vec![], Type::Int, false),
+            (Type::Str, "chars") => (vec![], Type::List(Box::new(Type::Str)), false),
+            (Type::Str, "slice") => (vec![Type::Int, Type::Int], Type::Str, false),
+            (Type::Str, "find") => (vec![Type::Str], Type::Optional(Box::new(Type::Int)), false),
+            (Type::Str, "replace") => (vec![Type::Str, Type::Str], Type::Str, false),
+            (Type::Str, "repeat") => (vec![Type::Int], Type::Str, false),
+            (Type::Str, "parse_int") => (vec![], Type::Result(Box::new(Type::Int), Box::new(Type::Str)), false),
+            (Type::Str, "parse_float") => (vec![], Type::Result(Box::new(Type::Float), Box::new(Type::Str)), false),
+            (Type::List(elem), "join") if **elem == Type::Str => (vec![Type::Str], Type::Str, false),
+            (Type::Int | Type::Sized(_) | Type::BigInt | Type::Float, "abs") => (vec![], ty, false),
+            (Type::Int | Type::Sized(_) | Type::BigInt | Type::Float, "min" | "max") => (vec![ty.clone()], ty, false),
+            (Type::Float, "round" | "floor" | "ceil" | "sqrt") => (vec![], Type::Float, false),
+            (Type::List(_), "is_empty") | (Type::Map(..), "is_empty") => (vec![], Type::Bool, false),
+            (Type::List(elem), "contains") => (vec![*elem.clone()], Type::Bool, false),
+            (Type::List(elem), "pop") => (vec![], Type::Optional(elem.clone()), true),
+            (Type::List(elem), "insert") => (vec![Type::Int, *elem.clone()], Type::Void, true),
+            (Type::List(elem), "remove") => (vec![Type::Int], *elem.clone(), true),
+            (Type::List(_), "reverse") => (vec![], Type::Void, true),
+            (Type::Map(key, _), "keys") => (vec![], Type::List(key.clone()), false),
+            (Type::Map(_, value), "values") => (vec![], Type::List(value.clone()), false),
+            (Type::Map(key, _), "contains_key") => (vec![*key.clone()], Type::Bool, false),
+            (Type::Map(key, value), "get") => (vec![*key.clone()], Type::Optional(value.clone()), false),
+            (Type::Map(key, value), "remove") => (vec![*key.clone()], Type::Optional(value.clone()), true),
+            _ => return None,
+        };
+        if args.len() != params.len() {
+            self.error(
+                format!(
+                    "method `{}` of {} takes {} argument(s) but {} were given",
+                    method,
+                    receiver_ty,
+                    params.len(),
+                    args.len()
+                ),
+                line,
+            );
+        }
+        for (arg, param) in args.iter().zip(&params) {
+            let found = self.check_expr(arg, Some(param));
+            self.expect_type(param, &found, &format!("argument of `{}`", method), arg.line);
+        }
+        if let (Type::List(elem), "contains") = (receiver_ty, method) {
+            if !self.implements(elem, "Eq") {
+                self.error(format!("`contains` needs {} to implement `Eq`", elem), line);
+            }
+        }
+        if mutates {
+            self.check_mutable(receiver, &format!("call `{}` on", method), line);
+        }
+        Some(ret)
+    }
+
+    // a call to a trait method, `show(p)`. the first argument picks the implementation.
+    fn check_method_call(&mut self, name: &str, args: &[Expr], line: usize) -> Type {
+        let receiver = match args.first() {
+            Some(receiver) => self.check_expr(receiver, None),
+            None => {
+                self.error(format!("trait method `{}` needs a receiver as its first argument", name), line);
+                return Type::Error;
+            }
+        };
+        let receiver = self.prune(&receiver);
+        if receiver == Type::Error {
+            for arg in &args[1..] {
+                self.check_expr(arg, None);
+            }
+            return Type::Error;
+        }
+        let mut candidates: Vec<(String, TraitMethod)> = self
+            .traits
+            .values()
+            .flat_map(|t| {
+                t.methods
+                    .iter()
+                    .filter(|m| m.name == name)
+                    .map(|m| (t.name.clone(), m.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0));
+        let trait_names: Vec<String> = candidates.iter().map(|(t, _)| t.clone()).collect();
+        let implemented: Vec<(String, TraitMethod)> = candidates
+            .into_iter()
+            .filter(|(trait_name, _)| self.implements(&receiver, trait_name))
+            .collect();
+        let (trait_name, method) = match implemented.as_slice() {
+            [one] => one.clone(),
+            [] => {
+                self.error(
+                    format!(
+                        "{} does not implement `{}`, so `{}` cannot be called on it",
+                        receiver,
+                        trait_names.join("` or `"),
+                        name
+                    ),
+                    line,
+                );
+                for arg in &args[1..] {
+                    self.check_expr(arg, None);
+                }
+                return Type::Error;
+            }
+            _ => {
+                let names: Vec<&str> = implemented.iter().map(|(t, _)| t.as_str()).collect();
+                self.error(
+                    format!("`{}` is ambiguous for {}, it is a method of `{}`", name, receiver, names.join("` and `")),
+                    line,
+                );
+                return Type::Error;
+            }
+        };
+        if args.len() != method.params.len() {
+            self.error(
+                format!(
+                    "`{}` of trait `{}` takes {} argument(s) but {} were given",
+                    name,
+                    trait_name,
+                    method.params.len(),
+                    args.len()
+                ),
+                line,
+            );
+        }
+        // a generic trait's type arguments come from the receiver's impl, or are left to
+        // inference when the receiver is a type parameter
+        let trait_params = self.traits[&trait_name].type_params.clone();
+        let trait_args = match self.impl_trait_args(&receiver, &trait_name) {
+            Some(args) => args,
+            None => trait_params.iter().map(|_| self.fresh_var()).collect(),
+        };
+        let mapping: HashMap<String, Type> = trait_params
+            .iter()
+            .map(|p| p.name.clone())
+            .zip(trait_args)
+            .collect();
+        let saved = self.self_type.replace(receiver.clone());
+        let saved_params = std::mem::replace(&mut self.type_params, trait_params);
+        let params: Vec<Type> = method
+            .params
+            .iter()
+            .map(|p| Self::substitute(&self.resolve_type(&p.ty, method.line), &mapping))
+            .collect();
+        let ret = Self::substitute(&self.resolve_type(&method.ret, method.line), &mapping);
+        self.self_type = saved;
+        self.type_params = saved_params;
+        for (arg, (param, param_ty)) in args.iter().zip(method.params.iter().zip(&params)).skip(1) {
+            let found = self.check_expr(arg, Some(param_ty));
+            self.expect_type(param_ty, &found, &format!("argument `{}` of `{}`", param.name, name), arg.line);
+        }
+        for (arg, (param, param_ty)) in args.iter().zip(method.params.iter().zip(&params)) {
+            if param.mutable && self.is_shared(param_ty) {
+                self.check_mutable(arg, &format!("let `{}` change", name), arg.line);
+            }
+        }
+        ret
+    }
+
+    // lists, maps and structs are shared when they're passed or stored somewhere else, never
+    // copied, so a change through one name shows through every other
+    fn is_shared(&mut self, ty: &Type) -> bool {
+        match self.prune(ty) {
+            Type::List(_) | Type::Map(..) | Type::Struct(..) | Type::Param(_) => true,
+            Type::Optional(inner) => self.is_shared(&inner),
+            Type::Result(ok, err) => self.is_shared(&ok) || self.is_shared(&err),
+            Type::Tuple(items) => items.iter().any(|item| self.is_shared(item)),
+            _ => false,
+        }
+    }
+
+    // comparing, printing or doing arithmetic on a struct can run its `eq`, `show`, `add`...,
+    // which are calls like any other
+    fn runs_methods(&mut self, ty: &Type) -> bool {
+        match self.prune(ty) {
+            Type::Struct(..) | Type::Param(_) => true,
+            Type::List(inner) | Type::Optional(inner) => self.runs_methods(&inner),
+            Type::Map(a, b) | Type::Result(a, b) => self.runs_methods(&a) || self.runs_methods(&b),
+            Type::Tuple(items) => items.iter().any(|item| self.runs_methods(item)),
+            _ => false,
+        }
+    }
+
+    fn check_builtin(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Option<Type> {
+        if name == "format" {
+            return Some(self.check_format(args, line));
+        }
+        let arity = match name {
+            "len" | "sort" | "ok" | "err" | "is_ok" | "is_err" | "unwrap" | "unwrap_err" => 1,
+            "parse_bigint" | "to_str" | "input" => 1,
+            "push" | "unwrap_or" | "pow" => 2,
+            "read_line" | "read_all" => 0,
+            _ => return None,
+        };
+        // `ok()` is the ok of a `Result<void, E>`
+        if args.len() != arity && !(name == "ok" && args.is_empty()) {
+            self.error(
+                format!("`{}` takes {} argument(s) but {} were given", name, arity, args.len()),
+                line,
+            );
+            for arg in args {
+                self.check_expr(arg, None);
+            }
+            return Some(Type::Error);
+        }
+        if name == "ok" || name == "err" {
+            return Some(self.check_result_constructor(name, args.first(), expected, line));
+        }
+        // reading standard input, none once it has all been read
+        match name {
+            "read_line" => return Some(Type::Optional(Box::new(Type::Str))),
+            "read_all" => return Some(Type::Str),
+            _ => {}
+        }
+        let first = self.check_expr(&args[0], None);
+        let first = self.prune(&first);
+        let ty = match (name, &first) {
+            (_, Type::Error) => {
+                for arg in &args[1..] {
+                    self.check_expr(arg, None);
+                }
+                Type::Error
+            }
+            ("len", Type::List(_) | Type::Str | Type::Map(..)) => Type::Int,
+            ("sort", Type::List(elem)) => {
+                self.check_mutable(&args[0], "sort", line);
+                if !self.implements(elem, "Ord") {
+                    self.error(format!("cannot sort {} because {} does not implement `Ord`", first, elem), line);
+                }
+                Type::Void
+            }
+            ("push", Type::List(elem)) => {
+                self.check_mutable(&args[0], "push onto", line);
+                let found = self.check_expr(&args[1], Some(elem));
+                self.expect_type(elem, &found, "pushed value", args[1].line);
+                Type::Void
+            }
+            ("pow", Type::Int | Type::Sized(_) | Type::BigInt | Type::Float) => {
+                let found = self.check_expr(&args[1], Some(&Type::Int));
+                self.expect_type(&Type::Int, &found, "exponent", args[1].line);
+                first.clone()
+            }
+            ("parse_bigint", Type::Str) => Type::Result(Box::new(Type::BigInt), Box::new(Type::Str)),
+            ("to_str", ty) if *ty != Type::Void => Type::Str,
+            ("input", Type::Str) => Type::Optional(Box::new(Type::Str)),
+            ("is_ok" | "is_err", Type::Result(..)) => Type::Bool,
+            ("unwrap", Type::Result(value, _) | Type::Optional(value)) => *value.clone(),
+            ("unwrap_err", Type::Result(_, error)) => *error.clone(),
+            ("unwrap_or", Type::Result(value, _) | Type::Optional(value)) => {
+                let found = self.check_expr(&args[1], Some(value));
+                self.expect_type(value, &found, "default value", args[1].line);
+                *value.clone()
+            }
+            _ => {
+                self.error(format!("`{}` cannot be used on a value of type {}", name, first), line);
+                Type::Error
+            }
+        };
+        Some(ty)
+    }
+
+    // `r?` hands an err back to the caller, so the function has to return a Result with the same
+    // error type. `x?` on an optional does the same with none.
+    fn check_propagate(&mut self, inner: &Expr, line: usize) -> Type {
+        let inner_ty = self.check_expr(inner, None);
+        let ret = match self.return_type.clone() {
+            Some(ret) => self.prune(&ret),
+            None => {
+                self.error("`?` can only be used inside a function", line);
+                return Type::Error;
+            }
+        };
+        match (self.prune(&inner_ty), ret) {
+            (Type::Error, _) | (_, Type::Error) => Type::Error,
+            (Type::Result(value, error), Type::Result(_, ret_error)) => {
+                if !self.unify(&ret_error, &error) {
+                    let error = self.prune(&error);
+                    self.error(
+                        format!(
+                            "`?` would return an err of type {} from a function whose errors are {}",
+                            error, ret_error
+                        ),
+                        line,
+                    );
+                }
+                *value
+            }
+            (Type::Optional(value), Type::Optional(_)) => *value,
+            (found @ (Type::Result(..) | Type::Optional(_)), ret) => {
+                self.error(
+                    format!(
+                        "`?` on a {} needs the function to return {}, but it returns {}",
+                        found,
+                        if matches!(found, Type::Result(..)) { "a Result" } else { "an optional" },
+                        ret
+                    ),
+                    line,
+                );
+                Type::Error
+            }
+            (other, _) => {
+                self.error(format!("`?` can only be used on a Result or an optional, found {}", other), line);
+                Type::Error
+            }
+        }
+    }
+
+    // a template that is a string literal is checked against the values right away, otherwise
+    // mistakes in it are runtime errors
+    fn check_format(&mut self, args: &[Expr], line: usize) -> Type {
+        let types: Vec<Type> = args.iter().map(|arg| self.check_expr(arg, None)).collect();
+        let (template, values) = match (args.first(), types.split_first()) {
+            (Some(template), Some((template_ty, values))) => {
+                self.expect_type(&Type::Str, template_ty, "format string", template.line);
+                (template, values)
+            }
+            _ => {
+                self.error("`format` needs a template string, e.g. `format(\"{}\", x)`", line);
+                return Type::Error;
+            }
+        };
+        if let ExprKind::Str(template) = &template.kind {
+            let specs = match parse_template(template) {
+                Ok(pieces) => pieces.into_iter().filter_map(|piece| match piece {
+                    Piece::Placeholder(spec) => Some(spec),
+                    Piece::Text(_) => None,
+                }),
+                Err(message) => {
+                    self.error(message, line);
+                    return Type::Str;
+                }
+            };
+            let specs: Vec<FormatSpec> = specs.collect();
+            if specs.len() != values.len() {
+                self.error(
+                    format!(
+                        "format string has {} placeholder(s) but {} value(s) were given",
+                        specs.len(),
+                        values.len()
+                    ),
+                    line,
+                );
+            }
+            for (spec, ty) in specs.iter().zip(values) {
+                self.check_format_spec(spec, ty, line);
+            }
+        } else {
+            for ty in values {
+                self.check_format_spec(&FormatSpec::default(), ty, line);
+            }
+        }
+        Type::Str
+    }
+
+    fn check_format_spec(&mut self, spec: &FormatSpec, ty: &Type, line: usize) {
+        let ty = self.prune(ty);
+        match ty {
+            Type::Void => self.error("cannot format a void value", line),
+            Type::Error | Type::Var(_) | Type::Param(_) => {}
+            _ if spec.radix.is_some() && int_kind(&ty).is_none() => {
+                self.error(format!("radix `{}` only applies to ints, found {}", spec.radix.unwrap(), ty), line)
+            }
+            _ if spec.precision.is_some() && int_kind(&ty).is_some() => {
+                self.error(format!("precision only applies to floats and strings, found {}", ty), line)
+            }
+            _ => {}
+        }
+    }
+
+    // `ok(v)` and `err(e)` take the other half of the Result from the type they're assigned to
+    fn check_result_constructor(
+        &mut self,
+        name: &str,
+        arg: Option<&Expr>,
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        let (value, error) = match expected.map(|e| self.prune(e)) {
+            Some(Type::Result(value, error)) => (*value, *error),
+            _ => (self.fresh_var(), self.fresh_var()),
+        };
+        let (known, unknown, what) = if name == "ok" {
+            (&value, &error, "error")
+        } else {
+            (&error, &value, "value")
+        };
+        if let Type::Var(v) = unknown {
+            self.pending.push(PendingInference {
+                var: *v,
+                message: format!(
+                    "cannot infer the {} type of `{}(...)`; declare it, e.g. `r:Result<int, str> = {}(...)`",
+                    what, name, name
+                ),
+                line,
+            });
+        }
+        match arg {
+            Some(arg) => {
+                let found = self.check_expr(arg, Some(known));
+                self.expect_type(known, &found, &format!("argument of `{}`", name), arg.line);
+            }
+            None => self.expect_type(known, &Type::Void, "`ok()`", line),
+        }
+        Type::Result(Box::new(value), Box::new(error))
+    }
+
+    // `a ?? b` is the inner type of `a`, or still optional when `b` can be none too
+    fn check_coalesce(&mut self, left: &Expr, right: &Expr, line: usize) -> Type {
+        let left_ty = self.check_expr(left, None);
+        match self.prune(&left_ty) {
+            Type::Optional(inner) => {
+                let right_ty = self.check_expr(right, Some(&inner));
+                let optional = Type::Optional(inner.clone());
+                if self.unify(&inner, &right_ty) {
+                    *inner
+                } else if self.unify(&optional, &right_ty) {
+                    optional
+                } else {
+                    let right_ty = self.prune(&right_ty);
+                    self.error(
+                        format!("the default after `??` must be a {}, found {}", inner, right_ty),
+                        line,
+                    );
+                    Type::Error
+                }
+            }
+            Type::Error => {
+                self.check_expr(right, None);
+                Type::Error
+            }
+            other => {
+                self.error(format!("`??` expects an optional value on its left, found {}", other), line);
+                self.check_expr(right, None);
+                Type::Error
+            }
+        }
+    }
+
+    // an integer literal has to fit in its type
+    fn check_literal(&mut self, int: i128, kind: IntKind, line: usize) -> Type {
+        if !kind.fits(int) {
+            let hint = if kind == IntKind::I32 { ", add a suffix like `i64` for a bigger type" } else { "" };
+            self.error(
+                format!(
+                    "literal `{}` does not fit in {}, whose values go from {} to {}{}",
+                    int,
+                    kind,
+                    kind.min(),
+                    kind.max(),
+                    hint
+                ),
+                line,
+            );
+        }
+        integer_type(kind)
+    }
+
+    // `<<`, `>>` and `**` give back the type of their left side. the right side is a shift amount
+    // or an exponent and can be any integer type, or a float for a float power
+    fn check_shift_or_power(
+        &mut self,
+        op: BinaryOp,
+        left: &Expr,
+        right: &Expr,
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        let left_ty = self.check_expr(left, expected);
+        let left_ty = self.prune(&left_ty);
+        let right_ty = self.check_expr(right, None);
+        let right_ty = self.prune(&right_ty);
+        if left_ty == Type::Error || right_ty == Type::Error {
+            return Type::Error;
+        }
+        let allowed = match (op, &left_ty) {
+            (BinaryOp::Pow, Type::Float) => matches!(right_ty, Type::Float | Type::Int),
+            (BinaryOp::Pow, Type::BigInt) => int_kind(&right_ty).is_some(),
+            _ => int_kind(&left_ty).is_some() && int_kind(&right_ty).is_some(),
+        };
+        if !allowed {
+            self.error(
+                format!("operator `{}` cannot be applied to {} and {}", op, left_ty, right_ty),
+                line,
+            );
+            return Type::Error;
+        }
+        left_ty
+    }
+
+    fn check_binary(
+        &mut self,
+        op: BinaryOp,
+        left: &Expr,
+        right: &Expr,
+        expected: Option<&Type>,
+        line: usize,
+    ) -> Type {
+        if op == BinaryOp::Coalesce {
+            return self.check_coalesce(left, right, line);
+        }
+        if op.is_shift_or_power() {
+            return self.check_shift_or_power(op, left, right, expected, line);
+        }
+        // in `1 + x` the literal takes its type from `x`, and in `x:u8 = 1 + 2` from the `u8`
+        let expected = if op.is_arithmetic() { expected } else { None };
+        let (left_ty, right_ty) = if matches!(left.kind, ExprKind::Integer(..)) && expected.is_none() {
+            let right_ty = self.check_expr(right, None);
+            (self.check_expr(left, Some(&right_ty)), right_ty)
+        } else {
+            let left_ty = self.check_expr(left, expected);
+            // the right side of `x != none && x > 0` only runs when the left side was true
+            let narrowings = match op {
+                BinaryOp::And => self.narrowings(left, true),
+                BinaryOp::Or => self.narrowings(left, false),
+                _ => Vec::new(),
+            };
+            self.scopes.push(HashMap::new());
+            for (name, ty) in narrowings {
+                self.narrow(&name, ty);
+            }
+            let right_ty = self.check_expr(right, Some(&left_ty));
+            self.scopes.pop();
+            (left_ty, right_ty)
+        };
+        let left_ty = self.prune(&left_ty);
+        let right_ty = self.prune(&right_ty);
+        if self.runs_methods(&left_ty) || self.runs_methods(&right_ty) {
+            self.forget_global_narrowings();
+        }
+        if left_ty == Type::Error || right_ty == Type::Error {
+            return if op.is_arithmetic() { Type::Error } else { Type::Bool };
+        }
+        // an optional can be compared against `none` or against a plain value of its inner type
+        let comparing_optional = matches!(op, BinaryOp::Eq | BinaryOp::Ne)
+            && (self.unify_coercing(&left_ty, &right_ty) || self.unify_coercing(&right_ty, &left_ty));
+        if !comparing_optional && !self.unify(&left_ty, &right_ty) {
+            self.error(
+                format!("operator `{}` cannot be applied to {} and {}", op, left_ty, right_ty),
+                line,
+            );
+            return Type::Error;
+        }
+        if matches!(right.kind, ExprKind::None) || matches!(left.kind, ExprKind::None) {
+            return Type::Bool;
+        }
+        let ty = self.prune(&left_ty);
+        // a struct or a type parameter gets the arithmetic operators from the prelude's traits
+        if let (Type::Struct(..) | Type::Param(_), Some((trait_name, _))) = (&ty, op.overload()) {
+            if !self.implements(&ty, trait_name) {
+                self.error(
+                    format!("operator `{}` cannot be applied to type {}, it does not implement `{}`", op, ty, trait_name),
+                    line,
+                );
+                return Type::Error;
+            }
+            return ty;
+        }
+        let allowed = match op {
+            BinaryOp::Add => matches!(ty, Type::Int | Type::Sized(_) | Type::BigInt | Type::Float | Type::Str),
+            BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+                matches!(ty, Type::Int | Type::Sized(_) | Type::BigInt | Type::Float)
+            }
+            // wrapping and saturating only mean something for integers
+            BinaryOp::WrappingAdd
+            | BinaryOp::WrappingSub
+            | BinaryOp::WrappingMul
+            | BinaryOp::SaturatingAdd
+            | BinaryOp::SaturatingSub
+            | BinaryOp::SaturatingMul => matches!(ty, Type::Int | Type::Sized(_)),
+            BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => matches!(ty, Type::Int | Type::Sized(_)),
+            BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Pow => unreachable!("handled by check_shift_or_power"),
+            BinaryOp::Eq | BinaryOp::Ne => {
+                if !self.implements(&ty, "Eq") {
+                    self.error(format!("operator `{}` needs {} to implement `Eq`", op, ty), line);
+                    return Type::Error;
+                }
+                true
+            }
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+                if !self.implements(&ty, "Ord") {
+                    self.error(format!("operator `{}` needs {} to implement `Ord`", op, ty), line);
+                    return Type::Error;
+                }
+                true
+            }
+            BinaryOp::And | BinaryOp::Or => ty == Type::Bool,
+            BinaryOp::Coalesce => unreachable!("handled by check_coalesce"),
+        };
+        if !allowed {
+            self.error(format!("operator `{}` cannot be applied to type {}", op, ty), line);
+            return Type::Error;
+        }
+        if op.is_arithmetic() {
+            ty
+        } else {
+            Type::Bool
+        }
+    }
+}
+
+// the names of every variable assigned to anywhere in `statements`
+fn assigned_names(statements: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in statements {
+        match &stmt.kind {
+            StmtKind::Assign {
+                target: Expr {
+                    kind: ExprKind::Identifier(name),
+                    ..
+                },
+                ..
+            } => names.push(name.clone()),
+            StmtKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                names.extend(assigned_names(then_branch));
+                if let Some(else_branch) = else_branch {
+                    names.extend(assigned_names(else_branch));
+                }
+            }
+            StmtKind::While { body, .. } | StmtKind::For { body, .. } | StmtKind::Block(body) => {
+                names.extend(assigned_names(body))
+            }
+            StmtKind::Try { body, handler, .. } => {
+                names.extend(assigned_names(body));
+                names.extend(assigned_names(handler));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+fn const_compare(op: BinaryOp, ordering: Option<std::cmp::Ordering>) -> bool {
+    use std::cmp::Ordering::*;
+    matches!(
+        (op, ordering),
+        (BinaryOp::Lt, Some(Less))
+            | (BinaryOp::Gt, Some(Greater))
+            | (BinaryOp::Le, Some(Less | Equal))
+            | (BinaryOp::Ge, Some(Greater | Equal))
+    )
+}
+
+fn builtin_implements(ty: &Type, trait_name: &str) -> bool {
+    match trait_name {
+        "Show" => true,
+        "Eq" => matches!(
+            ty,
+            Type::Int | Type::Sized(_) | Type::BigInt | Type::Float | Type::Str | Type::Bool | Type::Range
+        ),
+        "Ord" => matches!(ty, Type::Int | Type::Sized(_) | Type::BigInt | Type::Float | Type::Str),
+        "Add" => matches!(ty, Type::Int | Type::Sized(_) | Type::BigInt | Type::Float | Type::Str),
+        "Sub" | "Mul" | "Div" | "Rem" => matches!(ty, Type::Int | Type::Sized(_) | Type::BigInt | Type::Float),
+        "Neg" => matches!(ty, Type::Int | Type::BigInt | Type::Float) || matches!(ty, Type::Sized(kind) if kind.signed()),
+        _ => false,
+    }
+}
+
+/// The name impl blocks are looked up by, at check time and at runtime.
+pub fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Int => Some("int".to_string()),
+        Type::Sized(kind) => Some(kind.name().to_string()),
+        Type::BigInt => Some("bigint".to_string()),
+        Type::Float => Some("float".to_string()),
+        Type::Str => Some("str".to_string()),
+        Type::Bool => Some("bool".to_string()),
+        Type::Range => Some("range".to_string()),
+        Type::Struct(name, _) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// The type of an integer of `kind`, `int` for `i32`.
+fn integer_type(kind: IntKind) -> Type {
+    match kind {
+        IntKind::I32 => Type::Int,
+        kind => Type::Sized(kind),
+    }
+}
+
+fn int_kind(ty: &Type) -> Option<IntKind> {
+    match ty {
+        Type::Int => Some(IntKind::I32),
+        Type::Sized(kind) => Some(*kind),
+        _ => None,
+    }
+}
+
+// whether a written type uses `name` anywhere, like `T` in `map<str, T[]>`
+fn mentions(ty: &TypeExpr, name: &str) -> bool {
+    match ty {
+        TypeExpr::Named(n, args) => n == name || args.iter().any(|arg| mentions(arg, name)),
+        TypeExpr::List(inner) | TypeExpr::Optional(inner) => mentions(inner, name),
+        TypeExpr::Tuple(items) => items.iter().any(|item| mentions(item, name)),
+    }
+}
+
+fn param_names(type_params: &[TypeParam]) -> String {
+    let names: Vec<&str> = type_params.iter().map(|p| p.name.as_str()).collect();
+    names.join(", ")
+}
+
+fn always_returns(statements: &[Stmt]) -> bool {
+    statements.iter().any(|stmt| match &stmt.kind {
+        StmtKind::Return(_) => true,
+        StmtKind::Block(inner) => always_returns(inner),
+        StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => always_returns(then_branch) && always_returns(else_branch),
+        StmtKind::Try { body, handler, .. } => always_returns(body) && always_returns(handler),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    fn errors(source: &str) -> Vec<String> {
+        let program = parse_source(source).unwrap();
+        match check(&program) {
+            Ok(()) => Vec::new(),
+            Err(diagnostics) => diagnostics.into_iter().map(|d| d.message).collect(),
+        }
+    }
+
+    #[test]
+    fn test_generic_call_infers_type_parameter() {
+        let source = "first<T>(xs:T[]):T { return xs[0]; }\n\
+                      a:int = first([1, 2]);\n\
+                      b:str = first([\"x\"]);\n\
+                      c:int = first([\"x\"]);";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("mismatched types"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_uninferable_type_parameter_is_reported() {
+        let source = "empty<T>():T[] { xs:T[] = []; return xs; }\n\
+                      print(len(empty()));\n\
+                      ys:int[] = empty();\n\
+                      zs:str[] = empty<str>();";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("cannot infer type parameter `T` of `empty`"));
+    }
+
+    #[test]
+    fn test_generic_struct_fields_are_substituted() {
+        let source = "struct Box<T> { value:T }\n\
+                      b:Box<int> = Box { value: 3 };\n\
+                      n:int = b.value;\n\
+                      s:str = b.value;";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_trait_bounds_and_impls_are_enforced() {
+        let source = "struct P { x:int }\n\
+                      trait Area { area(self):float }\n\
+                      impl Area for P { }\n\
+                      largest<T: Ord>(xs:T[]):T { return xs[0]; }\n\
+                      same<T>(a:T, b:T):bool { return a == b; }\n\
+                      p:P = largest([P { x: 1 }]);\n\
+                      n:int = largest([1, 2]);";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("missing method `area`")));
+        assert!(errors.iter().any(|e| e.contains("P does not implement `Ord`")));
+        assert!(errors.iter().any(|e| e.contains("needs T to implement `Eq`")));
+    }
+
+    #[test]
+    fn test_optional_values_must_be_narrowed_before_use() {
+        let source = "mut x:int? = 3;\n\
+                      a:int = x + 1;\n\
+                      if (x != none) { b:int = x + 1; }\n\
+                      if x == none { c:int = 0; } else { d:int = x; }\n\
+                      if x != none { x = none; e:int = x; }\n\
+                      f:int = x ?? 0;";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].contains("int? and int"));
+        assert!(errors[1].contains("may be `none`"));
+    }
+
+    #[test]
+    fn test_the_right_side_of_and_or_is_narrowed_by_the_left() {
+        let source = "x:int? = 3;\n\
+                      a := x != none && x > 2;\n\
+                      b := x == none || x > 2;\n\
+                      c := x == none && x > 2;";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("`>` cannot be applied to int? and int"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_calls_undo_the_narrowing_of_mut_globals() {
+        let source = "mut g:int? = 1;\n\
+                      clear() { g = none; }\n\
+                      if g != none { a:int = g; clear(); b:int = g + 1; }\n\
+                      f(x:int?) { mut y := x; if y == none { return; } clear(); c:int = y; y = none; print(y); }";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("int? and int"));
+    }
+
+    #[test]
+    fn test_operators_and_printing_on_structs_undo_the_narrowing_of_mut_globals() {
+        let source = "mut g:int? = 1;\n\
+                      struct P { a:int }\n\
+                      impl Add for P { add(self, o:P):P { g = none; return self; } }\n\
+                      if g != none { n := P { a: 1 } + P { a: 2 }; a:int = g + 1; }\n\
+                      if g != none { p := P { a: 1 }; print(p); b:int = g + 1; }\n\
+                      if g != none { p := P { a: 1 }; s := \"{p}\"; c:int = g + 1; }\n\
+                      if g != none { d:int = g + 1; }";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_question_mark_needs_a_matching_return_type() {
+        let source = "parse(s:str):Result<int, str> { return ok(1); }\n\
+                      good(s:str):Result<int, str> { n:int = parse(s)?; return ok(n); }\n\
+                      wrong(s:str):Result<int, int> { n:int = parse(s)?; return err(n); }\n\
+                      plain(s:str):int { return parse(s)?; }\n\
+                      r:Result<int, str> = err(\"no\");\n\
+                      x:int = unwrap_or(r, \"zero\");";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("err of type str from a function whose errors are int")));
+        assert!(errors.iter().any(|e| e.contains("needs the function to return a Result")));
+        assert!(errors.iter().any(|e| e.contains("default value expected int, found str")));
+    }
+
+    #[test]
+    fn test_assignments_need_mut_and_constants_are_fixed() {
+        let source = "const LIMIT:int = 4 * 25;\n\
+                      const HALF:int = LIMIT / 2;\n\
+                      const BAD:int = len([1]);\n\
+                      count:int = 0;\n\
+                      count = 1;\n\
+                      mut total:int = HALF;\n\
+                      total = total + 1;\n\
+                      LIMIT = 5;\n\
+                      bump(mut n:int, xs:int[]):int { n = n + 1; push(xs, n); return n; }";
+        let program = parse_source(source).unwrap();
+        let diagnostics = check(&program).unwrap_err();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(diagnostics.len(), 4, "{:?}", messages);
+        assert!(messages[0].contains("constant `BAD` must be known before the program runs"));
+        assert_eq!(messages[1], "cannot assign to `count` because it is not declared `mut`");
+        assert_eq!(diagnostics[1].line, 5);
+        assert_eq!(diagnostics[1].notes[0].line, 4);
+        assert_eq!(messages[2], "cannot assign to constant `LIMIT`");
+        assert_eq!(messages[3], "cannot push onto `xs` because it is not declared `mut`");
+    }
+
+    #[test]
+    fn test_mut_parameters_that_share_need_mut_arguments() {
+        let source = "struct P { a:int }\n\
+                      set(mut p:P) { p.a = 5; }\n\
+                      add(mut xs:int[]) { xs.push(1); }\n\
+                      bump(mut n:int):int { n = n + 1; return n; }\n\
+                      d := P { a: 1 };\n\
+                      set(d);\n\
+                      mut e := P { a: 1 };\n\
+                      set(e);\n\
+                      ys := [1];\n\
+                      add(ys);\n\
+                      n := 1;\n\
+                      print(bump(n), bump(2));";
+        let errors = errors(source);
+        assert_eq!(
+            errors,
+            vec![
+                "cannot let `set` change `d` because it is not declared `mut`",
+                "cannot let `add` change `ys` because it is not declared `mut`"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_looping_over_an_iterator_struct_needs_it_mut() {
+        let source = "struct Countdown { n:int }\n\
+                      impl Iterator<int> for Countdown {\n\
+                          next(mut self):int? { if self.n == 0 { return none; } self.n = self.n - 1; return self.n; }\n\
+                      }\n\
+                      c := Countdown { n: 3 };\n\
+                      for n in c { }\n\
+                      mut d := Countdown { n: 3 };\n\
+                      for n in d { }";
+        assert_eq!(errors(source), vec!["cannot loop over `c` because it is not declared `mut`"]);
+    }
+
+    #[test]
+    fn test_loop_variables_get_their_own_note() {
+        let program = parse_source("for i in 0..3 {\n    i = i + 1;\n}").unwrap();
+        let diagnostics = check(&program).unwrap_err();
+        assert_eq!(diagnostics[0].message, "cannot assign to loop variable `i`");
+        assert_eq!(diagnostics[0].notes[0].message, "loop variables cannot be changed, copy `i` into a `mut` local first");
+        assert_eq!(diagnostics[0].notes[0].line, 1);
+    }
+
+    #[test]
+    fn test_declarations_infer_their_type_from_the_value() {
+        let source = "wrap<T>(x:T):T[] { return [x]; }\n\
+                      a := 1 + 2;\n\
+                      b: = wrap(\"s\");\n\
+                      c := b[0];\n\
+                      d := none;";
+        let program = parse_source(source).unwrap();
+        let diagnostics = check(&program).unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("cannot infer the type of `d`"));
+        let program = parse_source(&source.replace("d := none;", "")).unwrap();
+        let types: Vec<String> = variable_types(&program)
+            .unwrap()
+            .iter()
+            .map(|(line, name, ty)| format!("{} {}: {}", line, name, ty))
+            .collect();
+        assert_eq!(types, ["2 a: int", "3 b: str[]", "4 c: str"]);
+    }
+
+    #[test]
+    fn test_for_loops_need_something_iterable() {
+        let found = errors(
+            "struct P { x:int }\n\
+             impl Iterator<str> for P { next(mut self):int? { return none; } }\n\
+             for (a, b) in [1] { }\n\
+             for a in 5 { }\n\
+             for a in 0..\"x\" { }",
+        );
+        assert!(found.iter().any(|e| e.starts_with("method `next` does not match trait `Iterator`, expected (P):str?")));
+        assert!(found.iter().any(|e| e.starts_with("only maps and lists of tuples can be looped over as `(a, b)`")));
+        assert!(found.iter().any(|e| e.starts_with("cannot loop over int")));
+        assert!(found.iter().any(|e| e.contains("end of range expected int, found str")));
+        assert_eq!(found.len(), 4);
+    }
+
+    #[test]
+    fn test_sized_integer_literals_must_fit_and_types_do_not_mix() {
+        let found = errors(
+            "a:u8 = 256;\n\
+             b:u64 = 4000000000;\n\
+             c := 3000000000;\n\
+             d := b + 1i64;\n\
+             e := -a;\n\
+             f := 1.5 +% 2.0;",
+        );
+        assert!(found.iter().any(|e| e.starts_with("literal `256` does not fit in u8")));
+        assert!(found.iter().any(|e| e.starts_with("literal `3000000000` does not fit in int")));
+        assert!(found.iter().any(|e| e.starts_with("operator `+` cannot be applied to u64 and i64")));
+        assert!(found.iter().any(|e| e.starts_with("cannot negate a value of type u8")));
+        assert!(found.iter().any(|e| e.starts_with("operator `+%` cannot be applied to type float")));
+        assert_eq!(found.len(), 5);
+    }
+
+    #[test]
+    fn test_literals_take_the_sized_type_inside_optionals_and_generic_calls() {
+        let source = "id<T>(x:T):T { return x; }\n\
+                      a:u8? = 200;\n\
+                      b:u8 = { 200 };\n\
+                      c:u8 = id(200);\n\
+                      d:bigint? = 5;\n\
+                      e:u8? = 300;";
+        let errors = errors(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].starts_with("literal `300` does not fit in u8"));
+    }
+
+    #[test]
+    fn test_bigints_only_mix_with_literals() {
+        let found = errors(
+            "a:bigint = 5;\n\
+             n:int = 2;\n\
+             b := a * 3 + 1;\n\
+             c := a + n;\n\
+             d:int = a;\n\
+             e := a +% 1;",
+        );
+        assert!(found.iter().any(|e| e.starts_with("operator `+` cannot be applied to bigint and int")));
+        assert!(found.iter().any(|e| e.contains("expected int, found bigint")));
+        assert!(found.iter().any(|e| e.starts_with("operator `+%` cannot be applied to type bigint")));
+        assert_eq!(found.len(), 3);
+    }
+
+    #[test]
+    fn test_bit_operators_need_integers() {
+        let found = errors(
+            "a := 1.5 & 2.0;\n\
+             b := \"x\" << 1;\n\
+             c := ~2.0;\n\
+             d := 2 ** 0.5;\n\
+             e := 1u8 << 3 ** 2;",
+        );
+        assert!(found.iter().any(|e| e.starts_with("operator `&` cannot be applied to type float")));
+        assert!(found.iter().any(|e| e.starts_with("operator `<<` cannot be applied to str and int")));
+        assert!(found.iter().any(|e| e.starts_with("`~` expects an integer, found float")));
+        assert!(found.iter().any(|e| e.starts_with("operator `**` cannot be applied to int and float")));
+        assert_eq!(found.len(), 4);
+    }
+
+    #[test]
+    fn test_method_calls_are_checked_like_calls() {
+        let found = errors(
+            "xs := [1, 2];\n\
+             xs.pop();\n\
+             \"a\".frobnicate();\n\
+             \"a\".split(1);\n\
+             n:str = xs.len();",
+        );
+        assert!(found.iter().any(|e| e.starts_with("cannot call `pop` on `xs` because it is not declared `mut`")));
+        assert!(found.iter().any(|e| e.starts_with("str has no method `frobnicate`")));
+        assert!(found.iter().any(|e| e.contains("argument of `split` expected str, found int")));
+        assert!(found.iter().any(|e| e.contains("expected str, found int")));
+        assert_eq!(found.len(), 4);
+    }
+
+    #[test]
+    fn test_if_expression_branches_must_agree() {
+        let found = errors(
+            "a := if true { 1 } else { \"no\" };\n\
+             b:int = if true { 1 };\n\
+             c:int? = if true { 1 } else { none };\n\
+             f:int(x:int) { d:int = if x < 0 { return 0; } else { x }; return d; }",
+        );
+        assert!(found.iter().any(|e| e.starts_with("`if` and `else` have different types: int and str")));
+        assert!(found.iter().any(|e| e.starts_with("an `if` used as a value needs an `else`")));
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn test_tuples_are_checked_item_by_item() {
+        let found = errors(
+            "(a, b, c) := (1, 2);\n\
+             t := (1, \"a\");\n\
+             print(t.2);\n\
+             t.0 = 3;\n\
+             x:(int, str) = (\"a\", 1);\n\
+             for (p, q) in [(1, \"x\")] { n:int = q; }",
+        );
+        assert!(found.iter().any(|e| e.starts_with("cannot take apart a tuple of 2 items into 3 names")));
+        assert!(found.iter().any(|e| e.starts_with("a tuple of 2 items has no item 2")));
+        assert!(found.iter().any(|e| e.starts_with("the items of a tuple cannot be changed")));
+        assert!(found.iter().any(|e| e.contains("`x` expected (int, str), found (str, int)")));
+        assert!(found.iter().any(|e| e.contains("`n` expected int, found str")));
+        assert_eq!(found.len(), 5);
+    }
+
+    #[test]
+    fn test_named_default_and_variadic_arguments() {
+        let found = errors(
+            "f(a:int, b:int = 1):int { return a + b; }\n\
+             g(a:int = 1, b:int) { }\n\
+             h(xs: ...int, y:int) { }\n\
+             sum(xs: ...int):int { return len(xs); }\n\
+             n:int = f(1, c: 2) + f(a: 1, a: 2) + f(b: 1) + sum(1, 2, \"3\") + sum() + f(b: 2, a: 1);",
+        );
+        assert!(found.iter().any(|e| e.starts_with("parameter `b` needs a default value, it comes after `a`")));
+        assert!(found.iter().any(|e| e.starts_with("the variadic parameter `xs` must be the last one")));
+        assert!(found.iter().any(|e| e.starts_with("function `f` has no parameter `c`")));
+        assert!(found.iter().any(|e| e.starts_with("argument `a` of `f` is given more than once")));
+        assert!(found.iter().any(|e| e.starts_with("missing argument `a` of `f`")));
+        assert!(found.iter().any(|e| e.contains("argument `xs` of `sum` expected int, found str")));
+        assert_eq!(found.len(), 6);
+    }
+
+    #[test]
+    fn test_operators_on_structs_need_the_operator_traits() {
+        let found = errors(
+            "struct P { x:int }\n\
+             impl Add for P { add(self, other:P):P { return P { x: self.x + other.x }; } }\n\
+             add(a:int, b:int):int { return a + b; }\n\
+             a:P = P { x: 1 } + P { x: 2 };\n\
+             b := P { x: 1 } - P { x: 2 };\n\
+             c := -P { x: 1 };\n\
+             twice<T: Add>(v:T):T { return v + v; }\n\
+             times<T>(v:T):T { return v * v; }",
+        );
+        assert!(found.iter().any(|e| e.starts_with("operator `-` cannot be applied to type P, it does not implement `Sub`")));
+        assert!(found.iter().any(|e| e.starts_with("cannot negate a value of type P, it does not implement `Neg`")));
+        assert!(found.iter().any(|e| e.starts_with("operator `*` cannot be applied to type T, it does not implement `Mul`")));
+        assert_eq!(found.len(), 3);
+    }
+
+    #[test]
+    fn test_string_methods_have_typed_results() {
+        let found = errors(
+            "n:int = \"42\".parse_int().unwrap();\n\
+             x:float = \"2.5\".parse_float().unwrap_or(0.0);\n\
+             at:int = \"abc\".find(\"b\");\n\
+             s := [1, 2].join(\",\");\n\
+             t := \"ab\".slice(1);\n\
+             u:str = \"ab\".repeat(\"3\");",
+        );
+        assert!(found.iter().any(|e| e.contains("expected int, found int?")));
+        assert!(found.iter().any(|e| e == "int[] has no method `join`"));
+        assert!(found.iter().any(|e| e.starts_with("method `slice` of str takes 2 argument(s) but 1 were given")));
+        assert!(found.iter().any(|e| e.contains("argument of `repeat` expected int, found str")));
+        assert_eq!(found.len(), 4);
+    }
+
+    #[test]
+    fn test_ok_without_a_value_is_a_void_result() {
+        let found = errors(
+            "save():Result<void, str> { return ok(); }\n\
+             count():Result<int, str> { return ok(); }\n\
+             r := err(\"no\", 1);",
+        );
+        assert!(found.iter().any(|e| e.starts_with("mismatched types: `ok()` expected int, found void")));
+        assert!(found.iter().any(|e| e.starts_with("`err` takes 1 argument(s) but 2 were given")));
+        assert_eq!(found.len(), 2);
+    }
+}
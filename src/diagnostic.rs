@@ -0,0 +1,56 @@
+// diagnostic.rs
+// errors reported to the user by the parser and the type checker
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: usize,
+    // the file the line is in, when the program is made of more than one
+    pub file: Option<String>,
+    // other places in the source that explain the error, e.g. where a variable was declared
+    pub notes: Vec<Note>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub message: String,
+    pub line: usize,
+    // set along with the diagnostic's own file, a note can be in another one
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, line: usize) -> Self {
+        Diagnostic {
+            message: message.into(),
+            line,
+            file: None,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>, line: usize) -> Self {
+        self.notes.push(Note {
+            message: message.into(),
+            line,
+            file: None,
+        });
+        self
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "error: {} at line {}", self.message, self.line)?;
+        if let Some(file) = &self.file {
+            write!(f, " of {}", file)?;
+        }
+        for note in &self.notes {
+            write!(f, "\nnote: {} at line {}", note.message, note.line)?;
+            if let Some(file) = &note.file {
+                write!(f, " of {}", file)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+// format.rs
+// format specifiers shared by string interpolation (`"{x:>8.2}"`) and the `format` builtin. a
+// spec is `[[fill]align][0][width][.precision][radix]` like in rust: `<` `>` `^` align, `0` pads
+// numbers with zeros, and `b` `o` `x` `X` print ints in binary, octal or hex.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FormatSpec {
+    pub fill: Option<char>,
+    pub align: Option<Align>,
+    pub zero: bool,
+    pub width: usize,
+    pub precision: Option<usize>,
+    pub radix: Option<char>,
+}
+
+/// A piece of a `format` template, either text to copy or a `{}` to fill in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Placeholder(FormatSpec),
+}
+
+fn align_of(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+pub fn parse_spec(spec: &str) -> Result<FormatSpec, String> {
+    let mut result = FormatSpec::default();
+    let chars: Vec<char> = spec.chars().collect();
+    let mut i = 0;
+    if let Some(align) = chars.get(1).copied().and_then(align_of) {
+        result.fill = Some(chars[0]);
+        result.align = Some(align);
+        i = 2;
+    } else if let Some(align) = chars.first().copied().and_then(align_of) {
+        result.align = Some(align);
+        i = 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        result.zero = true;
+        i += 1;
+    }
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>()
+    };
+    let width = digits(&mut i);
+    if !width.is_empty() {
+        result.width = width
+            .parse()
+            .map_err(|_| format!("width `{}` is too large", width))?;
+    }
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        let precision = digits(&mut i);
+        if precision.is_empty() {
+            return Err(format!("expected a precision after `.` in `{}`", spec));
+        }
+        result.precision = Some(
+            precision
+                .parse()
+                .map_err(|_| format!("precision `{}` is too large", precision))?,
+        );
+    }
+    if let Some(&c @ ('b' | 'o' | 'x' | 'X')) = chars.get(i) {
+        result.radix = Some(c);
+        i += 1;
+    }
+    if i < chars.len() {
+        return Err(format!("invalid format spec `{}`", spec));
+    }
+    Ok(result)
+}
+
+/// Splits a `format` template into text and `{}` / `{:spec}` placeholders. `{{` and `}}` are
+/// literal braces.
+pub fn parse_template(template: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut inside = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inside.push(c),
+                        None => return Err("unclosed `{` in format string".to_string()),
+                    }
+                }
+                let spec = match inside.strip_prefix(':') {
+                    Some(spec) => parse_spec(spec)?,
+                    None if inside.is_empty() => FormatSpec::default(),
+                    None => {
+                        return Err(format!(
+                            "`{{{}}}` is not a placeholder, use `{{}}` or `{{:spec}}`",
+                            inside
+                        ))
+                    }
+                };
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Placeholder(spec));
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+impl FormatSpec {
+    pub fn int(&self, n: i128) -> String {
+        let digits = match self.radix {
+            Some('b') => format!("{:b}", n.unsigned_abs()),
+            Some('o') => format!("{:o}", n.unsigned_abs()),
+            Some('x') => format!("{:x}", n.unsigned_abs()),
+            Some('X') => format!("{:X}", n.unsigned_abs()),
+            _ => n.unsigned_abs().to_string(),
+        };
+        self.number(n < 0, digits)
+    }
+
+    pub fn float(&self, x: f64) -> String {
+        let text = match self.precision {
+            Some(precision) => format!("{:.*}", precision, x.abs()),
+            None => format!("{:?}", x.abs()),
+        };
+        self.number(x.is_sign_negative() && x != 0.0, text)
+    }
+
+    /// Anything that isn't a number is formatted as its text, cut to the precision if any.
+    pub fn text(&self, text: &str) -> String {
+        let text = match self.precision {
+            Some(precision) => text.chars().take(precision).collect(),
+            None => text.to_string(),
+        };
+        self.pad(text, Align::Left)
+    }
+
+    // zero padding goes between the sign and the digits
+    fn number(&self, negative: bool, digits: String) -> String {
+        let sign = if negative { "-" } else { "" };
+        if self.zero && self.align.is_none() {
+            let len = sign.len() + digits.chars().count();
+            let zeros = "0".repeat(self.width.saturating_sub(len));
+            return format!("{}{}{}", sign, zeros, digits);
+        }
+        self.pad(format!("{}{}", sign, digits), Align::Right)
+    }
+
+    fn pad(&self, text: String, default: Align) -> String {
+        let len = text.chars().count();
+        if len >= self.width {
+            return text;
+        }
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let space = self.width - len;
+        let (before, after) = match self.align.unwrap_or(default) {
+            Align::Left => (0, space),
+            Align::Right => (space, 0),
+            Align::Center => (space / 2, space - space / 2),
+        };
+        format!("{}{}{}", fill.repeat(before), text, fill.repeat(after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specs_pad_align_and_change_radix() {
+        let spec = |s: &str| parse_spec(s).unwrap();
+        assert_eq!(spec(">6").text("ab"), "    ab");
+        assert_eq!(spec("*^7").text("ab"), "**ab***");
+        assert_eq!(spec("6").int(-42), "   -42");
+        assert_eq!(spec("06").int(-42), "-00042");
+        assert_eq!(spec("08b").int(5), "00000101");
+        assert_eq!(spec("x").int(255), "ff");
+        assert_eq!(spec("<8.2").float(1.005), "1.00    ");
+        assert_eq!(spec(".3").text("abcdef"), "abc");
+        assert!(parse_spec("8q").is_err());
+        assert_eq!(
+            parse_template("{{{}}} {:>3}").unwrap(),
+            vec![
+                Piece::Text("{".to_string()),
+                Piece::Placeholder(FormatSpec::default()),
+                Piece::Text("} ".to_string()),
+                Piece::Placeholder(spec(">3")),
+            ]
+        );
+    }
+}
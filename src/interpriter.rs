@@ -1,163 +1,1886 @@
-use crate::Token;
+// interpriter.rs
+// walks the checked program and runs it. the checker has already rejected badly typed programs
+// so anything that goes wrong here is a problem that can only be seen at runtime. those problems
+// become a RuntimeError that a `try`/`catch` in the program can handle.
 
-fn print_f(msg: &str) {
-    println!("{}", msg);
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::bigint::{self, BigInt};
+use crate::format::{parse_template, FormatSpec, Piece};
+use crate::ints::{self, IntKind};
+use crate::json;
+use crate::stdlib;
+use crate::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub line: usize,
+    pub file: Option<String>,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>, line: usize) -> Self {
+        RuntimeError {
+            message: message.into(),
+            line,
+            file: None,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "runtime error: {} at line {}", self.message, self.line)?;
+        match &self.file {
+            Some(file) => write!(f, " of {}", file),
+            None => Ok(()),
+        }
+    }
+}
+
+// why evaluation stopped early: a `return` (or `?`) leaving the current function, or a runtime
+// error on its way to the nearest `catch`
+enum Unwind {
+    Return(Value),
+    Error(RuntimeError),
+    // `process.exit(code)`, stops the whole program
+    Exit(i32),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+type Exec<T> = Result<T, Unwind>;
+
+fn fail<T>(message: impl Into<String>, line: usize) -> Exec<T> {
+    Err(Unwind::Error(RuntimeError::new(message, line)))
 }
+
+pub struct Interpreter<W: Write> {
+    out: W,
+    // where `input`, `read_line` and `read_all` read from
+    input: Box<dyn BufRead>,
+    functions: HashMap<String, FunctionDecl>,
+    // trait methods keyed by the name of the type they're implemented for and the method name
+    methods: HashMap<(String, String), FunctionDecl>,
+    // field types, so a literal stored in a sized field gets its type
+    structs: HashMap<String, StructDecl>,
+    scopes: Vec<HashMap<String, Value>>,
+    context: stdlib::Context,
+    // how many calls deep the program is and where the stack was when it started, so runaway
+    // recursion is an error it can catch instead of the interpreter running out of stack
+    depth: usize,
+    stack_base: usize,
+}
+
+/// The most calls that can be running at once.
+pub const MAX_DEPTH: usize = 10_000;
+/// The stack `main` runs the interpreter with. A call in a debug build can take a lot of it, so
+/// calls also stop when all but the last few megabytes are used.
+pub const STACK_SIZE: usize = 1 << 30;
+const STACK_MARGIN: usize = 32 << 20;
+
+// roughly where the top of the stack is, it grows down on everything cube runs on
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
 //𓎢𓄿𓈖 𓇌𓅲 𓅲𓈖𓂧𓂋𓋴𓏏𓄿𓈖𓂧 𓅓?
-pub fn interpret(tokens: Vec<Token>) {
-   let mut i = 0;
-    let mut variables = Vec::new();
-   let mut objects: Vec<(String, String)> = Vec::new();
-   let mut functions: Vec<(String, Vec<Token>)> = Vec::new();
-   let mut classes: Vec<(String, Vec<Token>)> = Vec::new();
-   let mut function_args: Vec<(String, Vec<Token>)> = Vec::new();
- 
-
-    while i < tokens.len() {
-        //println!("{}",i);
-        //println!("TOKEN {:?}", tokens[i]);
-         match tokens[i] {
-                Token::NewLine => {
-                    i += 1;
+pub fn interpret(program: &[Stmt], context: stdlib::Context) -> Result<i32, RuntimeError> {
+    Interpreter::new(io::stdout()).with_context(context).run(program)
+}
+
+impl<W: Write> Interpreter<W> {
+    pub fn new(out: W) -> Self {
+        Interpreter {
+            out,
+            input: Box::new(io::stdin().lock()),
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            structs: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            context: stdlib::Context::default(),
+            depth: 0,
+            stack_base: 0,
+        }
+    }
+
+    pub fn with_context(mut self, context: stdlib::Context) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Runs the top level statements in order, then `main` if the program declares one. Gives
+    /// the code the program exits with, 0 unless it calls `process.exit`.
+    pub fn run(&mut self, program: &[Stmt]) -> Result<i32, RuntimeError> {
+        self.stack_base = stack_address();
+        for stmt in program {
+            match &stmt.kind {
+                StmtKind::Function(decl) => {
+                    self.functions.insert(decl.name.clone(), decl.clone());
+                }
+                StmtKind::Struct(decl) => {
+                    self.structs.insert(decl.name.clone(), decl.clone());
+                }
+                StmtKind::Impl(decl) => {
+                    let type_name = match &decl.target {
+                        TypeExpr::Named(name, _) => name.clone(),
+                        TypeExpr::List(_) | TypeExpr::Optional(_) | TypeExpr::Tuple(_) => continue,
+                    };
+                    for method in &decl.methods {
+                        self.methods
+                            .insert((type_name.clone(), method.name.clone()), method.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        let result = self.run_statements(program);
+        match result {
+            Ok(()) | Err(Unwind::Return(_)) => Ok(0),
+            Err(Unwind::Exit(code)) => Ok(code),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+
+    fn run_statements(&mut self, program: &[Stmt]) -> Exec<()> {
+        // constants can be used anywhere, so they're set before the rest runs
+        let (constants, statements): (Vec<&Stmt>, Vec<&Stmt>) = program
+            .iter()
+            .partition(|stmt| matches!(stmt.kind, StmtKind::Const { .. }));
+        for stmt in constants.into_iter().chain(statements) {
+            self.exec(stmt)?;
+        }
+        if let Some(main) = self.functions.get("main") {
+            if main.params.is_empty() {
+                let line = main.line;
+                self.call("main", Vec::new(), line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads standard input from `input` instead, for the tests.
+    #[cfg(test)]
+    pub fn with_input(mut self, input: &str) -> Self {
+        self.input = Box::new(io::Cursor::new(input.as_bytes().to_vec()));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    fn exec_block(&mut self, statements: &[Stmt]) -> Exec<()> {
+        self.scopes.push(HashMap::new());
+        let result = statements.iter().try_for_each(|stmt| self.exec(stmt));
+        self.scopes.pop();
+        result
+    }
+
+    // a struct's arithmetic operators call the methods of its `Add`, `Sub`, ... impls
+    fn arithmetic(&mut self, op: BinaryOp, left: Value, right: Value, line: usize) -> Exec<Value> {
+        match (&left, op.overload()) {
+            (Value::Struct(..), Some((_, method))) => self.call_method(method, vec![left, right], line),
+            _ => binary(op, left, right, line),
+        }
+    }
+
+    fn unary(&mut self, op: UnaryOp, value: Value, line: usize) -> Exec<Value> {
+        match (&value, op.overload()) {
+            (Value::Struct(..), Some((_, method))) => self.call_method(method, vec![value], line),
+            _ => unary(op, value, line),
+        }
+    }
+
+    // runs a block used as a value and gives back its last expression, or void without one
+    fn eval_block(&mut self, statements: &[Stmt], ty: Option<&TypeExpr>) -> Exec<Value> {
+        let (value, statements) = match statements.split_last() {
+            Some((
+                Stmt {
+                    kind: StmtKind::Expr(value),
+                    ..
                 },
-                Token::Identifier(ref ident) => {
-                    i += 1;
-                    match tokens[i] {
-                        Token::Colon => {
-                            i += 1;
-                            match tokens[i] {
-                                // check if the token is a Int,String,Float, or Bool
-                                Token::Int | Token::Str | Token::Float | Token::Bool | Token::Void => {
-                                    i += 1;
-                                    match tokens[i] {
-                                        Token::Equals => {
-                                            i += 1;
-                                            match tokens[i] {
-                                                Token::Integer(ref int) => {
-                                                    // if the token is a int folowed by a semicolon
-                                                    if tokens[i + 1] == Token::Semicolon {
-                                                        variables.push((ident.clone(), int.to_string()));
-                                                        break;
-                                                    } else {
-                                                        panic!("Expected a semicolon after the integer at line {}", i);
-                                                    }
-                                                },
-                                                Token::String(ref string) => {
-                                                  if tokens[i + 1] == Token::Semicolon {
-                                                        variables.push((ident.clone(), string.clone()));
-                                                        break;
-                                                    } else {
-                                                        panic!("Expected a semicolon after the string at line {}", i);
-                                                    }
-                                                },
-                                                Token::CFloat(ref float) => {
-                                                    if tokens[i - 2] == Token::Float || tokens[i - 3] == Token::Semicolon {
-                                                        variables.push((ident.clone(), float.to_string()));
-                                                    } else {
-                                                        panic!("Expected a float after equals and a semicolon after the float at line {}", i);
-                                                    }
-                                                },
-                                                
-                                                _ => {
-                                                    panic!("Expected an integer, string, float, or boolean after equals");
-                                                }
-                                            }
-                                        },
-                                        Token::LeftParen => {
-                                            i += 1;
-                                            let mut args: Vec<Token> = Vec::new();
-                                            while tokens[i] != Token::RightParen {
-                                                args.push(tokens[i].clone());
-                                                i += 1;
-                                            }
-                                            function_args.push((ident.clone(), args));
-
-                                        },
-                                        Token::LeftBrace => {
-                                            i += 1;
-                                            let mut statements: Vec<Token> = Vec::new();
-                                            while tokens[i] != Token::RightBrace {
-                                                statements.push(tokens[i].clone());
-                                                i += 1;
-                                            }
-                                            classes.push((ident.clone(), statements));
-                                        },
-                                        Token::Comma => {
-                                            i += 1;
-                                            match tokens[i] {
-                                                Token::Identifier(ref ident) => {
-                                                    objects.push((ident.clone(), ident.clone()));
-                                                },
-                                                _ => {
-                                                    panic!("Expected an identifier after comma {:?} at line {}", tokens[i], i);
-                                                }
-                                            }
-                                        }
-                                                
-                                            
-                                        _ => {
-                                            println!("Unexpected token {:?} at line {}", tokens[i], i);
-                                         
-                                        }
-                                    }
-                                },
-                                
-                                _ => {
-                                    break;
-                                }
-                            }
-                        },
-                        Token::LeftParen => {
-                            i += 1;
-                            let mut args: Vec<Token> = Vec::new();
-                            while tokens[i] != Token::RightParen {
-                                args.push(tokens[i].clone());
-                                i += 1;
-                            }
-                            functions.push((ident.clone(), args));
-                        },
-                        _ => {
-                            println!("Expected a colon or left parenthesis after identifier {:?} at line {}", tokens[i], i);
+                rest,
+            )) => (Some(value), rest),
+            _ => (None, statements),
+        };
+        self.scopes.push(HashMap::new());
+        let result = statements.iter().try_for_each(|stmt| self.exec(stmt)).and_then(|()| match (value, ty) {
+            (Some(value), Some(ty)) => self.eval_as(value, ty),
+            (Some(value), None) => self.eval(value),
+            (None, _) => Ok(Value::Void),
+        });
+        self.scopes.pop();
+        result
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Exec<()> {
+        match &stmt.kind {
+            StmtKind::Let { name, ty, value: expr, mutable } => {
+                let value = match ty {
+                    Some(ty) => self.eval_as(expr, ty)?,
+                    None => self.eval(expr)?,
+                };
+                let value = if *mutable { copy_of_place(expr, value) } else { value };
+                self.scopes.last_mut().unwrap().insert(name.clone(), value);
+            }
+            StmtKind::Destructure { names, ty, value: expr, mutable } => {
+                let value = match ty {
+                    Some(ty) => self.eval_as(expr, ty)?,
+                    None => self.eval(expr)?,
+                };
+                let value = if *mutable { copy_of_place(expr, value) } else { value };
+                let Value::Tuple(items) = value else {
+                    return fail(format!("cannot take apart {}, it is not a tuple", value.type_name()), stmt.line);
+                };
+                let scope = self.scopes.last_mut().unwrap();
+                scope.extend(names.iter().cloned().zip(items));
+            }
+            StmtKind::Const { name, ty, value, .. } => {
+                let value = self.eval_as(value, ty)?;
+                self.scopes.last_mut().unwrap().insert(name.clone(), value);
+            }
+            StmtKind::Assign { target, value: expr } => {
+                let value = self.eval(expr)?;
+                self.assign(target, copy_of_place(expr, value))?;
+            }
+            StmtKind::Print(args) => {
+                let mut text = Vec::new();
+                for arg in args {
+                    let value = self.eval(arg)?;
+                    text.push(self.format_value(&value, false)?);
+                }
+                let written = writeln!(self.out, "{}", text.join(" "));
+                self.output(written, stmt.line)?;
+            }
+            StmtKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                if self.eval_bool(cond)? {
+                    self.exec_block(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.exec_block(else_branch)?;
+                }
+            }
+            StmtKind::While { cond, body } => {
+                while self.eval_bool(cond)? {
+                    self.exec_block(body)?;
+                }
+            }
+            StmtKind::For {
+                names,
+                iterable,
+                body,
+            } => {
+                let iterable = self.eval(iterable)?;
+                self.exec_for(names, iterable, body, stmt.line)?;
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Void,
+                };
+                return Err(Unwind::Return(value));
+            }
+            StmtKind::Try {
+                body,
+                error_name,
+                handler,
+            } => match self.exec_block(body) {
+                Err(Unwind::Error(error)) => {
+                    self.scopes.push(HashMap::new());
+                    if let Some(name) = error_name {
+                        self.scopes
+                            .last_mut()
+                            .unwrap()
+                            .insert(name.clone(), Value::Str(error.message));
+                    }
+                    let result = handler.iter().try_for_each(|stmt| self.exec(stmt));
+                    self.scopes.pop();
+                    result?;
+                }
+                other => other?,
+            },
+            StmtKind::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            StmtKind::Block(statements) => self.exec_block(statements)?,
+            StmtKind::Function(_)
+            | StmtKind::Struct(_)
+            | StmtKind::Trait(_)
+            | StmtKind::Impl(_)
+            | StmtKind::Import(_) => {}
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Exec<()> {
+        let line = target.line;
+        match &target.kind {
+            ExprKind::Identifier(name) => {
+                for scope in self.scopes.iter_mut().rev() {
+                    if let Some(slot) = scope.get_mut(name) {
+                        *slot = retype(slot, value);
+                        return Ok(());
+                    }
+                }
+                fail(format!("undefined variable `{}`", name), line)
+            }
+            ExprKind::Field(base, field) => match self.eval(base)? {
+                Value::Struct(_, fields) => {
+                    let mut fields = fields.borrow_mut();
+                    if let Some(slot) = fields.iter_mut().find(|(f, _)| f == field) {
+                        slot.1 = retype(&slot.1, value);
+                    }
+                    Ok(())
+                }
+                other => fail(format!("cannot set field `{}` on {}", field, other), line),
+            },
+            ExprKind::Index(base, index) => {
+                let base = self.eval(base)?;
+                let index = self.eval(index)?;
+                match base {
+                    Value::List(items) => {
+                        let mut items = items.borrow_mut();
+                        let i = list_index(&index, items.len(), line)?;
+                        items[i] = retype(&items[i], value);
+                    }
+                    Value::Map(entries) => {
+                        let mut entries = entries.borrow_mut();
+                        match entries.iter_mut().find(|(k, _)| *k == index) {
+                            Some(entry) => entry.1 = retype(&entry.1, value),
+                            None => entries.push((index, value)),
+                        }
+                    }
+                    other => return fail(format!("cannot index into {}", other), line),
+                }
+                Ok(())
+            }
+            _ => fail("invalid assignment target", line),
+        }
+    }
+
+    fn lookup(&self, name: &str, line: usize) -> Exec<Value> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(value) = scope.get(name) {
+                return Ok(value.clone());
+            }
+        }
+        fail(format!("undefined variable `{}`", name), line)
+    }
+
+    fn eval_bool(&mut self, expr: &Expr) -> Exec<bool> {
+        match self.eval(expr)? {
+            Value::Bool(b) => Ok(b),
+            other => fail(format!("expected a bool, found {}", other), expr.line),
+        }
+    }
+
+    // evaluates a value that has a declared type and stores it as that type
+    fn eval_as(&mut self, expr: &Expr, ty: &TypeExpr) -> Exec<Value> {
+        let value = self.eval(expr)?;
+        coerce(value, ty, expr.line)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Exec<Value> {
+        let line = expr.line;
+        let value = match &expr.kind {
+            // the type the checker gave it, the smallest that fits in a program that wasn't checked
+            ExprKind::Integer(i, literal) => match literal.get() {
+                Some(Numeric::Int(kind)) => Value::integer(kind, *i),
+                Some(Numeric::Big) => Value::Big(BigInt::from_i128(*i)),
+                None => Value::literal(*i),
+            },
+            ExprKind::SizedInteger(i, kind) => Value::integer(*kind, *i),
+            ExprKind::BigInteger(n) => Value::Big(n.clone()),
+            ExprKind::Cast(operand, ty) => {
+                let value = self.eval(operand)?;
+                cast(value, ty, line)?
+            }
+            ExprKind::Float(x) => Value::Float(*x),
+            ExprKind::Str(s) => Value::Str(s.clone()),
+            ExprKind::Interpolated(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        StringPart::Text(s) => text.push_str(s),
+                        StringPart::Value(expr, spec) => {
+                            let value = self.eval(expr)?;
+                            text.push_str(&self.format_with(&value, spec, expr.line)?);
                         }
                     }
+                }
+                Value::Str(text)
+            }
+            ExprKind::Bool(b) => Value::Bool(*b),
+            ExprKind::None => Value::None,
+            ExprKind::Identifier(name) => self.lookup(name, line)?,
+            ExprKind::List(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.eval(item)?);
+                }
+                Value::list(values)
+            }
+            ExprKind::Tuple(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.eval(item)?);
+                }
+                Value::Tuple(values)
+            }
+            ExprKind::Map(entries) => {
+                let mut map: Vec<(Value, Value)> = Vec::new();
+                for (k, v) in entries {
+                    let k = self.eval(k)?;
+                    let v = self.eval(v)?;
+                    match map.iter_mut().find(|(existing, _)| *existing == k) {
+                        Some(entry) => entry.1 = v,
+                        None => map.push((k, v)),
+                    }
+                }
+                Value::map(map)
+            }
+            ExprKind::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                self.unary(*op, value, line)?
+            }
+            ExprKind::Binary(BinaryOp::And, left, right) => {
+                Value::Bool(self.eval_bool(left)? && self.eval_bool(right)?)
+            }
+            ExprKind::Binary(BinaryOp::Or, left, right) => {
+                Value::Bool(self.eval_bool(left)? || self.eval_bool(right)?)
+            }
+            ExprKind::Binary(BinaryOp::Coalesce, left, right) => match self.eval(left)? {
+                Value::None => self.eval(right)?,
+                value => value,
+            },
+            ExprKind::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                match op {
+                    BinaryOp::Eq => Value::Bool(self.values_equal(&left, &right)?),
+                    BinaryOp::Ne => Value::Bool(!self.values_equal(&left, &right)?),
+                    BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+                        let ordering = self.compare_values(&left, &right)?;
+                        Value::Bool(ordering.is_some_and(|ordering| compare(*op, ordering)))
+                    }
+                    _ => self.arithmetic(*op, left, right, line)?,
+                }
+            }
+            // the type `json.parse<T>` decodes into is only known here, where it's written out
+            ExprKind::Call { name, type_args, args } if name == "json.parse" && type_args.len() == 1 => {
+                let mut values = Vec::new();
+                self.eval_args(args, &mut values)?;
+                let decoded = match values.as_slice() {
+                    [Value::Str(text)] => json::parse(text).and_then(|parsed| json::decode(&parsed, &type_args[0], &self.structs, "$")),
+                    _ => return fail("`json.parse` takes the text to parse", line),
+                };
+                match decoded {
+                    Ok(value) => Value::Ok(Box::new(value)),
+                    Err(message) => Value::Err(Box::new(Value::Str(message))),
+                }
+            }
+            ExprKind::Call { name, args, .. } => {
+                let mut values = Vec::new();
+                let named = self.eval_args(args, &mut values)?;
+                self.call_named(name, values, named, line)?
+            }
+            ExprKind::MethodCall {
+                receiver, method, args, ..
+            } => {
+                let mut values = vec![self.eval(receiver)?];
+                let named = self.eval_args(args, &mut values)?;
+                match self.call_builtin_method(unqualified(method), &values, line)? {
+                    Some(value) => value,
+                    None => self.call_named(method, values, named, line)?,
+                }
+            }
+            ExprKind::NamedArg(name, _) => {
+                return fail(format!("`{}:` can only name an argument of a function", name), line)
+            }
+            ExprKind::Index(base, index) => {
+                let base = self.eval(base)?;
+                let index = self.eval(index)?;
+                match base {
+                    Value::List(items) => {
+                        let items = items.borrow();
+                        items[list_index(&index, items.len(), line)?].clone()
+                    }
+                    Value::Str(s) => {
+                        let chars: Vec<char> = s.chars().collect();
+                        Value::Str(chars[list_index(&index, chars.len(), line)?].to_string())
+                    }
+                    Value::Map(entries) => match entries.borrow().iter().find(|(k, _)| *k == index) {
+                        Some((_, v)) => v.clone(),
+                        None => return fail(format!("key {} not found in map", index), line),
+                    },
+                    other => return fail(format!("cannot index into {}", other), line),
+                }
+            }
+            ExprKind::Field(base, field) => match self.eval(base)? {
+                Value::Struct(_, fields) => {
+                    let found = fields.borrow().iter().find(|(f, _)| f == field).map(|(_, v)| v.clone());
+                    match found {
+                        Some(value) => value,
+                        None => return fail(format!("no field `{}`", field), line),
+                    }
+                }
+                Value::Tuple(items) => match field.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                    Some(item) => item.clone(),
+                    None => return fail(format!("a tuple of {} items has no item {}", items.len(), field), line),
                 },
-                Token::Print => {
-                i += 1;
-                println!("FUCK {:?}", tokens[i]);
-                match tokens[i] {
-                     Token::String(ref msg) => {
-                        println!("AAAAAAAAAAA {}", tokens[i + 1]);
-                          match tokens[i + 2] {
-                            Token::Semicolon => {print_f(msg)},
-                            _ => print!("missing semicolon {}", msg)
-                          }
-                     },
-                     Token::Identifier(ref ident) => {
-                        print_f(ident);
-                         //check to see if the identifier has been added to the list of varibles
-                            if variables.contains(&(ident.clone(), ident.clone())) {
-                                println!("{}", variables.iter().find(|(x, _)| x == ident).unwrap().1);
-                            } else {
-                                panic!("Identifier {:?} not found at line {}", tokens[i], i);
-                            }
-                     },
-                     
-                                  
-                     _ => {
-                          panic!("Expected a string or identifier after print {:?} at line {}", tokens[i], i);
-                     }
-                     
-
-                }
-                i += 1;
-              },
-              _ => {
-                     println!("Unexpected token {:?} at line {}", tokens[i], i);
-              }
-         }
-         i += 1;
-    }
-    println!("Var {:?}", variables);
-    println!("Object {:?}", objects);
-    
-}
\ No newline at end of file
+                other => return fail(format!("{} has no field `{}`", other, field), line),
+            },
+            ExprKind::StructLiteral { name, fields, .. } => {
+                let mut values = Vec::new();
+                for (field, value) in fields {
+                    let declared = self.structs.get(name).and_then(|decl| {
+                        decl.fields.iter().find(|(f, _)| f == field).map(|(_, ty)| ty.clone())
+                    });
+                    let value = match declared {
+                        Some(ty) => self.eval_as(value, &ty)?,
+                        None => self.eval(value)?,
+                    };
+                    values.push((field.clone(), value));
+                }
+                Value::Struct(name.clone(), Rc::new(RefCell::new(values)))
+            }
+            // `?` hands an err or a none straight back to the caller
+            ExprKind::Propagate(inner) => match self.eval(inner)? {
+                Value::Ok(value) => *value,
+                err @ Value::Err(_) => return Err(Unwind::Return(err)),
+                Value::None => return Err(Unwind::Return(Value::None)),
+                value => value,
+            },
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                if self.eval_bool(cond)? {
+                    self.eval_block(then_branch, None)?
+                } else if let Some(else_branch) = else_branch {
+                    self.eval_block(else_branch, None)?
+                } else {
+                    Value::Void
+                }
+            }
+            ExprKind::Block(statements) => self.eval_block(statements, None)?,
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+                step,
+            } => {
+                let bounds = (self.eval(start)?, self.eval(end)?);
+                let step = match step {
+                    Some(step) => self.eval(step)?,
+                    None => Value::Int(1),
+                };
+                match (bounds, step) {
+                    (_, Value::Int(0)) => return fail("the step of a range cannot be zero", line),
+                    ((Value::Int(start), Value::Int(end)), Value::Int(step)) => Value::Range {
+                        start,
+                        end,
+                        inclusive: *inclusive,
+                        step,
+                    },
+                    _ => return fail("ranges need int bounds and step", line),
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    // runs a `for` loop. lists and maps are copied first so the body can change them, anything
+    // else is an `Iterator` whose `next` is called until it returns none
+    fn exec_for(&mut self, names: &[String], iterable: Value, body: &[Stmt], line: usize) -> Exec<()> {
+        match iterable {
+            Value::Range {
+                start,
+                end,
+                inclusive,
+                step,
+            } => {
+                let (mut i, end, step) = (start as i64, end as i64, step as i64);
+                while (step > 0 && (i < end || (inclusive && i == end)))
+                    || (step < 0 && (i > end || (inclusive && i == end)))
+                {
+                    self.exec_loop_body(names, vec![Value::Int(i as i32)], body)?;
+                    i += step;
+                }
+            }
+            Value::List(items) => {
+                let items = items.borrow().clone();
+                for item in items {
+                    self.exec_loop_body(names, vec![item], body)?;
+                }
+            }
+            Value::Map(entries) => {
+                let entries = entries.borrow().clone();
+                for (key, value) in entries {
+                    self.exec_loop_body(names, vec![key, value], body)?;
+                }
+            }
+            Value::Str(s) => {
+                for c in s.chars() {
+                    self.exec_loop_body(names, vec![Value::Str(c.to_string())], body)?;
+                }
+            }
+            iterator => loop {
+                match self.call_method("next", vec![iterator.clone()], line)? {
+                    Value::None => break,
+                    item => self.exec_loop_body(names, vec![item], body)?,
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // binds the loop variables, a map's value is only bound with `for (k, v)`
+    fn exec_loop_body(&mut self, names: &[String], values: Vec<Value>, body: &[Stmt]) -> Exec<()> {
+        // `for (a, b) in pairs` takes each tuple apart
+        let values = match <[Value; 1]>::try_from(values) {
+            Ok([Value::Tuple(items)]) if names.len() > 1 => items,
+            Ok([value]) => vec![value],
+            Err(values) => values,
+        };
+        self.scopes
+            .push(names.iter().cloned().zip(values).collect());
+        let result = self.exec_block(body);
+        self.scopes.pop();
+        result
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>, line: usize) -> Exec<Value> {
+        let value = match (name, args.as_slice()) {
+            ("len", [Value::List(items)]) => Value::Int(items.borrow().len() as i32),
+            ("len", [Value::Map(entries)]) => Value::Int(entries.borrow().len() as i32),
+            ("len", [Value::Str(s)]) => Value::Int(s.chars().count() as i32),
+            ("push", [Value::List(items), value]) => {
+                items.borrow_mut().push(value.clone());
+                Value::Void
+            }
+            ("sort", [Value::List(items)]) => {
+                let unsorted = items.borrow().clone();
+                // NaN goes after every other float instead of being equal to all of them
+                let sorted = merge_sort(unsorted, &mut |a, b| match (a, b) {
+                    (Value::Float(x), Value::Float(y)) => Ok(match (x.is_nan(), y.is_nan()) {
+                        (false, false) => x.total_cmp(y) == Ordering::Less,
+                        (x_nan, y_nan) => !x_nan && y_nan,
+                    }),
+                    _ => Ok(self.compare_values(a, b)? == Some(Ordering::Less)),
+                })?;
+                *items.borrow_mut() = sorted;
+                Value::Void
+            }
+            ("format", _) => Value::Str(self.format_template(&args, line)?),
+            ("ok", []) => Value::Ok(Box::new(Value::Void)),
+            ("ok", [value]) => Value::Ok(Box::new(value.clone())),
+            ("err", [value]) => Value::Err(Box::new(value.clone())),
+            ("is_ok", [value]) => Value::Bool(matches!(value, Value::Ok(_))),
+            ("is_err", [value]) => Value::Bool(matches!(value, Value::Err(_))),
+            ("unwrap", [Value::Ok(value)]) => (**value).clone(),
+            ("unwrap", [Value::Err(error)]) => {
+                return fail(format!("called unwrap on err({})", self.format_value(error, true)?), line)
+            }
+            ("unwrap", [Value::None]) => return fail("called unwrap on none", line),
+            ("unwrap", [value]) => value.clone(),
+            ("unwrap_err", [Value::Err(error)]) => (**error).clone(),
+            ("unwrap_err", [Value::Ok(value)]) => {
+                return fail(format!("called unwrap_err on ok({})", self.format_value(value, true)?), line)
+            }
+            ("unwrap_or", [Value::Ok(value), _]) => (**value).clone(),
+            ("unwrap_or", [Value::Err(_) | Value::None, default]) => default.clone(),
+            ("unwrap_or", [value, _]) => value.clone(),
+            ("pow", [base, Value::Int(exponent)]) => power(base.clone(), *exponent, line)?,
+            ("parse_bigint", [Value::Str(text)]) => match BigInt::parse(text.trim()) {
+                Some(n) => Value::Ok(Box::new(Value::Big(n))),
+                None => Value::Err(Box::new(Value::Str(format!("`{}` is not an integer", text)))),
+            },
+            ("to_str", [value]) => Value::Str(self.format_value(value, false)?),
+            ("input", [Value::Str(prompt)]) => {
+                let written = write!(self.out, "{}", prompt).and_then(|_| self.out.flush());
+                self.output(written, line)?;
+                self.read_line(line)?
+            }
+            ("read_line", []) => self.read_line(line)?,
+            ("read_all", []) => {
+                let mut text = String::new();
+                if let Err(e) = self.input.read_to_string(&mut text) {
+                    return fail(format!("cannot read standard input: {}", e), line);
+                }
+                Value::Str(text)
+            }
+            _ => match self.functions.get(name) {
+                Some(decl) => {
+                    let decl = decl.clone();
+                    self.call_function(&decl, args, Vec::new())?
+                }
+                None => self.call_method(name, args, line)?,
+            },
+        };
+        Ok(value)
+    }
+
+    // only functions declared in the program take named arguments
+    fn call_named(&mut self, name: &str, args: Vec<Value>, named: Vec<(String, Value)>, line: usize) -> Exec<Value> {
+        match self.functions.get(name) {
+            Some(decl) if !named.is_empty() => {
+                let decl = decl.clone();
+                self.call_function(&decl, args, named)
+            }
+            _ => self.call(name, args, line),
+        }
+    }
+
+    // the methods the checker's table gives `str`, numbers, lists and maps. `None` for any other
+    // method, which is then called as a function with the receiver first
+    fn call_builtin_method(&mut self, method: &str, args: &[Value], line: usize) -> Exec<Option<Value>> {
+        let value = match (method, args) {
+            ("trim", [Value::Str(s)]) => Value::Str(s.trim().to_string()),
+            ("trim_start", [Value::Str(s)]) => Value::Str(s.trim_start().to_string()),
+            ("trim_end", [Value::Str(s)]) => Value::Str(s.trim_end().to_string()),
+            ("to_upper", [Value::Str(s)]) => Value::Str(s.to_uppercase()),
+            ("to_lower", [Value::Str(s)]) => Value::Str(s.to_lowercase()),
+            ("split", [Value::Str(_), Value::Str(sep)]) if sep.is_empty() => {
+                return fail("cannot split on an empty separator", line)
+            }
+            ("split", [Value::Str(s), Value::Str(sep)]) => {
+                Value::list(s.split(sep.as_str()).map(|part| Value::Str(part.to_string())).collect())
+            }
+            ("contains", [Value::Str(s), Value::Str(part)]) => Value::Bool(s.contains(part.as_str())),
+            ("starts_with", [Value::Str(s), Value::Str(prefix)]) => Value::Bool(s.starts_with(prefix.as_str())),
+            ("ends_with", [Value::Str(s), Value::Str(suffix)]) => Value::Bool(s.ends_with(suffix.as_str())),
+            ("byte_len", [Value::Str(s)]) => Value::Int(s.len() as i32),
+            ("chars", [Value::Str(s)]) => Value::list(s.chars().map(|c| Value::Str(c.to_string())).collect()),
+            // positions count chars, not bytes, so `"héllo".slice(1, 3)` is "él"
+            ("slice", [Value::Str(s), Value::Int(start), Value::Int(end)]) => {
+                let count = s.chars().count();
+                if *start < 0 || *end < *start || *end as usize > count {
+                    return fail(format!("slice {}..{} out of range for a string of {} chars", start, end, count), line);
+                }
+                Value::Str(s.chars().skip(*start as usize).take((end - start) as usize).collect())
+            }
+            ("find", [Value::Str(s), Value::Str(part)]) => match s.find(part.as_str()) {
+                Some(at) => Value::Int(s[..at].chars().count() as i32),
+                None => Value::None,
+            },
+            ("replace", [Value::Str(s), Value::Str(from), Value::Str(to)]) => {
+                Value::Str(s.replace(from.as_str(), to))
+            }
+            ("repeat", [Value::Str(_), Value::Int(times)]) if *times < 0 => {
+                return fail(format!("cannot repeat a string {} times", times), line)
+            }
+            ("repeat", [Value::Str(s), Value::Int(times)]) => Value::Str(s.repeat(*times as usize)),
+            ("parse_int", [Value::Str(text)]) => match text.trim().parse::<i32>() {
+                Ok(n) => Value::Ok(Box::new(Value::Int(n))),
+                Err(_) => Value::Err(Box::new(Value::Str(format!("`{}` is not an int", text)))),
+            },
+            ("parse_float", [Value::Str(text)]) => match text.trim().parse::<f64>() {
+                Ok(x) => Value::Ok(Box::new(Value::Float(x))),
+                Err(_) => Value::Err(Box::new(Value::Str(format!("`{}` is not a float", text)))),
+            },
+            ("join", [Value::List(items), Value::Str(sep)]) => {
+                let parts: Vec<String> = items.borrow().iter().map(|item| item.to_string()).collect();
+                Value::Str(parts.join(sep))
+            }
+            ("abs", [Value::Float(x)]) => Value::Float(x.abs()),
+            ("abs", [Value::Big(n)]) => Value::Big(if *n < BigInt::zero() { -n } else { n.clone() }),
+            ("abs", [value @ (Value::Int(_) | Value::Sized(..))]) => match value.as_integer() {
+                Some((_, n)) if n < 0 => unary(UnaryOp::Neg, value.clone(), line)?,
+                _ => value.clone(),
+            },
+            ("min" | "max", [a, b]) if matches!(a, Value::Int(_) | Value::Sized(..) | Value::Big(_) | Value::Float(_)) => {
+                let ordering = self.compare_values(a, b)?.unwrap_or(Ordering::Equal);
+                let first = (method == "min") == (ordering != Ordering::Greater);
+                let chosen = if first { a.clone() } else { b.clone() };
+                retype(a, chosen)
+            }
+            ("round", [Value::Float(x)]) => Value::Float(x.round()),
+            ("floor", [Value::Float(x)]) => Value::Float(x.floor()),
+            ("ceil", [Value::Float(x)]) => Value::Float(x.ceil()),
+            ("sqrt", [Value::Float(x)]) => Value::Float(x.sqrt()),
+            ("is_empty", [Value::List(items)]) => Value::Bool(items.borrow().is_empty()),
+            ("is_empty", [Value::Map(entries)]) => Value::Bool(entries.borrow().is_empty()),
+            ("contains", [Value::List(items), value]) => {
+                let items = items.borrow().clone();
+                let mut found = false;
+                for item in &items {
+                    if self.values_equal(item, value)? {
+                        found = true;
+                        break;
+                    }
+                }
+                Value::Bool(found)
+            }
+            ("pop", [Value::List(items)]) => items.borrow_mut().pop().unwrap_or(Value::None),
+            ("insert", [Value::List(items), index, value]) => {
+                let len = items.borrow().len();
+                let at = list_index(index, len + 1, line)?;
+                items.borrow_mut().insert(at, value.clone());
+                Value::Void
+            }
+            ("remove", [Value::List(items), index]) => {
+                let len = items.borrow().len();
+                let at = list_index(index, len, line)?;
+                items.borrow_mut().remove(at)
+            }
+            ("reverse", [Value::List(items)]) => {
+                items.borrow_mut().reverse();
+                Value::Void
+            }
+            ("keys", [Value::Map(entries)]) => Value::list(entries.borrow().iter().map(|(k, _)| k.clone()).collect()),
+            ("values", [Value::Map(entries)]) => Value::list(entries.borrow().iter().map(|(_, v)| v.clone()).collect()),
+            ("contains_key", [Value::Map(entries), key]) => Value::Bool(entries.borrow().iter().any(|(k, _)| k == key)),
+            ("get", [Value::Map(entries), key]) => {
+                entries.borrow().iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).unwrap_or(Value::None)
+            }
+            ("remove", [Value::Map(entries), key]) => {
+                let at = entries.borrow().iter().position(|(k, _)| k == key);
+                match at {
+                    Some(at) => entries.borrow_mut().remove(at).1,
+                    None => Value::None,
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    // trait methods dispatch on the type of their first argument, falling back to the built in
+    // implementations of `Show`, `Eq` and `Ord`
+    fn call_method(&mut self, name: &str, args: Vec<Value>, line: usize) -> Exec<Value> {
+        let receiver = match args.first() {
+            Some(receiver) => receiver.type_name().to_string(),
+            None => return fail(format!("undefined function `{}`", name), line),
+        };
+        if let Some(decl) = self.methods.get(&(receiver, name.to_string())) {
+            let decl = decl.clone();
+            return self.call_function(&decl, args, Vec::new());
+        }
+        let value = match (name, args.as_slice()) {
+            ("show", [value]) => Value::Str(self.format_value(value, false)?),
+            ("eq", [a, b]) => Value::Bool(self.values_equal(a, b)?),
+            ("cmp", [a, b]) => Value::Int(match self.compare_values(a, b)? {
+                Some(Ordering::Less) => -1,
+                Some(Ordering::Greater) => 1,
+                _ => 0,
+            }),
+            _ => return fail(format!("undefined function `{}`", name), line),
+        };
+        Ok(value)
+    }
+
+    fn call_function(
+        &mut self,
+        decl: &FunctionDecl,
+        args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> Exec<Value> {
+        let used = self.stack_base.saturating_sub(stack_address());
+        if self.depth == MAX_DEPTH || used > STACK_SIZE - STACK_MARGIN {
+            return fail(format!("too much recursion, `{}` was called {} calls deep", decl.name, self.depth), decl.line);
+        }
+        self.depth += 1;
+        // a function sees the globals and its own locals, never its caller's locals
+        let caller = self.scopes.split_off(1);
+        self.scopes.push(HashMap::new());
+        let result = self.bind_params(decl, args, named).and_then(|()| {
+            if decl.native {
+                return self.call_native(decl);
+            }
+            self.exec_block(&decl.body)
+        });
+        self.scopes.truncate(1);
+        self.scopes.extend(caller);
+        self.depth -= 1;
+        match result {
+            Ok(()) => Ok(Value::Void),
+            Err(Unwind::Return(value)) => coerce(value, &decl.ret, decl.line),
+            Err(error) => Err(error),
+        }
+    }
+
+    // the next line of standard input without its line ending, none at the end
+    fn read_line(&mut self, line: usize) -> Exec<Value> {
+        let mut text = String::new();
+        match self.input.read_line(&mut text) {
+            Ok(0) => Ok(Value::None),
+            Ok(_) => {
+                let end = text.trim_end_matches(['\n', '\r']).len();
+                text.truncate(end);
+                Ok(Value::Str(text))
+            }
+            Err(e) => fail(format!("cannot read standard input: {}", e), line),
+        }
+    }
+
+    // output that can't be written is a runtime error, except that when whatever reads it has
+    // gone away, like `head` in `cube x.cu | head`, the program just stops
+    fn output(&mut self, written: io::Result<()>, line: usize) -> Exec<()> {
+        match written {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Err(Unwind::Exit(0)),
+            Err(error) => fail(format!("cannot write the output: {}", error), line),
+        }
+    }
+
+    // a function of a standard module that the interpreter runs itself, with the parameters as
+    // bound in the new frame
+    fn call_native(&mut self, decl: &FunctionDecl) -> Exec<()> {
+        let frame = self.scopes.last().unwrap();
+        let args: Vec<Value> = decl.params.iter().map(|param| frame[&param.name].clone()).collect();
+        if let ("process.exit", [Value::Int(code)]) = (decl.name.as_str(), args.as_slice()) {
+            if !(0..=255).contains(code) {
+                return fail(format!("exit code {} is not from 0 to 255", code), decl.line);
+            }
+            let flushed = self.out.flush();
+            return match self.output(flushed, decl.line) {
+                Err(Unwind::Error(error)) => Err(Unwind::Error(error)),
+                _ => Err(Unwind::Exit(*code)),
+            };
+        }
+        match stdlib::call(&decl.name, &args, &mut self.context) {
+            Ok(value) => Err(Unwind::Return(value)),
+            Err(message) => fail(message, decl.line),
+        }
+    }
+
+    // fills the new frame: the positional arguments in order, then the named ones, then the defaults
+    // of the rest, which can use the parameters before them. a variadic parameter gets the
+    // positional arguments left over as a list
+    fn bind_params(&mut self, decl: &FunctionDecl, args: Vec<Value>, mut named: Vec<(String, Value)>) -> Exec<()> {
+        let mut args = args.into_iter();
+        for param in &decl.params {
+            let value = if param.variadic {
+                Value::list(args.by_ref().collect())
+            } else if let Some(value) = args.next() {
+                value
+            } else if let Some(i) = named.iter().position(|(name, _)| *name == param.name) {
+                named.swap_remove(i).1
+            } else if let Some(default) = &param.default {
+                self.eval_as(default, &param.ty)?
+            } else {
+                return fail(format!("missing argument `{}` of `{}`", param.name, decl.name), decl.line);
+            };
+            let value = coerce(value, &param.ty, decl.line)?;
+            self.scopes.last_mut().unwrap().insert(param.name.clone(), value);
+        }
+        Ok(())
+    }
+
+    // evaluates a call's arguments in order, keeping the named ones apart
+    fn eval_args(&mut self, args: &[Expr], values: &mut Vec<Value>) -> Exec<Vec<(String, Value)>> {
+        let mut named = Vec::new();
+        for arg in args {
+            match &arg.kind {
+                ExprKind::NamedArg(name, value) => named.push((name.clone(), self.eval(value)?)),
+                _ => values.push(self.eval(arg)?),
+            }
+        }
+        Ok(named)
+    }
+
+    fn has_method(&self, type_name: &str, method: &str) -> bool {
+        self.methods
+            .contains_key(&(type_name.to_string(), method.to_string()))
+    }
+
+    /// Formats a value the way `print` shows it, using `Show` implementations where there are
+    /// any. Strings nested in other values keep their quotes.
+    fn format_value(&mut self, value: &Value, nested: bool) -> Exec<String> {
+        let text = match value {
+            Value::Str(s) if nested => format!("{:?}", s),
+            Value::Struct(name, _) if self.has_method(name, "show") => {
+                match self.call_method("show", vec![value.clone()], 0)? {
+                    Value::Str(s) => s,
+                    other => other.to_string(),
+                }
+            }
+            Value::Struct(name, fields) => {
+                let fields = fields.borrow().clone();
+                let mut parts = Vec::new();
+                for (k, v) in &fields {
+                    parts.push(format!("{}: {}", k, self.format_value(v, true)?));
+                }
+                format!("{} {{ {} }}", name, parts.join(", "))
+            }
+            Value::List(items) => {
+                let items = items.borrow().clone();
+                let mut parts = Vec::new();
+                for item in &items {
+                    parts.push(self.format_value(item, true)?);
+                }
+                format!("[{}]", parts.join(", "))
+            }
+            Value::Tuple(items) => {
+                let mut parts = Vec::new();
+                for item in items {
+                    parts.push(self.format_value(item, true)?);
+                }
+                format!("({})", parts.join(", "))
+            }
+            Value::Map(entries) if !entries.borrow().is_empty() => {
+                let entries = entries.borrow().clone();
+                let mut parts = Vec::new();
+                for (k, v) in &entries {
+                    parts.push(format!("{}: {}", self.format_value(k, true)?, self.format_value(v, true)?));
+                }
+                format!("[{}]", parts.join(", "))
+            }
+            Value::Ok(inner) => format!("ok({})", self.format_value(inner, true)?),
+            Value::Err(inner) => format!("err({})", self.format_value(inner, true)?),
+            _ => value.to_string(),
+        };
+        Ok(text)
+    }
+
+    /// Formats a value for `"{x:spec}"` and `format`. Without a spec this is what `print` shows.
+    fn format_with(&mut self, value: &Value, spec: &FormatSpec, line: usize) -> Exec<String> {
+        // the checker catches these in literal templates, but not in a `format` template built
+        // at runtime or for a value of a type parameter
+        let int = matches!(value, Value::Int(_) | Value::Sized(..));
+        if let Some(radix) = spec.radix.filter(|_| !int) {
+            return fail(format!("radix `{}` only applies to ints, found {}", radix, value.type_name()), line);
+        }
+        if spec.precision.is_some() && int {
+            return fail(format!("precision only applies to floats and strings, found {}", value.type_name()), line);
+        }
+        let text = match value {
+            Value::Int(_) | Value::Sized(..) => spec.int(value.as_integer().unwrap().1),
+            Value::Float(x) => spec.float(*x),
+            _ => {
+                let text = self.format_value(value, false)?;
+                spec.text(&text)
+            }
+        };
+        Ok(text)
+    }
+
+    // format("{} is {:>5}", a, b)
+    fn format_template(&mut self, args: &[Value], line: usize) -> Exec<String> {
+        let template = match args.first() {
+            Some(Value::Str(template)) => template,
+            _ => return fail("format needs a template string", line),
+        };
+        let pieces = match parse_template(template) {
+            Ok(pieces) => pieces,
+            Err(message) => return fail(message, line),
+        };
+        let placeholders = pieces.iter().filter(|p| matches!(p, Piece::Placeholder(_))).count();
+        if placeholders != args.len() - 1 {
+            return fail(
+                format!("format string has {} placeholder(s) but {} value(s) were given", placeholders, args.len() - 1),
+                line,
+            );
+        }
+        let mut values = args[1..].iter();
+        let mut text = String::new();
+        for piece in &pieces {
+            match piece {
+                Piece::Text(s) => text.push_str(s),
+                Piece::Placeholder(spec) => {
+                    let value = values.next().unwrap();
+                    text.push_str(&self.format_with(value, spec, line)?);
+                }
+            }
+        }
+        Ok(text)
+    }
+
+    fn values_equal(&mut self, a: &Value, b: &Value) -> Exec<bool> {
+        let equal = match (a, b) {
+            (Value::Struct(name, _), Value::Struct(..)) if self.has_method(name, "eq") => {
+                matches!(self.call_method("eq", vec![a.clone(), b.clone()], 0)?, Value::Bool(true))
+            }
+            (Value::Struct(n1, f1), Value::Struct(n2, f2)) => {
+                let (f1, f2) = (f1.borrow().clone(), f2.borrow().clone());
+                if n1 != n2 || f1.len() != f2.len() {
+                    return Ok(false);
+                }
+                for ((_, x), (_, y)) in f1.iter().zip(&f2) {
+                    if !self.values_equal(x, y)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            (Value::List(x), Value::List(y)) => {
+                let (x, y) = (x.borrow().clone(), y.borrow().clone());
+                self.items_equal(&x, &y)?
+            }
+            (Value::Tuple(x), Value::Tuple(y)) => self.items_equal(x, y)?,
+            (Value::Map(x), Value::Map(y)) => {
+                let (x, y) = (x.borrow().clone(), y.borrow().clone());
+                if x.len() != y.len() {
+                    return Ok(false);
+                }
+                for (k, v) in &x {
+                    match y.iter().find(|(k2, _)| k2 == k) {
+                        Some((_, other)) if self.values_equal(v, other)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                true
+            }
+            (Value::Ok(x), Value::Ok(y)) | (Value::Err(x), Value::Err(y)) => self.values_equal(x, y)?,
+            _ => a == b,
+        };
+        Ok(equal)
+    }
+
+    fn items_equal(&mut self, x: &[Value], y: &[Value]) -> Exec<bool> {
+        if x.len() != y.len() {
+            return Ok(false);
+        }
+        for (a, b) in x.iter().zip(y) {
+            if !self.values_equal(a, b)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // lists and tuples compare item by item, a shorter one first when it runs out
+    fn compare_items(&mut self, x: &[Value], y: &[Value]) -> Exec<Option<Ordering>> {
+        for (a, b) in x.iter().zip(y) {
+            match self.compare_values(a, b)? {
+                Some(Ordering::Equal) => {}
+                other => return Ok(other),
+            }
+        }
+        Ok(Some(x.len().cmp(&y.len())))
+    }
+
+    fn compare_values(&mut self, a: &Value, b: &Value) -> Exec<Option<Ordering>> {
+        let ordering = match (a, b) {
+            (Value::Int(x), Value::Int(y)) => Some(x.cmp(y)),
+            (Value::Big(_), _) | (_, Value::Big(_)) => match (a.as_bigint(), b.as_bigint()) {
+                (Some(x), Some(y)) => Some(x.cmp(&y)),
+                _ => None,
+            },
+            _ if Value::integers(a, b).is_some() => {
+                let (_, x, y) = Value::integers(a, b).unwrap();
+                Some(x.cmp(&y))
+            }
+            (Value::Float(x), Value::Float(y)) => x.partial_cmp(y),
+            (Value::Str(x), Value::Str(y)) => Some(x.cmp(y)),
+            (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+            (Value::List(x), Value::List(y)) => {
+                let (x, y) = (x.borrow().clone(), y.borrow().clone());
+                self.compare_items(&x, &y)?
+            }
+            (Value::Tuple(x), Value::Tuple(y)) => self.compare_items(x, y)?,
+            (Value::Struct(..), Value::Struct(..)) => {
+                match self.call_method("cmp", vec![a.clone(), b.clone()], 0)? {
+                    Value::Int(n) => Some(n.cmp(&0)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        Ok(ordering)
+    }
+}
+
+// a stable merge sort. unlike `sort_by` it can't panic when a struct's `cmp` isn't a total
+// order, the items just come out in some order
+fn merge_sort(mut items: Vec<Value>, less: &mut impl FnMut(&Value, &Value) -> Exec<bool>) -> Exec<Vec<Value>> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, less)?;
+    let right = merge_sort(right, less)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        if less(b, a)? {
+            merged.push(right.next().unwrap());
+        } else {
+            merged.push(left.next().unwrap());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+fn list_index(index: &Value, len: usize, line: usize) -> Exec<usize> {
+    match index {
+        Value::Int(i) if *i >= 0 && (*i as usize) < len => Ok(*i as usize),
+        other => fail(format!("index {} out of range for length {}", other, len), line),
+    }
+}
+
+fn unary(op: UnaryOp, value: Value, line: usize) -> Exec<Value> {
+    let value = match (op, value) {
+        (UnaryOp::Neg, value @ (Value::Int(_) | Value::Sized(..))) => {
+            let (kind, n) = value.as_integer().unwrap();
+            match ints::arithmetic(BinaryOp::Sub, kind, 0, n) {
+                Ok(n) => Value::integer(kind, n),
+                Err(_) => return fail(format!("integer overflow: -({}) does not fit in {}", n, kind), line),
+            }
+        }
+        (UnaryOp::Neg, Value::Float(x)) => Value::Float(-x),
+        (UnaryOp::Neg, Value::Big(n)) => Value::Big(-&n),
+        (UnaryOp::Not, Value::Bool(b)) => Value::Bool(!b),
+        (UnaryOp::BitNot, value @ (Value::Int(_) | Value::Sized(..))) => {
+            let (kind, n) = value.as_integer().unwrap();
+            Value::integer(kind, kind.not(n))
+        }
+        (_, other) => return fail(format!("invalid operand {}", other), line),
+    };
+    Ok(value)
+}
+
+// what a `mut` variable, or one of its fields or elements, gets when it's set to `value`. taken
+// from another variable it's a copy, so changing it can't change that one, which may not be `mut`
+fn copy_of_place(expr: &Expr, value: Value) -> Value {
+    match expr.kind {
+        ExprKind::Identifier(_) | ExprKind::Field(..) | ExprKind::Index(..) => value.deep_copy(),
+        _ => value,
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value, line: usize) -> Exec<Value> {
+    // a plain int next to a bigint is a literal the checker gave the bigint type
+    if matches!(left, Value::Big(_)) || matches!(right, Value::Big(_)) {
+        if let (Some(a), Some(b)) = (left.as_bigint(), right.as_bigint()) {
+            if !op.is_arithmetic() {
+                return Ok(Value::Bool(compare(op, a.cmp(&b))));
+            }
+            return match bigint::arithmetic(op, &a, &b) {
+                Ok(n) => Ok(Value::Big(n)),
+                Err(message) => fail(message, line),
+            };
+        }
+    }
+    // a shift or a power keeps the type of its left side, the right one only says how far
+    if let (true, Some((kind, a)), Some((_, b))) = (op.is_shift_or_power(), left.as_integer(), right.as_integer()) {
+        return match ints::arithmetic(op, kind, a, b) {
+            Ok(n) => Ok(Value::integer(kind, n)),
+            Err(message) => fail(message, line),
+        };
+    }
+    if let Some((kind, a, b)) = Value::integers(&left, &right) {
+        if !op.is_arithmetic() {
+            return Ok(Value::Bool(compare(op, a.cmp(&b))));
+        }
+        return match ints::arithmetic(op, kind, a, b) {
+            Ok(n) => Ok(Value::integer(kind, n)),
+            Err(message) => fail(message, line),
+        };
+    }
+    let value = match (left, right) {
+        (Value::Float(a), Value::Float(b)) => match op {
+            BinaryOp::Add => Value::Float(a + b),
+            BinaryOp::Sub => Value::Float(a - b),
+            BinaryOp::Mul => Value::Float(a * b),
+            BinaryOp::Div => Value::Float(a / b),
+            BinaryOp::Mod => Value::Float(a % b),
+            BinaryOp::Pow => Value::Float(a.powf(b)),
+            _ => return fail(format!("invalid float operator {}", op), line),
+        },
+        (Value::Float(a), Value::Int(b)) if op == BinaryOp::Pow => Value::Float(a.powi(b)),
+        (Value::Str(a), Value::Str(b)) => match op {
+            BinaryOp::Add => Value::Str(a + &b),
+            _ => Value::Bool(compare(op, a.cmp(&b))),
+        },
+        (left, right) => {
+            return fail(format!("operator {} cannot be applied to {} and {}", op, left, right), line)
+        }
+    };
+    Ok(value)
+}
+
+// `x:u8 = 5` stores the literal as a u8, and so does passing it to a `u8` parameter, returning it
+// as one or putting it in a `u8` field. the checker has made sure it fits.
+fn coerce(value: Value, ty: &TypeExpr, line: usize) -> Exec<Value> {
+    let value = match (value, ty) {
+        (value @ (Value::Int(_) | Value::Sized(..)), TypeExpr::Named(name, _)) if name == "bigint" => {
+            Value::Big(value.as_bigint().unwrap())
+        }
+        (value @ (Value::Int(_) | Value::Sized(..)), TypeExpr::Named(name, _)) => {
+            match (IntKind::from_name(name), value.as_integer().unwrap()) {
+                (Some(kind), (_, n)) if kind.fits(n) => Value::integer(kind, n),
+                (Some(kind), (_, n)) => return fail(format!("{} does not fit in {}", n, kind), line),
+                (None, _) => value,
+            }
+        }
+        (value, TypeExpr::Optional(inner)) => coerce(value, inner, line)?,
+        (Value::Tuple(items), TypeExpr::Tuple(types)) => Value::Tuple(
+            items
+                .into_iter()
+                .zip(types)
+                .map(|(item, ty)| coerce(item, ty, line))
+                .collect::<Exec<_>>()?,
+        ),
+        (Value::List(items), TypeExpr::List(inner)) => {
+            let coerced: Exec<Vec<Value>> = items
+                .borrow()
+                .iter()
+                .map(|item| coerce(item.clone(), inner, line))
+                .collect();
+            *items.borrow_mut() = coerced?;
+            Value::List(items)
+        }
+        (Value::Map(entries), TypeExpr::Named(name, args)) if name == "map" && args.len() == 2 => {
+            let coerced: Exec<Vec<(Value, Value)>> = entries
+                .borrow()
+                .iter()
+                .map(|(k, v)| Ok((coerce(k.clone(), &args[0], line)?, coerce(v.clone(), &args[1], line)?)))
+                .collect();
+            *entries.borrow_mut() = coerced?;
+            Value::Map(entries)
+        }
+        (Value::Ok(value), TypeExpr::Named(name, args)) if name == "Result" && args.len() == 2 => {
+            Value::Ok(Box::new(coerce(*value, &args[0], line)?))
+        }
+        (Value::Err(error), TypeExpr::Named(name, args)) if name == "Result" && args.len() == 2 => {
+            Value::Err(Box::new(coerce(*error, &args[1], line)?))
+        }
+        (value, _) => value,
+    };
+    Ok(value)
+}
+
+// assigning a plain literal over a sized integer keeps the sized type
+fn retype(old: &Value, new: Value) -> Value {
+    match (old, new.as_integer()) {
+        (Value::Sized(kind, _), Some((_, n))) => Value::Sized(*kind, n),
+        (Value::Big(_), Some(_)) => Value::Big(new.as_bigint().unwrap()),
+        _ => new,
+    }
+}
+
+// `value as type`. going to a smaller integer type is checked like arithmetic is, and a float
+// loses its fraction
+fn cast(value: Value, ty: &TypeExpr, line: usize) -> Exec<Value> {
+    let name = match ty {
+        TypeExpr::Named(name, _) => name.as_str(),
+        _ => "",
+    };
+    if name == "float" {
+        return match value {
+            Value::Float(x) => Ok(Value::Float(x)),
+            Value::Big(n) => Ok(Value::Float(n.to_f64())),
+            value => match value.as_integer() {
+                Some((_, n)) => Ok(Value::Float(n as f64)),
+                None => fail(format!("cannot cast {} to float", value), line),
+            },
+        };
+    }
+    if name == "bigint" {
+        return match value {
+            Value::Float(x) if !x.is_finite() => fail(format!("cannot cast {} to bigint", x), line),
+            // whole floats are exact in a bigint, however big they are
+            Value::Float(x) => Ok(Value::Big(float_to_bigint(x.trunc()))),
+            value => match value.as_bigint() {
+                Some(n) => Ok(Value::Big(n)),
+                None => fail(format!("cannot cast {} to bigint", value), line),
+            },
+        };
+    }
+    let kind = match IntKind::from_name(name) {
+        Some(kind) => kind,
+        None => return fail(format!("cannot cast {} to {}", value, ty), line),
+    };
+    let n = match value {
+        Value::Big(n) => match n.to_i128() {
+            Some(n) => n,
+            None => return fail(format!("cannot cast {} to {}, it is out of range", n, kind), line),
+        },
+        Value::Float(x) if !x.is_finite() => {
+            return fail(format!("cannot cast {} to {}, it is out of range", x, kind), line)
+        }
+        // `as i128` saturates, so anything too big is caught by the range check below
+        Value::Float(x) => x.trunc() as i128,
+        value => match value.as_integer() {
+            Some((_, n)) => n,
+            None => return fail(format!("cannot cast {} to {}", value, kind), line),
+        },
+    };
+    if !kind.fits(n) {
+        return fail(format!("cannot cast {} to {}, it is out of range", n, kind), line);
+    }
+    Ok(Value::integer(kind, n))
+}
+
+// a whole float, built up from its binary digits so nothing is lost above i128
+fn float_to_bigint(x: f64) -> BigInt {
+    if x.abs() < 1e36 {
+        return BigInt::from_i128(x as i128);
+    }
+    let bits = x.abs().to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as u32 - 1075;
+    let mantissa = BigInt::from_i128(((bits & ((1 << 52) - 1)) | (1 << 52)) as i128);
+    let magnitude = &mantissa * &BigInt::from_i128(2).pow(exponent);
+    if x < 0.0 {
+        -&magnitude
+    } else {
+        magnitude
+    }
+}
+
+// `pow(base, exponent)`. integers can't take a negative exponent and overflow like `*` does
+fn power(base: Value, exponent: i32, line: usize) -> Exec<Value> {
+    match base {
+        Value::Float(x) => Ok(Value::Float(x.powi(exponent))),
+        base if exponent < 0 => fail(format!("cannot raise {} to the negative power {}", base, exponent), line),
+        Value::Big(n) => Ok(Value::Big(n.pow(exponent as u32))),
+        base => {
+            let (kind, n) = match base.as_integer() {
+                Some(integer) => integer,
+                None => return fail(format!("cannot raise {} to a power", base), line),
+            };
+            match ints::power(kind, n, exponent as u32) {
+                Ok(n) => Ok(Value::integer(kind, n)),
+                Err(message) => fail(message, line),
+            }
+        }
+    }
+}
+
+fn compare(op: BinaryOp, ordering: Ordering) -> bool {
+    use std::cmp::Ordering::*;
+    match op {
+        BinaryOp::Eq => ordering == Equal,
+        BinaryOp::Ne => ordering != Equal,
+        BinaryOp::Lt => ordering == Less,
+        BinaryOp::Gt => ordering == Greater,
+        BinaryOp::Le => ordering != Greater,
+        BinaryOp::Ge => ordering != Less,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::check;
+    use crate::parser::parse_source;
+
+    fn run(source: &str) -> String {
+        let program = parse_source(source).unwrap();
+        check(&program).unwrap();
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.run(&program);
+        let mut output = String::from_utf8(interpreter.into_output()).unwrap();
+        if let Err(error) = result {
+            output.push_str(&error.to_string());
+        }
+        output
+    }
+
+    #[test]
+    fn test_generic_functions_and_structs_run() {
+        let source = r#"
+            struct Pair<A, B> { left:A, right:B }
+            first<T>(xs:T[]):T { return xs[0]; }
+            swap<A, B>(p:Pair<A, B>):Pair<B, A> { return Pair { left: p.right, right: p.left }; }
+
+            print(first([3, 4]), first(["a", "b"]))
+            p:Pair<int, str> = swap(Pair { left: "one", right: 1 });
+            print(p.left + 1, p.right)
+        "#;
+        assert_eq!(run(source), "3 a\n2 one\n");
+    }
+
+    #[test]
+    fn test_traits_drive_print_equality_and_sort() {
+        let source = r#"
+            struct Money { cents:int }
+            impl Show for Money { show(self):str { return "$" + show(self.cents); } }
+            impl Eq for Money { eq(self, other:Self):bool { return self.cents == other.cents; } }
+            impl Ord for Money { cmp(self, other:Self):int { return self.cents - other.cents; } }
+
+            mut ms:Money[] = [Money { cents: 5 }, Money { cents: 1 }, Money { cents: 3 }];
+            sort(ms)
+            print(ms, ms[0] == Money { cents: 1 }, ms[0] < ms[1])
+        "#;
+        assert_eq!(run(source), "[$1, $3, $5] true true\n");
+    }
+
+    #[test]
+    fn test_sort_survives_a_cmp_that_is_not_an_order() {
+        let source = r#"
+            struct Liar { n:int }
+            impl Ord for Liar { cmp(self, other:Self):int { return -1; } }
+            impl Eq for Liar { eq(self, other:Self):bool { return false; } }
+
+            mut ls:Liar[] = [];
+            for i in 0..100 { push(ls, Liar { n: i }); }
+            sort(ls)
+            print(len(ls))
+            nan := (0.0 - 1.0).sqrt();
+            mut fs:float[] = [3.0, nan, 1.0, 2.0];
+            sort(fs)
+            print(fs)
+        "#;
+        assert_eq!(run(source), "100\n[1.0, 2.0, 3.0, NaN]\n");
+    }
+
+    #[test]
+    fn test_optionals_and_coalesce() {
+        let source = r#"
+            find(xs:int[], target:int):int? {
+                mut i:int = 0;
+                while i < len(xs) {
+                    if xs[i] == target { return i; }
+                    i = i + 1;
+                }
+                return none;
+            }
+            report(xs:int[]):void {
+                at:int? = find(xs, 5);
+                if at == none { return; }
+                print(at + 1, find(xs, 9), find(xs, 9) ?? -1)
+            }
+            report([4, 5])
+        "#;
+        assert_eq!(run(source), "2 none -1\n");
+    }
+
+    #[test]
+    fn test_results_propagate_and_runtime_errors_are_caught() {
+        let source = r#"
+            half(n:int):Result<int, str> {
+                if n % 2 != 0 { return err("odd"); }
+                return ok(n / 2);
+            }
+            quarter(n:int):Result<int, str> {
+                h:int = half(n)?;
+                return half(h);
+            }
+            print(quarter(8), quarter(6), unwrap_or(quarter(3), 0))
+            try { print(1 / 0); } catch (e) { print("caught", e); }
+            print(unwrap(quarter(2)))
+        "#;
+        assert_eq!(
+            run(source),
+            "ok(2) err(\"odd\") 0\ncaught division by zero\nruntime error: called unwrap on err(\"odd\") at line 12"
+        );
+    }
+
+    #[test]
+    fn test_interpolation_and_format_share_print_formatting() {
+        let source = r#"
+            struct P { x:int }
+            impl Show for P { show(self):str { return "P{{{self.x}}}"; } }
+            p := P { x: 7 }
+            n := 42
+            print("{p} {n:>4}|{n:x}|{1.5:.2}|{{}}")
+            print(format("{:<3}|{:^5}|{}", "ab", p, [p]))
+        "#;
+        assert_eq!(run(source), "P{7}   42|2a|1.50|{}\nab |P{7} |[P{7}]\n");
+    }
+
+    #[test]
+    fn test_a_spec_that_does_not_fit_the_value_is_a_runtime_error() {
+        let source = r#"
+            fmt := "{:x}"
+            try { print(format(fmt, 2.5)); } catch (e) { print(e); }
+            describe<T>(x:T):str { return "{x:.1}"; }
+            print(describe(7))
+        "#;
+        assert_eq!(
+            run(source),
+            "radix `x` only applies to ints, found float\nruntime error: precision only applies to floats and strings, found int at line 4"
+        );
+    }
+
+    #[test]
+    fn test_for_loops_over_ranges_collections_and_iterators() {
+        let source = r#"
+            struct Countdown { n:int }
+            impl Iterator<int> for Countdown {
+                next(mut self):int? {
+                    if (self.n == 0) { return none; }
+                    self.n = self.n - 1;
+                    return self.n + 1;
+                }
+            }
+            mut seen:int[] = []
+            for i in 0..3 { push(seen, i); }
+            for i in 10..=0 step -5 { push(seen, i); }
+            for n in (Countdown { n: 2 }) { push(seen, n); }
+            print(seen)
+            for (k, v) in ["a": 1, "b": 2] { print(k, v); }
+            for c in "hi" { print(c); }
+            try { for i in 0..1 step 0 { } } catch (e) { print(e); }
+        "#;
+        assert_eq!(
+            run(source),
+            "[0, 1, 2, 10, 5, 0, 2, 1]\na 1\nb 2\nh\ni\nthe step of a range cannot be zero\n"
+        );
+    }
+
+    #[test]
+    fn test_sized_integers_trap_wrap_saturate_and_cast() {
+        let source = r#"
+            struct Pixel { r:u8 }
+            a:u8 = 200
+            p := Pixel { r: 250 }
+            print(a +% 100, a +| 100, p.r +| 10, -128i8, 3000000000i64 * 3)
+            print(2.9 as int, 300 as u16, (7 as u64) / 2, "{255u8:x}")
+            try { print(a + 100); } catch (e) { print(e); }
+            try { print(256 as u8); } catch (e) { print(e); }
+        "#;
+        assert_eq!(
+            run(source),
+            "44 255 255 -128 9000000000\n2 300 3 ff\n\
+             integer overflow: 200 + 100 does not fit in u8\n\
+             cannot cast 256 to u8, it is out of range\n"
+        );
+    }
+
+    #[test]
+    fn test_mut_variables_copy_what_they_are_given() {
+        let source = r#"
+            struct P { a:int }
+            xs := [[1], [2]]
+            mut ys := xs
+            ys[0].push(9)
+            mut zs:int[][] = []
+            zs = xs
+            zs.push([3])
+            p := P { a: 1 }
+            mut q := p
+            q.a = 2
+            print(xs, ys, zs, p, q)
+        "#;
+        assert_eq!(run(source), "[[1], [2]] [[1, 9], [2]] [[1], [2], [3]] P { a: 1 } P { a: 2 }\n");
+    }
+
+    #[test]
+    fn test_runaway_recursion_is_a_runtime_error() {
+        let source = r#"
+            f(n:int):int { if n == 0 { return 0; } return 1 + f(n - 1); }
+            print(f(5000))
+            try { print(f(20000)); } catch (e) { print(e); }
+        "#;
+        // the same stack `main` gives the interpreter
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn(move || run(source)).unwrap();
+        assert_eq!(thread.join().unwrap(), "5000\ntoo much recursion, `f` was called 10000 calls deep\n");
+    }
+
+    #[test]
+    fn test_literals_have_the_type_the_checker_gave_them() {
+        let source = r#"
+            x: u64 = 5
+            mut xs:u8[] = []
+            xs.push(200)
+            b:u8 = 255
+            v := [200u8, 100]
+            c := [b, 255]
+            id<T>(a:T):T { return a; }
+            d:u8 = id(250)
+            print(xs[0] +% 100, v[1] +% 200, c[1] +% 1, d +% 10, 3000000000 * x)
+            z := 3000000000 * x - 20000000000
+            print(z)
+        "#;
+        assert_eq!(
+            run(source),
+            "44 44 0 4 15000000000\n\
+             runtime error: integer overflow: 15000000000 - 20000000000 does not fit in u64 at line 11"
+        );
+    }
+
+    #[test]
+    fn test_literals_become_bigints_where_expected_or_too_big() {
+        let source = r#"
+            mut xs:bigint[] = []
+            xs.push(2000000000)
+            b:bigint? = 5
+            x := 1000000000000000000000000000000000000000000
+            print(xs[0] * xs[0], b, x + 1)
+        "#;
+        assert_eq!(run(source), "4000000000000000000 5 1000000000000000000000000000000000000000001\n");
+    }
+
+    #[test]
+    fn test_bigints_do_exact_arithmetic_and_convert() {
+        let source = r#"
+            mut f:bigint = 1
+            for i in 1..=25 { f = f * (i as bigint); }
+            print(f, f / 1000000007, -f % 1000n, pow(2n, 70) > f)
+            print(parse_bigint("123456789012345678901234567890"), parse_bigint("12x"))
+            print(1000n as int, to_str(f) == "{f}")
+            try { print(f as int); } catch (e) { print(e); }
+        "#;
+        assert_eq!(
+            run(source),
+            "15511210043330985984000000 15511209934752516 0 false\n\
+             ok(123456789012345678901234567890) err(\"`12x` is not an integer\")\n1000 true\n\
+             cannot cast 15511210043330985984000000 to int, it is out of range\n"
+        );
+    }
+
+    #[test]
+    fn test_bit_operators_shifts_and_powers() {
+        let source = r#"
+            const MASK:u8 = ~(1 << 3);
+            big:u64 = 1 << 40;
+            print(6 & 3, 6 | 3, 6 ^ 3, ~5, -16 >> 2, 200u8 << 1, MASK, big)
+            print(2 ** 10, -2 ** 2, 2 ** 3 ** 2, 2.0 ** -1, 3n ** 40, 5 & 1 == 1)
+            try { print(1 << 32); } catch (e) { print(e); }
+        "#;
+        assert_eq!(
+            run(source),
+            "2 7 5 -6 -4 144 247 1099511627776\n1024 -4 512 0.5 12157665459056928801 true\n\
+             cannot shift int by 32, it only has 32 bits\n"
+        );
+    }
+
+    #[test]
+    fn test_method_calls_use_the_builtin_table_then_functions() {
+        let source = r#"
+            trait Area { area(self):float }
+            struct Square { side:float }
+            impl Area for Square { area(self):float { return self.side * self.side; } }
+            double:int(n:int) { return n * 2; }
+
+            print(" a,b ".trim().split(","), "Hi".to_upper(), (-5).abs(), 3.max(7), 2.5.round())
+            mut xs := [3, 1, 2];
+            xs.push(4);
+            xs.sort();
+            print(xs.pop(), xs, xs.contains(2), 4.double(), (Square { side: 2.0 }).area())
+            ages := ["ann": 31];
+            print(ages.keys(), ages.get("bob"), ages.contains_key("ann"))
+        "#;
+        assert_eq!(
+            run(source),
+            "[\"a\", \"b\"] HI 5 7 3.0\n4 [1, 2, 3] true 8 4.0\n[\"ann\"] none true\n"
+        );
+    }
+
+    #[test]
+    fn test_if_expressions_and_block_values() {
+        let source = r#"
+            sign(x:int):int { return if (x > 0) { 1 } else if x < 0 { -1 } else { 0 }; }
+            x := 5;
+            y:int = if (x > 0) { 1 } else { -1 };
+            z := {
+                a := x * 2;
+                a + 1
+            };
+            big:u64 = if x > 1 { 100000 * 100000 } else { 0 };
+            print(y, sign(-3), sign(0), z, big, if x == 5 { "five" } else { "other" })
+        "#;
+        assert_eq!(run(source), "1 -1 0 11 10000000000 five\n");
+    }
+
+    #[test]
+    fn test_functions_return_their_last_expression() {
+        let source = r#"
+            f:int(x:int) { if x > 0 { 1 } else { 2 } }
+            g(x:int):str {
+                if x == 0 { return "zero"; }
+                y := x * 2;
+                "{y}"
+            }
+            h(x:int):int { try { 10 / x } catch (e) { -1 } }
+            print(f(5), f(-5), g(0), g(4), h(2), h(0))
+        "#;
+        assert_eq!(run(source), "1 2 zero 8 5 -1\n");
+    }
+
+    #[test]
+    fn test_tuples_destructure_in_declarations_params_and_loops() {
+        let source = r#"
+            divmod(a:int, b:int):(int, int) { return (a / b, a % b); }
+            add((x, y):(int, int)):int { return x + y; }
+            (q, r) := divmod(17, 5);
+            pair:(u8, str) = (200, "one");
+            people:(str, int)[] = [("ann", 31), ("bob", 25)];
+            for (name, age) in people { print(name, age) }
+            print(q, r, pair, pair.0 +| 100, add((3, 4)), (1, 2) < (1, 3))
+        "#;
+        assert_eq!(run(source), "ann 31\nbob 25\n3 2 (200, \"one\") 255 7 true\n");
+    }
+
+    #[test]
+    fn test_default_named_and_variadic_arguments() {
+        let source = r#"
+            connect(host:str, port:int = 80, secure:bool = port == 443):str { return "{host}:{port} {secure}"; }
+            sum(xs: ...int):int {
+                mut total := 0;
+                for x in xs { total = total + x; }
+                return total;
+            }
+            print(connect("a"), connect(host: "b", port: 443), connect("c", secure: true))
+            print(sum(), sum(1, 2, 3), "d".connect(port: 1))
+        "#;
+        assert_eq!(run(source), "a:80 false b:443 true c:80 true\n0 6 d:1 false\n");
+    }
+
+    #[test]
+    fn test_operators_call_the_trait_methods_of_structs() {
+        let source = r#"
+            struct Vec2 { x:float, y:float }
+            impl Add for Vec2 { add(self, other:Vec2):Vec2 { return Vec2 { x: self.x + other.x, y: self.y + other.y }; } }
+            impl Neg for Vec2 { neg(self):Vec2 { return Vec2 { x: -self.x, y: -self.y }; } }
+            impl Eq for Vec2 { eq(self, other:Vec2):bool { return self.x == other.x && self.y == other.y; } }
+            total<T: Add>(items:T[], start:T):T {
+                mut sum := start;
+                for item in items { sum = sum + item; }
+                return sum;
+            }
+            a := Vec2 { x: 1.0, y: 2.0 };
+            print(a + a, -a, a + a == Vec2 { x: 2.0, y: 4.0 })
+            print(total([a, a, a], Vec2 { x: 0.0, y: 0.0 }), total([1, 2, 3], 0))
+        "#;
+        assert_eq!(
+            run(source),
+            "Vec2 { x: 2.0, y: 4.0 } Vec2 { x: -1.0, y: -2.0 } true\nVec2 { x: 3.0, y: 6.0 } 6\n"
+        );
+    }
+
+    #[test]
+    fn test_string_methods_count_chars_not_bytes() {
+        let source = r#"
+            s := "héllo wörld";
+            print(len(s), s.byte_len(), s.slice(1, 4), s.find("w"), s.find("z"), s.chars()[1])
+            print(s.replace("ö", "o"), "ab".repeat(3), "  x ".trim_start() + "|", "Straße".to_upper())
+            print("a,b,c".split(",").join("-"), " 42 ".parse_int(), "4x".parse_int(), "2.5".parse_float())
+        "#;
+        assert_eq!(
+            run(source),
+            "11 13 éll 6 none é\nhéllo world ababab x | STRASSE\na-b-c ok(42) err(\"`4x` is not an int\") ok(2.5)\n"
+        );
+    }
+
+    #[test]
+    fn test_input_builtins_read_standard_input() {
+        let source = r#"
+            name := input("name? ") ?? "nobody";
+            first := read_line();
+            print("hi", name, first)
+            print(read_all().split("\n"), read_line(), input("again? "))
+        "#;
+        let program = parse_source(source).unwrap();
+        check(&program).unwrap();
+        let mut interpreter = Interpreter::new(Vec::new()).with_input("ann\r\n1\n2\n3");
+        interpreter.run(&program).unwrap();
+        let output = String::from_utf8(interpreter.into_output()).unwrap();
+        assert_eq!(output, "name? hi ann 1\nagain? [\"2\", \"3\"] none none\n");
+    }
+}
@@ -0,0 +1,193 @@
+// ints.rs
+// the sized integer types. `int` is an `i32`, the others are `i8`..`i64` and `u8`..`u64`. their
+// arithmetic is done in i128 and checked against the type's range, so overflow is a runtime
+// error unless a wrapping (`+%`) or saturating (`+|`) operator asks for something else.
+
+use crate::ast::BinaryOp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntKind {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl IntKind {
+    /// The kind a type name or literal suffix stands for. `i32` is another name for `int`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let kind = match name {
+            "i8" => IntKind::I8,
+            "i16" => IntKind::I16,
+            "i32" | "int" => IntKind::I32,
+            "i64" => IntKind::I64,
+            "u8" => IntKind::U8,
+            "u16" => IntKind::U16,
+            "u32" => IntKind::U32,
+            "u64" => IntKind::U64,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntKind::I8 => "i8",
+            IntKind::I16 => "i16",
+            IntKind::I32 => "int",
+            IntKind::I64 => "i64",
+            IntKind::U8 => "u8",
+            IntKind::U16 => "u16",
+            IntKind::U32 => "u32",
+            IntKind::U64 => "u64",
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntKind::I8 | IntKind::U8 => 8,
+            IntKind::I16 | IntKind::U16 => 16,
+            IntKind::I32 | IntKind::U32 => 32,
+            IntKind::I64 | IntKind::U64 => 64,
+        }
+    }
+
+    pub fn signed(self) -> bool {
+        matches!(self, IntKind::I8 | IntKind::I16 | IntKind::I32 | IntKind::I64)
+    }
+
+    pub fn min(self) -> i128 {
+        if self.signed() {
+            -(1 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i128 {
+        if self.signed() {
+            (1 << (self.bits() - 1)) - 1
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+
+    pub fn fits(self, n: i128) -> bool {
+        (self.min()..=self.max()).contains(&n)
+    }
+
+    // keeps the low `bits` bits, like a cpu register would
+    pub fn wrap(self, n: i128) -> i128 {
+        let modulus = 1i128 << self.bits();
+        let low = n.rem_euclid(modulus);
+        if low > self.max() {
+            low - modulus
+        } else {
+            low
+        }
+    }
+
+    pub fn saturate(self, n: i128) -> i128 {
+        n.clamp(self.min(), self.max())
+    }
+
+    /// `~n`, every bit of `n` flipped.
+    pub fn not(self, n: i128) -> i128 {
+        if self.signed() {
+            !n
+        } else {
+            self.max() ^ n
+        }
+    }
+}
+
+impl std::fmt::Display for IntKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Applies an arithmetic operator to two integers of the same kind. Overflow is an error for
+/// the plain operators, wraps around for `+%` `-%` `*%` and sticks at the limit for `+|` `-|` `*|`.
+pub fn arithmetic(op: BinaryOp, kind: IntKind, a: i128, b: i128) -> Result<i128, String> {
+    // the bit operators can't overflow, both sides already fit in `kind` and so does the result
+    match op {
+        BinaryOp::BitAnd => return Ok(a & b),
+        BinaryOp::BitOr => return Ok(a | b),
+        BinaryOp::BitXor => return Ok(a ^ b),
+        BinaryOp::Shl | BinaryOp::Shr if !(0..kind.bits() as i128).contains(&b) => {
+            return Err(format!("cannot shift {} by {}, it only has {} bits", kind, b, kind.bits()))
+        }
+        // bits shifted past the top are dropped
+        BinaryOp::Shl => return Ok(kind.wrap(a.wrapping_shl(b as u32))),
+        BinaryOp::Shr => return Ok(a >> b),
+        BinaryOp::Pow if b < 0 => {
+            return Err(format!("cannot raise {} to the negative power {}", a, b))
+        }
+        BinaryOp::Pow => return power(kind, a, u32::try_from(b).unwrap_or(u32::MAX)),
+        _ => {}
+    }
+    // only u64 * u64 can leave i128
+    let exact = match op {
+        BinaryOp::Add | BinaryOp::WrappingAdd | BinaryOp::SaturatingAdd => a.checked_add(b),
+        BinaryOp::Sub | BinaryOp::WrappingSub | BinaryOp::SaturatingSub => a.checked_sub(b),
+        BinaryOp::Mul | BinaryOp::WrappingMul | BinaryOp::SaturatingMul => a.checked_mul(b),
+        BinaryOp::Div | BinaryOp::Mod if b == 0 => return Err("division by zero".to_string()),
+        BinaryOp::Div => Some(a / b),
+        BinaryOp::Mod => Some(a % b),
+        _ => return Err(format!("`{}` is not an arithmetic operator", op)),
+    };
+    match op {
+        BinaryOp::WrappingAdd => Ok(kind.wrap(a.wrapping_add(b))),
+        BinaryOp::WrappingSub => Ok(kind.wrap(a.wrapping_sub(b))),
+        BinaryOp::WrappingMul => Ok(kind.wrap(a.wrapping_mul(b))),
+        BinaryOp::SaturatingAdd | BinaryOp::SaturatingSub | BinaryOp::SaturatingMul => {
+            Ok(match exact {
+                Some(n) => kind.saturate(n),
+                None if (a < 0) != (b < 0) => kind.min(),
+                None => kind.max(),
+            })
+        }
+        _ => match exact {
+            Some(n) if kind.fits(n) => Ok(n),
+            _ => Err(format!("integer overflow: {} {} {} does not fit in {}", a, op, b, kind)),
+        },
+    }
+}
+
+/// `base` to the power of `exponent`, an overflow error if it doesn't fit in `kind`.
+pub fn power(kind: IntKind, base: i128, exponent: u32) -> Result<i128, String> {
+    match base.checked_pow(exponent) {
+        Some(n) if kind.fits(n) => Ok(n),
+        _ => Err(format!("integer overflow: {} to the power of {} does not fit in {}", base, exponent, kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow_traps_wraps_or_saturates() {
+        assert_eq!(arithmetic(BinaryOp::Add, IntKind::U8, 200, 55), Ok(255));
+        assert_eq!(
+            arithmetic(BinaryOp::Add, IntKind::U8, 200, 56),
+            Err("integer overflow: 200 + 56 does not fit in u8".to_string())
+        );
+        assert_eq!(arithmetic(BinaryOp::WrappingAdd, IntKind::U8, 200, 56), Ok(0));
+        assert_eq!(arithmetic(BinaryOp::WrappingSub, IntKind::I8, -128, 1), Ok(127));
+        assert_eq!(arithmetic(BinaryOp::SaturatingSub, IntKind::U8, 3, 5), Ok(0));
+        assert!(arithmetic(BinaryOp::Div, IntKind::I8, -128, -1).is_err());
+        let big = IntKind::U64.max();
+        assert_eq!(arithmetic(BinaryOp::SaturatingMul, IntKind::U64, big, big), Ok(big));
+        assert_eq!(arithmetic(BinaryOp::WrappingMul, IntKind::U64, big, big), Ok(1));
+        assert_eq!(arithmetic(BinaryOp::Shl, IntKind::U8, 200, 1), Ok(144));
+        assert_eq!(arithmetic(BinaryOp::Shr, IntKind::I8, -128, 7), Ok(-1));
+        assert!(arithmetic(BinaryOp::Shl, IntKind::U8, 1, 8).is_err());
+        assert_eq!(IntKind::U8.not(0), 255);
+    }
+}
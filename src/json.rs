@@ -0,0 +1,525 @@
+// json.rs
+// reading and writing JSON for the `json` standard module. text is parsed into a `Json` tree
+// first, then decoded into a cube value of the type the program asked for, so a value that
+// doesn't fit can be reported along with where it is in the document, like `$.servers[2].port`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{StructDecl, TypeExpr};
+use crate::bigint::BigInt;
+use crate::ints::IntKind;
+use crate::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    // kept as written so big integers don't go through a float
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // what the value is called in errors
+    fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "a boolean",
+            Json::Number(_) => "a number",
+            Json::String(_) => "a string",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+}
+
+// how many arrays and objects deep a document can go, each level is a few rust calls deep in the
+// reader and again in decode
+const MAX_NESTING: usize = 512;
+
+/// Parses a whole JSON document.
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut reader = Reader { chars: text.chars().collect(), pos: 0, depth: 0 };
+    let json = reader.value()?;
+    reader.skip_whitespace();
+    if reader.pos < reader.chars.len() {
+        return Err(reader.error("unexpected text after the value"));
+    }
+    Ok(json)
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize,
+    // arrays and objects the reader is inside of
+    depth: usize,
+}
+
+impl Reader {
+    fn error(&self, message: &str) -> String {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        format!("invalid JSON at line {}, column {}: {}", line, column, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", expected)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        if matches!(self.chars.get(self.pos), Some('{' | '[')) {
+            if self.depth == MAX_NESTING {
+                return Err(self.error(&format!("arrays and objects are nested more than {} deep", MAX_NESTING)));
+            }
+            self.depth += 1;
+            let nested = self.nested();
+            self.depth -= 1;
+            return nested;
+        }
+        match self.chars.get(self.pos) {
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => {
+                for (word, json) in [("true", Json::Bool(true)), ("false", Json::Bool(false)), ("null", Json::Null)] {
+                    if self.chars[self.pos..].starts_with(&word.chars().collect::<Vec<char>>()) {
+                        self.pos += word.len();
+                        return Ok(json);
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+            None => Err(self.error("the text ended where a value was expected")),
+        }
+    }
+
+    // an object or an array
+    fn nested(&mut self) -> Result<Json, String> {
+        match self.chars[self.pos] {
+            '{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.eat('}') {
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.get(self.pos) != Some(&'"') {
+                        return Err(self.error("expected a string as the key"));
+                    }
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    if !self.eat(',') {
+                        break;
+                    }
+                }
+                self.expect('}')?;
+                Ok(Json::Object(fields))
+            }
+            _ => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if !self.eat(',') {
+                        break;
+                    }
+                }
+                self.expect(']')?;
+                Ok(Json::Array(items))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        let digits = |reader: &mut Reader| {
+            let from = reader.pos;
+            while reader.chars.get(reader.pos).is_some_and(|c| c.is_ascii_digit()) {
+                reader.pos += 1;
+            }
+            reader.pos > from
+        };
+        self.pos += (self.chars[self.pos] == '-') as usize;
+        // no leading zeros, `0` is fine but `01` isn't
+        let leading_zero = self.chars.get(self.pos) == Some(&'0')
+            && self.chars.get(self.pos + 1).is_some_and(|c| c.is_ascii_digit());
+        let mut valid = digits(self) && !leading_zero;
+        if self.chars.get(self.pos) == Some(&'.') {
+            self.pos += 1;
+            valid &= digits(self);
+        }
+        if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+            self.pos += 1;
+            if matches!(self.chars.get(self.pos), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            valid &= digits(self);
+        }
+        if !valid {
+            return Err(self.error("invalid number"));
+        }
+        Ok(Json::Number(self.chars[start..self.pos].iter().collect()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                Some(c) => *c,
+                None => return Err(self.error("the string is never closed")),
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = self.chars.get(self.pos).copied();
+                    self.pos += 1;
+                    match escaped {
+                        Some('"') => text.push('"'),
+                        Some('\\') => text.push('\\'),
+                        Some('/') => text.push('/'),
+                        Some('b') => text.push('\u{8}'),
+                        Some('f') => text.push('\u{c}'),
+                        Some('n') => text.push('\n'),
+                        Some('r') => text.push('\r'),
+                        Some('t') => text.push('\t'),
+                        Some('u') => text.push(self.unicode_escape()?),
+                        _ => return Err(self.error("invalid escape in string")),
+                    }
+                }
+                c if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                c => text.push(c),
+            }
+        }
+    }
+
+    // `\u00e9`, or a pair of them for a character outside the basic plane
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+        if self.chars.get(self.pos) != Some(&'\\') || self.chars.get(self.pos + 1) != Some(&'u') {
+            return Err(self.error("unpaired surrogate in unicode escape"));
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("unpaired surrogate in unicode escape"));
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+        if digits.len() != 4 {
+            return Err(self.error("invalid unicode escape"));
+        }
+        self.pos += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error("invalid unicode escape"))
+    }
+}
+
+/// Turns parsed JSON into a value of type `ty`: null into an optional, numbers into the number
+/// types, arrays into lists and tuples, objects into maps and structs. A struct needs each of
+/// its fields and no others. `path` is where `json` is in the document, `$` for all of it.
+pub fn decode(json: &Json, ty: &TypeExpr, structs: &HashMap<String, StructDecl>, path: &str) -> Result<Value, String> {
+    let mismatch = || Err(format!("expected {} at {}, found {}", ty, path, json.kind()));
+    let value = match (ty, json) {
+        (TypeExpr::Optional(_), Json::Null) => Value::None,
+        (TypeExpr::Optional(inner), json) => decode(json, inner, structs, path)?,
+        (TypeExpr::List(item), Json::Array(items)) => {
+            let mut values = Vec::new();
+            for (i, json) in items.iter().enumerate() {
+                values.push(decode(json, item, structs, &format!("{}[{}]", path, i))?);
+            }
+            Value::list(values)
+        }
+        (TypeExpr::Tuple(types), Json::Array(items)) => {
+            if items.len() != types.len() {
+                return Err(format!(
+                    "expected {} at {}, found an array of {} items",
+                    ty,
+                    path,
+                    items.len()
+                ));
+            }
+            let mut values = Vec::new();
+            for (i, (json, ty)) in items.iter().zip(types).enumerate() {
+                values.push(decode(json, ty, structs, &format!("{}[{}]", path, i))?);
+            }
+            Value::Tuple(values)
+        }
+        (TypeExpr::Named(name, args), json) => match (name.as_str(), json) {
+            ("str", Json::String(s)) => Value::Str(s.clone()),
+            ("bool", Json::Bool(b)) => Value::Bool(*b),
+            ("float", Json::Number(text)) => Value::Float(text.parse().map_err(|_| format!("invalid number at {}", path))?),
+            ("bigint", Json::Number(text)) => match BigInt::parse(text) {
+                Some(n) => Value::Big(n),
+                None => return Err(format!("expected an integer at {}, found {}", path, text)),
+            },
+            (name, Json::Number(text)) if IntKind::from_name(name).is_some() => {
+                let kind = IntKind::from_name(name).unwrap();
+                match text.parse::<i128>() {
+                    Ok(n) if kind.fits(n) => Value::integer(kind, n),
+                    Ok(n) => return Err(format!("{} at {} does not fit in {}", n, path, kind)),
+                    Err(_) => return Err(format!("expected an integer at {}, found {}", path, text)),
+                }
+            }
+            ("map", Json::Object(fields)) if args.len() == 2 => {
+                let mut entries = Vec::new();
+                for (key, json) in fields {
+                    let field_path = field_path(path, key);
+                    let key = decode(&Json::String(key.clone()), &args[0], structs, &field_path)
+                        .or_else(|_| decode(&Json::Number(key.clone()), &args[0], structs, &field_path))
+                        .map_err(|_| format!("expected a key of type {} at {}", args[0], field_path))?;
+                    let value = decode(json, &args[1], structs, &field_path)?;
+                    match entries.iter_mut().find(|(k, _)| *k == key) {
+                        Some(entry) => entry.1 = value,
+                        None => entries.push((key, value)),
+                    }
+                }
+                Value::map(entries)
+            }
+            // `{"ok": value}` or `{"err": error}`, the way `stringify` writes a Result
+            ("Result", Json::Object(fields)) if args.len() == 2 && fields.len() == 1 => match &fields[0] {
+                (key, json) if key == "ok" => Value::Ok(Box::new(decode(json, &args[0], structs, &field_path(path, key))?)),
+                (key, json) if key == "err" => Value::Err(Box::new(decode(json, &args[1], structs, &field_path(path, key))?)),
+                _ => return mismatch(),
+            },
+            (name, Json::Object(fields)) if structs.contains_key(name) => {
+                let decl = &structs[name];
+                // the struct's type parameters stand for the type arguments it was given
+                let bindings: HashMap<&str, &TypeExpr> =
+                    decl.type_params.iter().map(|p| p.name.as_str()).zip(args).collect();
+                if let Some((key, _)) = fields.iter().find(|(key, _)| !decl.fields.iter().any(|(f, _)| f == key)) {
+                    return Err(format!("unknown field `{}` of {} at {}", key, ty, path));
+                }
+                let mut values = Vec::new();
+                for (field, field_ty) in &decl.fields {
+                    let field_ty = substitute(field_ty, &bindings);
+                    let field_path = field_path(path, field);
+                    let value = match fields.iter().rev().find(|(key, _)| key == field) {
+                        Some((_, json)) => decode(json, &field_ty, structs, &field_path)?,
+                        None if matches!(field_ty, TypeExpr::Optional(_)) => Value::None,
+                        None => return Err(format!("missing field `{}` of {} at {}", field, ty, path)),
+                    };
+                    values.push((field.clone(), value));
+                }
+                Value::Struct(name.to_string(), Rc::new(RefCell::new(values)))
+            }
+            _ => return mismatch(),
+        },
+        _ => return mismatch(),
+    };
+    Ok(value)
+}
+
+// `$.name`, or `$["two words"]` for a key that isn't a plain name
+fn field_path(path: &str, key: &str) -> String {
+    let plain = key.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{:?}]", path, key)
+    }
+}
+
+fn substitute(ty: &TypeExpr, bindings: &HashMap<&str, &TypeExpr>) -> TypeExpr {
+    match ty {
+        TypeExpr::Named(name, args) if args.is_empty() && bindings.contains_key(name.as_str()) => {
+            bindings[name.as_str()].clone()
+        }
+        TypeExpr::Named(name, args) => {
+            TypeExpr::Named(name.clone(), args.iter().map(|a| substitute(a, bindings)).collect())
+        }
+        TypeExpr::List(inner) => TypeExpr::List(Box::new(substitute(inner, bindings))),
+        TypeExpr::Optional(inner) => TypeExpr::Optional(Box::new(substitute(inner, bindings))),
+        TypeExpr::Tuple(items) => TypeExpr::Tuple(items.iter().map(|i| substitute(i, bindings)).collect()),
+    }
+}
+
+/// Writes a value as JSON. Lists, tuples and ranges become arrays, maps and structs objects and
+/// none null. `pretty` puts each item on its own line, indented by two spaces.
+pub fn stringify(value: &Value, pretty: bool) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, pretty, 0);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value, pretty: bool, depth: usize) {
+    match value {
+        Value::None | Value::Void => out.push_str("null"),
+        Value::Bool(b) => out.push_str(&b.to_string()),
+        Value::Int(_) | Value::Sized(..) | Value::Big(_) => out.push_str(&value.to_string()),
+        // JSON has no way to write these
+        Value::Float(x) if !x.is_finite() => out.push_str("null"),
+        Value::Float(x) => out.push_str(&format!("{:?}", x)),
+        Value::Str(s) => write_string(out, s),
+        Value::List(items) => write_items(out, items.borrow().iter(), pretty, depth),
+        Value::Tuple(items) => write_items(out, items.iter(), pretty, depth),
+        Value::Range { .. } => {
+            let items = range_items(value);
+            write_items(out, items.iter(), pretty, depth)
+        }
+        Value::Map(entries) => {
+            let entries: Vec<(String, Value)> = entries
+                .borrow()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect();
+            write_object(out, &entries, pretty, depth);
+        }
+        Value::Struct(_, fields) => write_object(out, &fields.borrow(), pretty, depth),
+        Value::Ok(inner) => write_object(out, &[("ok".to_string(), (**inner).clone())], pretty, depth),
+        Value::Err(inner) => write_object(out, &[("err".to_string(), (**inner).clone())], pretty, depth),
+    }
+}
+
+fn range_items(range: &Value) -> Vec<Value> {
+    let Value::Range { start, end, inclusive, step } = *range else {
+        return Vec::new();
+    };
+    let (mut i, end, step) = (start as i64, end as i64, step as i64);
+    let mut items = Vec::new();
+    while (step > 0 && (i < end || (inclusive && i == end))) || (step < 0 && (i > end || (inclusive && i == end))) {
+        items.push(Value::Int(i as i32));
+        i += step;
+    }
+    items
+}
+
+fn newline(out: &mut String, pretty: bool, depth: usize) {
+    if pretty {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    }
+}
+
+fn write_items<'a>(out: &mut String, items: impl Iterator<Item = &'a Value>, pretty: bool, depth: usize) {
+    out.push('[');
+    let mut empty = true;
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, pretty, depth + 1);
+        write_value(out, item, pretty, depth + 1);
+        empty = false;
+    }
+    if !empty {
+        newline(out, pretty, depth);
+    }
+    out.push(']');
+}
+
+fn write_object(out: &mut String, fields: &[(String, Value)], pretty: bool, depth: usize) {
+    out.push('{');
+    for (i, (key, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, pretty, depth + 1);
+        write_string(out, key);
+        out.push_str(if pretty { ": " } else { ":" });
+        write_value(out, value, pretty, depth + 1);
+    }
+    if !fields.is_empty() {
+        newline(out, pretty, depth);
+    }
+    out.push('}');
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_stringify_round_trip() {
+        let text = r#" {"name": "café 😀", "n": [1, -2.5e3, true, null], "empty": {}} "#;
+        let json = parse(text).unwrap();
+        assert_eq!(
+            json,
+            Json::Object(vec![
+                ("name".to_string(), Json::String("café 😀".to_string())),
+                (
+                    "n".to_string(),
+                    Json::Array(vec![
+                        Json::Number("1".to_string()),
+                        Json::Number("-2.5e3".to_string()),
+                        Json::Bool(true),
+                        Json::Null
+                    ])
+                ),
+                ("empty".to_string(), Json::Object(Vec::new())),
+            ])
+        );
+        let ty = TypeExpr::Named("map".to_string(), vec![TypeExpr::named("str"), TypeExpr::named("int")]);
+        let value = decode(&parse(r#"{"a": 1, "b\"": 2}"#).unwrap(), &ty, &HashMap::new(), "$").unwrap();
+        assert_eq!(stringify(&value, false), r#"{"a":1,"b\"":2}"#);
+        assert_eq!(stringify(&value, true), "{\n  \"a\": 1,\n  \"b\\\"\": 2\n}");
+        assert_eq!(parse("[1,]").unwrap_err(), "invalid JSON at line 1, column 4: expected a value");
+        assert_eq!(parse("{\n\"a\" 1}").unwrap_err(), "invalid JSON at line 2, column 5: expected `:`");
+        assert!(parse("01x").is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error_not_a_stack_overflow() {
+        let deep = "[".repeat(100_000) + &"]".repeat(100_000);
+        assert_eq!(
+            parse(&deep).unwrap_err(),
+            "invalid JSON at line 1, column 513: arrays and objects are nested more than 512 deep"
+        );
+        let ok = "[".repeat(512) + &"]".repeat(512);
+        assert!(parse(&ok).is_ok());
+    }
+}
@@ -37,19 +37,75 @@ use std::iter::Peekable;
 use std::str::Chars;
 
 use crate::Token;
+use crate::bigint::BigInt;
+use crate::ints::IntKind;
+use crate::token::TemplatePart;
 #[derive(Debug)]
 pub struct Lexer<'a> {
     input: Peekable<Chars<'a>>,
+    line: usize,
+    // the number being read follows a `.`, so `t.0.1` is two tuple indexes and not `t.` `0.1`
+    after_dot: bool,
+}
+
+/// Lexes a whole source file, pairing every token with the line it starts on.
+/// The returned vector always ends with `Token::Eof`.
+pub fn tokenize(source: &str) -> Vec<(Token, usize)> {
+    let mut lexer = Lexer::new(source);
+    let mut tokens = Vec::new();
+    loop {
+        match lexer.next() {
+            Some(Token::Eof) | None => break,
+            Some(token) => tokens.push((token, lexer.line())),
+        }
+    }
+    tokens.push((Token::Eof, lexer.line()));
+    tokens
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Lexer { 
             input: input.chars().peekable(),
+            line: 1,
+            after_dot: false,
         }
     }
 
-    fn read_identifier(&mut self, first_char: char) -> String {
+    /// The line the most recently returned token started on.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // looks one character past `peek`
+    fn peek_second(&self) -> Option<char> {
+        let mut ahead = self.input.clone();
+        ahead.next();
+        ahead.next()
+    }
+
+    fn skip_line_comment(&mut self) {
+        while let Some(&c) = self.input.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.input.next();
+        }
+    }
+
+    fn skip_block_comment(&mut self) {
+        // the opening `/*` has already been consumed
+        while let Some(c) = self.input.next() {
+            if c == '\n' {
+                self.line += 1;
+            } else if c == '*' && self.input.peek() == Some(&'/') {
+                self.input.next();
+                break;
+            }
+        }
+    }
+
+    fn read_identifier(&mut self, _first_char: char) -> String {
         // println!("read_identifier");
         let mut ident = String::new();
        
@@ -66,13 +122,18 @@ impl<'a> Lexer<'a> {
         ident
     }
 
-    fn read_number(&mut self, first_char: char) -> Token {
+    fn read_number(&mut self, _first_char: char) -> Token {
         let mut num_str = String::new();
        // println!("read_number");
 
 
         while let Some(&c) = self.input.peek() {
-            if c.is_numeric() || c == '.' {
+            // a dot only belongs to the number when a digit follows it
+            let fraction = c == '.'
+                && !self.after_dot
+                && !num_str.contains('.')
+                && self.peek_second().is_some_and(|d| d.is_ascii_digit());
+            if c.is_ascii_digit() || fraction {
                 num_str.push(c);
                 self.input.next();
             } else {
@@ -80,30 +141,121 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        self.after_dot = false;
         if num_str.contains('.') {
-            println!("float");
-            Token::Empty
-            
-        } else {
-            Token::Integer(num_str.parse().unwrap())
+            return Token::CFloat(num_str.parse().unwrap());
+        }
+        // `255u8`, `-5i64`; a suffix of `i32` is the same as none
+        let mut ahead = self.input.clone();
+        let suffix: String =
+            std::iter::from_fn(|| ahead.next_if(|c| c.is_alphanumeric() || *c == '_')).collect();
+        // `123n` is a bigint, which has no limit on its digits
+        if suffix == "n" {
+            self.input.next();
+            return Token::BigInteger(BigInt::parse(&num_str).unwrap_or_else(BigInt::zero));
+        }
+        let kind = IntKind::from_name(&suffix).filter(|_| suffix != "int");
+        if kind.is_some() {
+            for _ in 0..suffix.len() {
+                self.input.next();
+            }
+        }
+        // whether the number fits in its type is up to the checker
+        match (kind, num_str.parse::<i128>()) {
+            (None | Some(IntKind::I32), Ok(int)) => Token::Integer(int),
+            (Some(kind), Ok(int)) => Token::SizedInteger(int, kind),
+            // too many digits for any other type, so it can only be a bigint
+            (None, Err(_)) => Token::BigInteger(BigInt::parse(&num_str).unwrap_or_else(BigInt::zero)),
+            _ => Token::Unknown(num_str.chars().next().unwrap_or('0')),
         }
     }   
 
-   fn read_string(&mut self) -> String {
+   // a string with braces in it becomes a Template. `{{` and `}}` are kept as they are for the
+   // parser, since they mean something different in a `format` template
+   fn read_string(&mut self) -> Token {
     let mut str_literal = String::new();
+    let mut parts = Vec::new();
+    let mut braces = false;
+    let start = self.line;
     self.input.next();
 
-    while let Some(c) = self.input.next() {
-        if c != '"' {
-            str_literal.push(c);
-        } else {
-            break;
+    loop {
+        let Some(c) = self.input.next() else {
+            return Token::Unterminated("string", start);
+        };
+        match c {
+            '"' => break,
+            '{' | '}' if self.input.peek() == Some(&c) => {
+                self.input.next();
+                str_literal.push(c);
+                str_literal.push(c);
+                braces = true;
+            }
+            '{' => {
+                if !str_literal.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut str_literal)));
+                }
+                match self.read_interpolation() {
+                    Some(code) => parts.push(TemplatePart::Code(code)),
+                    None => return Token::Unterminated("`{` in a string", start),
+                }
+            }
+            '\\' => match self.input.next() {
+                Some('n') => str_literal.push('\n'),
+                Some('t') => str_literal.push('\t'),
+                Some('r') => str_literal.push('\r'),
+                Some('0') => str_literal.push('\0'),
+                Some(other) => str_literal.push(other),
+                None => return Token::Unterminated("string", start),
+            },
+            '\n' => {
+                self.line += 1;
+                str_literal.push(c);
+            }
+            _ => str_literal.push(c),
         }
     }
 
-    str_literal
+    if parts.is_empty() && !braces {
+        return Token::String(str_literal);
+    }
+    if !str_literal.is_empty() {
+        parts.push(TemplatePart::Text(str_literal));
+    }
+    Token::Template(parts)
 }
 
+    // the source between the braces of an interpolation, which may have braces and strings of
+    // its own. none if the file ends before the closing brace
+    fn read_interpolation(&mut self) -> Option<String> {
+        let mut code = String::new();
+        let mut depth = 0;
+        loop {
+            let c = self.input.next()?;
+            match c {
+                '}' if depth == 0 => break,
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' => {
+                    code.push(c);
+                    while let Some(c) = self.input.next() {
+                        code.push(c);
+                        if c == '\\' {
+                            code.extend(self.input.next());
+                        } else if c == '"' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                '\n' => self.line += 1,
+                _ => {}
+            }
+            code.push(c);
+        }
+        Some(code)
+    }
+
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -117,30 +269,75 @@ impl<'a> Iterator for Lexer<'a> {
 
 
             match c {
-                ' ' | '\n' | '\t' => {
+                '\n' => {
+                    self.line += 1;
+                    self.input.next();
+                }
+                ' ' | '\t' | '\r' => {
                     self.input.next();
                 }
                 '0'..='9' => {
                     return Some(self.read_number(c));
                 }
                 '"' => {
-                    return Some(Token::String(self.read_string()));
+                    return Some(self.read_string());
                 }
                 '+' => {
                     self.input.next();
-                    return Some(Token::Plus);
+                    match self.input.peek() {
+                        Some(&'%') => {
+                            self.input.next();
+                            return Some(Token::WrappingPlus);
+                        }
+                        Some(&'|') => {
+                            self.input.next();
+                            return Some(Token::SaturatingPlus);
+                        }
+                        _ => return Some(Token::Plus),
+                    }
                 }
                 '-' => {
                     self.input.next();
-                    return Some(Token::Minus);
+                    match self.input.peek() {
+                        Some(&'%') => {
+                            self.input.next();
+                            return Some(Token::WrappingMinus);
+                        }
+                        Some(&'|') => {
+                            self.input.next();
+                            return Some(Token::SaturatingMinus);
+                        }
+                        _ => return Some(Token::Minus),
+                    }
                 }
                 '*' => {
                     self.input.next();
-                    return Some(Token::Multiply);
+                    match self.input.peek() {
+                        Some(&'%') => {
+                            self.input.next();
+                            return Some(Token::WrappingMultiply);
+                        }
+                        Some(&'|') => {
+                            self.input.next();
+                            return Some(Token::SaturatingMultiply);
+                        }
+                        Some(&'*') => {
+                            self.input.next();
+                            return Some(Token::Power);
+                        }
+                        _ => return Some(Token::Multiply),
+                    }
                 }
                 '/' => {
                     self.input.next();
-                    return Some(Token::Divide);
+                    match self.input.peek() {
+                        Some(&'/') => self.skip_line_comment(),
+                        Some(&'*') => {
+                            self.input.next();
+                            self.skip_block_comment();
+                        }
+                        _ => return Some(Token::Divide),
+                    }
                 }
                 '%' => {
                     self.input.next();
@@ -152,7 +349,7 @@ impl<'a> Iterator for Lexer<'a> {
                         self.input.next();
                         return Some(Token::Equals);
                     } else {
-                        return Some(Token::Equals);
+                        return Some(Token::Assign);
                     }
                 }
                 '!' => {
@@ -166,20 +363,31 @@ impl<'a> Iterator for Lexer<'a> {
                 }
                 '<' => {
                     self.input.next();
-                    if let Some(&'=') = self.input.peek() {
-                        self.input.next();
-                        return Some(Token::LessThanOrEqual);
-                    } else {
-                        return Some(Token::LessThan);
+                    match self.input.peek() {
+                        Some(&'=') => {
+                            self.input.next();
+                            return Some(Token::LessThanOrEqual);
+                        }
+                        Some(&'<') => {
+                            self.input.next();
+                            return Some(Token::ShiftLeft);
+                        }
+                        _ => return Some(Token::LessThan),
                     }
                 }
                 '>' => {
                     self.input.next();
-                    if let Some(&'=') = self.input.peek() {
-                        self.input.next();
-                        return Some(Token::GreaterThanOrEqual);
-                    } else {
-                        return Some(Token::GreaterThan);
+                    // `>>` can also close two lists of type arguments, the parser splits it up there
+                    match self.input.peek() {
+                        Some(&'=') => {
+                            self.input.next();
+                            return Some(Token::GreaterThanOrEqual);
+                        }
+                        Some(&'>') => {
+                            self.input.next();
+                            return Some(Token::ShiftRight);
+                        }
+                        _ => return Some(Token::GreaterThan),
                     }
                 }
                 '&' => {
@@ -188,7 +396,7 @@ impl<'a> Iterator for Lexer<'a> {
                         self.input.next();
                         return Some(Token::And);
                     } else {
-                        return None;
+                        return Some(Token::Ampersand);
                     }
                 }
                 '|' => {
@@ -197,7 +405,24 @@ impl<'a> Iterator for Lexer<'a> {
                         self.input.next();
                         return Some(Token::Or);
                     } else {
-                        return None;
+                        return Some(Token::Pipe);
+                    }
+                }
+                '^' => {
+                    self.input.next();
+                    return Some(Token::Caret);
+                }
+                '~' => {
+                    self.input.next();
+                    return Some(Token::Tilde);
+                }
+                '?' => {
+                    self.input.next();
+                    if let Some(&'?') = self.input.peek() {
+                        self.input.next();
+                        return Some(Token::Coalesce);
+                    } else {
+                        return Some(Token::Question);
                     }
                 }
                 '(' => {
@@ -216,6 +441,14 @@ impl<'a> Iterator for Lexer<'a> {
                     self.input.next();
                     return Some(Token::RightBrace);
                 }
+                '[' => {
+                    self.input.next();
+                    return Some(Token::LeftBracket);
+                }
+                ']' => {
+                    self.input.next();
+                    return Some(Token::RightBracket);
+                }
                 ':' => {
                     self.input.next();
                     return Some(Token::Colon);
@@ -228,8 +461,26 @@ impl<'a> Iterator for Lexer<'a> {
                     self.input.next();
                     return Some(Token::Comma);
                 }
+                '.' => {
+                    self.input.next();
+                    // `..` and `..=` make ranges
+                    if self.input.peek() != Some(&'.') {
+                        self.after_dot = self.input.peek().is_some_and(|c| c.is_ascii_digit());
+                        return Some(Token::Dot);
+                    }
+                    self.input.next();
+                    if let Some(&'=') = self.input.peek() {
+                        self.input.next();
+                        return Some(Token::DotDotEq);
+                    } else if let Some(&'.') = self.input.peek() {
+                        self.input.next();
+                        return Some(Token::Ellipsis);
+                    } else {
+                        return Some(Token::DotDot);
+                    }
+                }
                 _ => {
-                    if c.is_alphabetic() {
+                    if c.is_alphabetic() || c == '_' {
                         let ident = self.read_identifier(c);
                         match ident.as_str() {
                             "int" => return Some(Token::Int),
@@ -241,26 +492,27 @@ impl<'a> Iterator for Lexer<'a> {
                             "else" => return Some(Token::Else),
                             "while" => return Some(Token::While),
                             "for" => return Some(Token::For),
-                            "print" => {
-                                self.input.next();
-                                while let Some(&c) = self.input.peek() {
-                                    if c != ';' {
-                                        self.input.next();
-                                    } else {
-                                     return Some(Token::Print);
-                                    }
-                                    
-                                  
-                                    println!("{}", self.input.peek().unwrap());
-                                }
-                                //self.input.next();
-                                // check if it ends with a semicolon
-                            }
+                            "in" => return Some(Token::In),
+                            "print" => return Some(Token::Print),
+                            "return" => return Some(Token::Return),
+                            "struct" => return Some(Token::Struct),
+                            "trait" => return Some(Token::Trait),
+                            "impl" => return Some(Token::Impl),
+                            "try" => return Some(Token::Try),
+                            "catch" => return Some(Token::Catch),
+                            "import" => return Some(Token::Import),
+                            "pub" => return Some(Token::Pub),
+                            "mut" => return Some(Token::Mut),
+                            "const" => return Some(Token::Const),
+                            "as" => return Some(Token::As),
+                            "true" => return Some(Token::True),
+                            "false" => return Some(Token::False),
+                            "none" => return Some(Token::NoneLiteral),
                             _ => return Some(Token::Identifier(ident)),
                         }
                     } else {
                         self.input.next();
-                        return None;
+                        return Some(Token::Unknown(c));
                     }
                 }
             }
@@ -270,5 +522,31 @@ impl<'a> Iterator for Lexer<'a> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-
+    #[test]
+    fn test_tokenize_tracks_lines_and_skips_comments() {
+        let tokens = tokenize("x:float = 5.5; // five and a half\ny = x == 1");
+        let kinds: Vec<Token> = tokens.iter().map(|(t, _)| t.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Token::Identifier("x".to_string()),
+                Token::Colon,
+                Token::Float,
+                Token::Assign,
+                Token::CFloat(5.5),
+                Token::Semicolon,
+                Token::Identifier("y".to_string()),
+                Token::Assign,
+                Token::Identifier("x".to_string()),
+                Token::Equals,
+                Token::Integer(1),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(tokens[6].1, 2);
+    }
+}
@@ -1,55 +1,155 @@
 // cube, a programming language that is inspired by Rust and Python
 
+mod ast;
+mod bigint;
+mod checker;
+mod diagnostic;
+mod format;
+mod ints;
+mod json;
 mod lexer;
+mod modules;
 mod parser;
 mod interpriter;
+mod stdlib;
+mod value;
 pub mod token;
 
 use std::io::{self, Write};
-use std::fs::*;
+use std::path::Path;
 use interpriter::interpret;
-use lexer::{Lexer};
 use crate::token::Token;
-fn print_s(msg: &str, i: i32) {
-    println!("{}{}", msg, i);
+
+// load the program and its imports, type check it, then run it if it's well formed. `context` has
+// the command line arguments after the file name, for `process.args()`. a program that doesn't
+// load, check or run to the end exits with 1, the same as `cube check`, and `process.exit(n)`
+// exits with `n`
+fn run(loaded: Result<modules::Program, diagnostic::Diagnostic>, context: stdlib::Context) {
+    let program = match loaded {
+        Ok(program) => program,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic);
+            std::process::exit(1);
+        }
+    };
+    if let Err(diagnostics) = checker::check(&program.statements) {
+        for diagnostic in diagnostics {
+            eprintln!("{}", program.sources.relocate(diagnostic));
+        }
+        std::process::exit(1);
+    }
+    match interpret(&program.statements, context) {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        Err(mut error) => {
+            (error.file, error.line) = program.sources.locate(error.line);
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
 }
-fn print_s_token(msg: &str, token: Token) {
-    println!("{}{:?}", msg, token);
+
+// `[--seed n] file.cu args...`, the file to run and what it gets. with --seed the `random` module
+// gives the same numbers every run, so a test of a script that uses it can be repeated
+fn run_file(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("usage: cube [run] [--seed <n>] <file> [args...]");
+        std::process::exit(2);
+    };
+    let (rng, args) = match args {
+        [flag, seed, rest @ ..] if flag == "--seed" => match seed.parse() {
+            Ok(seed) => (stdlib::Rng::new(seed), rest),
+            Err(_) => {
+                eprintln!("--seed needs a whole number from 0 up, not `{}`", seed);
+                std::process::exit(2);
+            }
+        },
+        [flag, ..] if flag == "--seed" => usage(),
+        args => (stdlib::Rng::default(), args),
+    };
+    let Some((path, args)) = args.split_first() else { usage() };
+    let context = stdlib::Context { args: args.to_vec(), rng };
+    run(modules::load_file(Path::new(path)), context);
 }
+
+// `cube check [--show-types] file.cu` type checks a program without running it. with
+// --show-types it also prints the type of every variable, inferred or not.
+fn check(args: &[String]) {
+    let show_types = args.iter().any(|arg| arg == "--show-types");
+    let path = match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: cube check [--show-types] <file>");
+            std::process::exit(2);
+        }
+    };
+    let program = match modules::load_file(Path::new(path)) {
+        Ok(program) => program,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic);
+            std::process::exit(1);
+        }
+    };
+    match checker::variable_types(&program.statements) {
+        Ok(variables) => {
+            if show_types {
+                for (line, name, ty) in variables {
+                    match program.sources.locate(line) {
+                        (Some(file), line) => println!("{}:{}: {}: {}", file, line, name, ty),
+                        (None, line) => println!("{}: {}: {}", line, name, ty),
+                    }
+                }
+            }
+        }
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", program.sources.relocate(diagnostic));
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
+    // every cube call is a few nested rust calls per expression, so the interpreter gets a stack
+    // big enough for deep recursion, and it stops a program before using all of it
+    let cube = std::thread::Builder::new().stack_size(interpriter::STACK_SIZE).spawn(cube).unwrap();
+    if cube.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn cube() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("check") => {
+            check(&args[2..]);
+            return;
+        }
+        // `cube run file.cu` or just `cube file.cu` leaves standard input to the program, so it
+        // can be used in a pipeline
+        Some("run") if args.len() > 2 => {
+            run_file(&args[2..]);
+            return;
+        }
+        Some(first) if first != "run" => {
+            run_file(&args[1..]);
+            return;
+        }
+        _ => {}
+    }
     let mut input = String::new();
-    let mut file = String::new();
     print!("cube> ");
     io::stdout().flush().unwrap();
     io::stdin().read_line(&mut input).unwrap();
-    // read the file contents
     if input.trim() == "run" {
         // read the next argument as the file name in the directory relative to the current directory
         let mut file_name = String::new();
         print!("file> ");
         io::stdout().flush().unwrap();
         io::stdin().read_line(&mut file_name).unwrap();
-        file = read_to_string(file_name.trim()).unwrap();
+        run(modules::load_file(Path::new(file_name.trim())), stdlib::Context::default());
     } else {
-        file = input;
+        run(modules::load_source(&input), stdlib::Context::default());
     }
-    // create a lexer
-    let lexer = Lexer::new(&file);
-    let mut tokens: Vec<Token> = Vec::new(); // create a vector to store the tokens
-    println!("these are our tokens {:?}", lexer);
-    for token in lexer { 
-        if &token == &Token::Eof {
-            println!("EOF");
-            break;
-        }
-            //print("cat"); print("catss");
-       
-            tokens.push(token);
-    
-    }
-    //println!("these are our tokens{:?}", tokens);
-    
-    interpret(tokens);
-
-   
-}
\ No newline at end of file
+}
@@ -0,0 +1,830 @@
+// modules.rs
+// loads a program and every module it imports. each file is read and parsed once no matter how
+// many modules import it. the top level names of an imported module are then prefixed with the
+// module's name (`add` in util.cu becomes `util.add`) so the checker and the interpreter can
+// treat all the modules as one program without their names clashing.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::parser::Parser;
+use crate::stdlib;
+
+type LoadResult<T> = Result<T, Diagnostic>;
+
+/// A program put together from the main file and everything it imports, dependencies first.
+#[derive(Debug)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    pub sources: SourceMap,
+}
+
+/// Maps the line numbers of the combined program back to the file they came from. Every file
+/// is numbered from where the previous one ended.
+#[derive(Debug)]
+pub struct SourceMap {
+    files: Vec<(Option<String>, usize)>,
+}
+
+impl SourceMap {
+    /// The file a line of the combined program is in and the line within that file. The file
+    /// is only named when the program is spread over more than one.
+    pub fn locate(&self, line: usize) -> (Option<String>, usize) {
+        let (name, first) = self
+            .files
+            .iter()
+            .rev()
+            .find(|(_, first)| *first <= line)
+            .cloned()
+            .unwrap_or((None, 0));
+        let name = if self.files.len() > 1 { name } else { None };
+        (name, line - first)
+    }
+
+    pub fn relocate(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        let (file, line) = self.locate(diagnostic.line);
+        diagnostic.file = file;
+        diagnostic.line = line;
+        for note in &mut diagnostic.notes {
+            (note.file, note.line) = self.locate(note.line);
+        }
+        diagnostic
+    }
+}
+
+struct Module {
+    // what importers call the module, the file name without `.cu`
+    name: String,
+    // what its names are prefixed with, `None` for the main file whose names are left as they are
+    prefix: Option<String>,
+    statements: Vec<Stmt>,
+    // the module each `import` brought in, under the name it is used by
+    imports: HashMap<String, usize>,
+    // functions, structs, traits and constants declared at the top level, and whether they're `pub`
+    items: HashMap<String, bool>,
+    // variables declared at the top level, always private
+    globals: HashSet<String>,
+}
+
+impl Module {
+    fn mangle(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.to_string(),
+        }
+    }
+}
+
+struct Loader {
+    search_path: Vec<PathBuf>,
+    modules: Vec<Module>,
+    loaded: HashMap<PathBuf, usize>,
+    // the files currently being loaded, to catch imports that lead back to one of them
+    loading: Vec<(PathBuf, String)>,
+    prefixes: HashSet<String>,
+    order: Vec<usize>,
+    sources: SourceMap,
+    next_line: usize,
+}
+
+/// Loads the program in `path` and everything it imports.
+pub fn load_file(path: &Path) -> Result<Program, Diagnostic> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| Diagnostic::new(format!("cannot read `{}`: {}", path.display(), e), 0))?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut loader = Loader::new();
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    loader.loading.push((key, path.display().to_string()));
+    loader.finish(&source, Some(path.display().to_string()), dir)
+}
+
+/// Loads a program typed in at the prompt. Its imports are looked up from the current directory.
+pub fn load_source(source: &str) -> Result<Program, Diagnostic> {
+    Loader::new().finish(source, None, PathBuf::from("."))
+}
+
+impl Loader {
+    fn new() -> Self {
+        // CUBE_PATH lists more directories to look for modules in, like PATH
+        let search_path = std::env::var_os("CUBE_PATH")
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        // a program's own module called `math` is renamed rather than the standard one
+        let prefixes = stdlib::MODULES.iter().map(|name| name.to_string()).collect();
+        Loader {
+            search_path,
+            modules: Vec::new(),
+            loaded: HashMap::new(),
+            loading: Vec::new(),
+            prefixes,
+            order: Vec::new(),
+            sources: SourceMap { files: Vec::new() },
+            next_line: 0,
+        }
+    }
+
+    fn finish(
+        mut self,
+        source: &str,
+        file: Option<String>,
+        dir: PathBuf,
+    ) -> Result<Program, Diagnostic> {
+        let result = self
+            .add(source, file, dir, "main", None, false)
+            .and_then(|_| self.resolve());
+        match result {
+            Ok(statements) => Ok(Program {
+                statements,
+                sources: self.sources,
+            }),
+            Err(diagnostic) => Err(self.sources.relocate(diagnostic)),
+        }
+    }
+
+    // parses a file, loads the modules it imports and records it after them
+    fn add(
+        &mut self,
+        source: &str,
+        file: Option<String>,
+        dir: PathBuf,
+        name: &str,
+        prefix: Option<String>,
+        standard: bool,
+    ) -> LoadResult<usize> {
+        let offset = self.next_line;
+        self.sources.files.push((file, offset));
+        self.next_line += source.lines().count() + 1;
+        let tokens = crate::lexer::tokenize(source)
+            .into_iter()
+            .map(|(token, line)| (token, line + offset))
+            .collect();
+        let mut parser = Parser::new(tokens);
+        if standard {
+            parser = parser.with_natives();
+        }
+        let statements = parser.parse_program()?;
+
+        let mut items = HashMap::new();
+        let mut globals = HashSet::new();
+        for stmt in &statements {
+            match &stmt.kind {
+                StmtKind::Function(decl) => {
+                    items.insert(decl.name.clone(), decl.public);
+                }
+                StmtKind::Struct(decl) => {
+                    items.insert(decl.name.clone(), decl.public);
+                }
+                StmtKind::Trait(decl) => {
+                    items.insert(decl.name.clone(), decl.public);
+                }
+                StmtKind::Const { name, public, .. } => {
+                    items.insert(name.clone(), *public);
+                }
+                StmtKind::Let { name, .. } => {
+                    globals.insert(name.clone());
+                }
+                StmtKind::Destructure { names, .. } => globals.extend(names.iter().cloned()),
+                _ => {}
+            }
+        }
+        let index = self.modules.len();
+        self.modules.push(Module {
+            name: name.to_string(),
+            prefix,
+            statements: Vec::new(),
+            imports: HashMap::new(),
+            items,
+            globals,
+        });
+
+        for stmt in &statements {
+            if let StmtKind::Import(path) = &stmt.kind {
+                let imported = self.import(path, &dir, stmt.line)?;
+                let alias = self.modules[imported].name.clone();
+                match self.modules[index].imports.insert(alias.clone(), imported) {
+                    Some(previous) if previous != imported => {
+                        return Err(Diagnostic::new(
+                            format!("two imported modules are both called `{}`", alias),
+                            stmt.line,
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.modules[index].statements = statements;
+        self.order.push(index);
+        Ok(index)
+    }
+
+    fn import(&mut self, path: &str, dir: &Path, line: usize) -> LoadResult<usize> {
+        let candidates = std::iter::once(dir.to_path_buf()).chain(self.search_path.iter().cloned());
+        let file = match candidates.map(|d| d.join(path)).find(|f| f.is_file()) {
+            Some(file) => file,
+            None => match stdlib::source(path) {
+                Some(source) => return self.import_standard(path, source),
+                None => {
+                    return Err(Diagnostic::new(
+                        format!(
+                            "cannot find module `{}` next to the importing file or in CUBE_PATH",
+                            path
+                        ),
+                        line,
+                    ))
+                }
+            },
+        };
+        let key = file.canonicalize().unwrap_or_else(|_| file.clone());
+        let shown = file.display().to_string();
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == key) {
+            let mut cycle: Vec<&str> = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            cycle.push(&shown);
+            return Err(Diagnostic::new(
+                format!("import cycle: {}", cycle.join(" -> ")),
+                line,
+            ));
+        }
+        if let Some(&index) = self.loaded.get(&key) {
+            return Ok(index);
+        }
+        let source = std::fs::read_to_string(&file)
+            .map_err(|e| Diagnostic::new(format!("cannot read `{}`: {}", shown, e), line))?;
+        let stem = file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut prefix = stem.clone();
+        let mut n = 2;
+        while !self.prefixes.insert(prefix.clone()) {
+            prefix = format!("{}{}", stem, n);
+            n += 1;
+        }
+        let module_dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.loading.push((key.clone(), shown.clone()));
+        let index = self.add(&source, Some(shown), module_dir, &stem, Some(prefix), false)?;
+        self.loading.pop();
+        self.loaded.insert(key, index);
+        Ok(index)
+    }
+
+    // a module that comes with cube, like `math`. it is only used when there's no file of that
+    // name, and its prefix is always its name so the interpreter can find its native functions
+    fn import_standard(&mut self, path: &str, source: &str) -> LoadResult<usize> {
+        let key = PathBuf::from(format!("<std>/{}", path));
+        if let Some(&index) = self.loaded.get(&key) {
+            return Ok(index);
+        }
+        let name = path.trim_end_matches(".cu");
+        let file = Some(key.display().to_string());
+        let index = self.add(source, file, PathBuf::new(), name, Some(name.to_string()), true)?;
+        self.loaded.insert(key, index);
+        Ok(index)
+    }
+
+    // renames every module's names and joins them into one program, dependencies first
+    fn resolve(&mut self) -> LoadResult<Vec<Stmt>> {
+        let mut program = Vec::new();
+        for &index in &self.order {
+            let statements = std::mem::take(&mut self.modules[index].statements);
+            let mut resolver = Resolver {
+                modules: &self.modules,
+                module: &self.modules[index],
+                locals: Vec::new(),
+                type_params: Vec::new(),
+            };
+            for mut stmt in statements {
+                if matches!(stmt.kind, StmtKind::Import(_)) {
+                    continue;
+                }
+                resolver.stmt(&mut stmt, true)?;
+                program.push(stmt);
+            }
+        }
+        Ok(program)
+    }
+}
+
+// rewrites the names used in one module to the names they have in the combined program
+struct Resolver<'a> {
+    modules: &'a [Module],
+    module: &'a Module,
+    locals: Vec<HashSet<String>>,
+    type_params: Vec<String>,
+}
+
+impl Resolver<'_> {
+    // `util.add` used in this module, checked against what util exports
+    fn qualified(&self, alias: &str, item: &str, line: usize) -> LoadResult<String> {
+        let target = match self.module.imports.get(alias) {
+            Some(&index) => &self.modules[index],
+            None => {
+                return Err(Diagnostic::new(
+                    format!(
+                        "unknown module `{}`; did you forget `import {};`?",
+                        alias, alias
+                    ),
+                    line,
+                ))
+            }
+        };
+        match target.items.get(item) {
+            Some(true) => Ok(target.mangle(item)),
+            Some(false) => Err(Diagnostic::new(
+                format!(
+                    "`{}` is private to module `{}`; mark it `pub` to use it here",
+                    item, alias
+                ),
+                line,
+            )),
+            None if target.globals.contains(item) => Err(Diagnostic::new(
+                format!("variable `{}` is private to module `{}`", item, alias),
+                line,
+            )),
+            None => Err(Diagnostic::new(
+                format!("module `{}` has no `{}`", alias, item),
+                line,
+            )),
+        }
+    }
+
+    // the name of a function, struct or trait
+    fn item(&self, name: &str, line: usize) -> LoadResult<String> {
+        if let Some((alias, item)) = name.split_once('.') {
+            return self.qualified(alias, item, line);
+        }
+        if self.module.items.contains_key(name) && !self.type_params.iter().any(|p| p == name) {
+            return Ok(self.module.mangle(name));
+        }
+        Ok(name.to_string())
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.locals.iter().any(|scope| scope.contains(name))
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.locals.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn ty(&self, ty: &mut TypeExpr, line: usize) -> LoadResult<()> {
+        match ty {
+            TypeExpr::Named(name, args) => {
+                *name = self.item(name, line)?;
+                for arg in args {
+                    self.ty(arg, line)?;
+                }
+            }
+            TypeExpr::List(inner) | TypeExpr::Optional(inner) => self.ty(inner, line)?,
+            TypeExpr::Tuple(items) => items.iter_mut().try_for_each(|item| self.ty(item, line))?,
+        }
+        Ok(())
+    }
+
+    fn type_params(&mut self, params: &mut [TypeParam], line: usize) -> LoadResult<()> {
+        for param in params.iter_mut() {
+            for bound in &mut param.bounds {
+                *bound = self.item(bound, line)?;
+            }
+        }
+        self.type_params
+            .extend(params.iter().map(|p| p.name.clone()));
+        Ok(())
+    }
+
+    fn function(&mut self, decl: &mut FunctionDecl, rename: bool) -> LoadResult<()> {
+        let outer_params = self.type_params.len();
+        if rename {
+            decl.name = self.module.mangle(&decl.name);
+        }
+        self.type_params(&mut decl.type_params, decl.line)?;
+        let mut scope = HashSet::new();
+        for param in &mut decl.params {
+            self.ty(&mut param.ty, decl.line)?;
+            // a default value can use the parameters before it
+            if let Some(default) = &mut param.default {
+                self.locals.push(scope.clone());
+                let result = self.expr(default);
+                self.locals.pop();
+                result?;
+            }
+            scope.insert(param.name.clone());
+        }
+        self.ty(&mut decl.ret, decl.line)?;
+        self.locals.push(scope);
+        let result = self.block(&mut decl.body);
+        self.locals.pop();
+        self.type_params.truncate(outer_params);
+        result
+    }
+
+    fn block(&mut self, statements: &mut [Stmt]) -> LoadResult<()> {
+        self.locals.push(HashSet::new());
+        let result = statements
+            .iter_mut()
+            .try_for_each(|stmt| self.stmt(stmt, false));
+        self.locals.pop();
+        result
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt, top_level: bool) -> LoadResult<()> {
+        let line = stmt.line;
+        match &mut stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                if let Some(ty) = ty {
+                    self.ty(ty, line)?;
+                }
+                self.expr(value)?;
+                if top_level {
+                    *name = self.module.mangle(name);
+                } else {
+                    self.declare(name);
+                }
+            }
+            StmtKind::Destructure {
+                names, ty, value, ..
+            } => {
+                if let Some(ty) = ty {
+                    self.ty(ty, line)?;
+                }
+                self.expr(value)?;
+                for name in names {
+                    if top_level {
+                        *name = self.module.mangle(name);
+                    } else {
+                        self.declare(name);
+                    }
+                }
+            }
+            StmtKind::Const {
+                name, ty, value, ..
+            } => {
+                self.ty(ty, line)?;
+                self.expr(value)?;
+                if top_level {
+                    *name = self.module.mangle(name);
+                }
+            }
+            StmtKind::Assign { target, value } => {
+                self.expr(target)?;
+                self.expr(value)?;
+            }
+            StmtKind::Print(args) => args.iter_mut().try_for_each(|arg| self.expr(arg))?,
+            StmtKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond)?;
+                self.block(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch)?;
+                }
+            }
+            StmtKind::While { cond, body } => {
+                self.expr(cond)?;
+                self.block(body)?;
+            }
+            StmtKind::For {
+                names,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable)?;
+                self.locals.push(names.iter().cloned().collect());
+                let result = self.block(body);
+                self.locals.pop();
+                result?;
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value)?;
+                }
+            }
+            StmtKind::Try {
+                body,
+                error_name,
+                handler,
+            } => {
+                self.block(body)?;
+                self.locals.push(error_name.iter().cloned().collect());
+                let result = self.block(handler);
+                self.locals.pop();
+                result?;
+            }
+            StmtKind::Expr(expr) => self.expr(expr)?,
+            StmtKind::Block(statements) => self.block(statements)?,
+            // nested declarations are reported by the checker, leave their names alone
+            StmtKind::Function(decl) => self.function(decl, top_level)?,
+            StmtKind::Struct(decl) => {
+                if top_level {
+                    decl.name = self.module.mangle(&decl.name);
+                }
+                self.type_params(&mut decl.type_params, line)?;
+                for (_, ty) in &mut decl.fields {
+                    self.ty(ty, line)?;
+                }
+                self.type_params.clear();
+            }
+            StmtKind::Trait(decl) => {
+                if top_level {
+                    decl.name = self.module.mangle(&decl.name);
+                }
+                self.type_params(&mut decl.type_params, line)?;
+                for method in &mut decl.methods {
+                    for param in &mut method.params {
+                        self.ty(&mut param.ty, method.line)?;
+                    }
+                    self.ty(&mut method.ret, method.line)?;
+                }
+                self.type_params.clear();
+            }
+            StmtKind::Impl(decl) => {
+                decl.trait_name = self.item(&decl.trait_name, line)?;
+                self.type_params(&mut decl.type_params, line)?;
+                for arg in &mut decl.trait_args {
+                    self.ty(arg, line)?;
+                }
+                self.ty(&mut decl.target, line)?;
+                // methods keep their names, they're found through the trait
+                for method in &mut decl.methods {
+                    self.function(method, false)?;
+                }
+                self.type_params.clear();
+            }
+            StmtKind::Import(_) => {
+                return Err(Diagnostic::new(
+                    "`import` must be at the top level of a file",
+                    line,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &mut Expr) -> LoadResult<()> {
+        let line = expr.line;
+        match &mut expr.kind {
+            ExprKind::Integer(..)
+            | ExprKind::SizedInteger(..)
+            | ExprKind::BigInteger(_)
+            | ExprKind::Float(_)
+            | ExprKind::Str(_)
+            | ExprKind::Bool(_)
+            | ExprKind::None => {}
+            ExprKind::Identifier(name) => {
+                let global = self.module.globals.contains(name.as_str())
+                    || self.module.items.contains_key(name.as_str());
+                if !self.is_local(name) && global {
+                    *name = self.module.mangle(name);
+                }
+            }
+            ExprKind::Interpolated(parts) => {
+                for part in parts {
+                    if let StringPart::Value(value, _) = part {
+                        self.expr(value)?;
+                    }
+                }
+            }
+            ExprKind::List(items) | ExprKind::Tuple(items) => {
+                items.iter_mut().try_for_each(|item| self.expr(item))?
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key)?;
+                    self.expr(value)?;
+                }
+            }
+            ExprKind::Unary(_, operand) => self.expr(operand)?,
+            ExprKind::Cast(operand, ty) => {
+                self.expr(operand)?;
+                self.ty(ty, line)?;
+            }
+            ExprKind::Binary(_, left, right) => {
+                self.expr(left)?;
+                self.expr(right)?;
+            }
+            ExprKind::Call {
+                name,
+                type_args,
+                args,
+            } => {
+                *name = self.item(name, line)?;
+                for ty in type_args {
+                    self.ty(ty, line)?;
+                }
+                args.iter_mut().try_for_each(|arg| self.expr(arg))?;
+            }
+            ExprKind::Index(base, index) => {
+                self.expr(base)?;
+                self.expr(index)?;
+            }
+            ExprKind::Propagate(inner) => self.expr(inner)?,
+            // the name is the called function's parameter, only the value is resolved here
+            ExprKind::NamedArg(_, value) => self.expr(value)?,
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond)?;
+                self.block(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch)?;
+                }
+            }
+            ExprKind::Block(statements) => self.block(statements)?,
+            ExprKind::Range {
+                start, end, step, ..
+            } => {
+                self.expr(start)?;
+                self.expr(end)?;
+                if let Some(step) = step {
+                    self.expr(step)?;
+                }
+            }
+            // `util.x` where util is a module rather than a variable
+            ExprKind::Field(base, field) => match &base.kind {
+                ExprKind::Identifier(alias)
+                    if !self.is_local(alias)
+                        && !self.module.globals.contains(alias.as_str())
+                        && self.module.imports.contains_key(alias.as_str()) =>
+                {
+                    let name = self.qualified(alias, field, line)?;
+                    expr.kind = ExprKind::Identifier(name);
+                }
+                _ => self.expr(base)?,
+            },
+            // `util.add(1)` calls a function of a module rather than a method of a variable
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                type_args,
+                args,
+            } => {
+                if let ExprKind::Identifier(alias) = &receiver.kind {
+                    if !self.is_local(alias)
+                        && !self.module.globals.contains(alias.as_str())
+                        && self.module.imports.contains_key(alias.as_str())
+                    {
+                        let name = self.qualified(alias, method, line)?;
+                        let (mut type_args, mut args) = (std::mem::take(type_args), std::mem::take(args));
+                        for ty in &mut type_args {
+                            self.ty(ty, line)?;
+                        }
+                        args.iter_mut().try_for_each(|arg| self.expr(arg))?;
+                        expr.kind = ExprKind::Call { name, type_args, args };
+                        return Ok(());
+                    }
+                }
+                self.expr(receiver)?;
+                *method = self.item(method, line)?;
+                for ty in type_args {
+                    self.ty(ty, line)?;
+                }
+                args.iter_mut().try_for_each(|arg| self.expr(arg))?;
+            }
+            ExprKind::StructLiteral {
+                name,
+                type_args,
+                fields,
+            } => {
+                *name = self.item(name, line)?;
+                for ty in type_args {
+                    self.ty(ty, line)?;
+                }
+                for (_, value) in fields {
+                    self.expr(value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes the files into a fresh directory under the system temp dir and loads `main.cu`
+    fn load(name: &str, files: &[(&str, &str)]) -> Result<Program, Diagnostic> {
+        let dir =
+            std::env::temp_dir().join(format!("cube-modules-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, source) in files {
+            std::fs::write(dir.join(file), source).unwrap();
+        }
+        let program = load_file(&dir.join("main.cu"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        program
+    }
+
+    #[test]
+    fn test_shared_module_is_loaded_once_and_names_are_prefixed() {
+        let program = load(
+            "diamond",
+            &[
+                (
+                    "main.cu",
+                    "import left\nimport right\nx:int = left.f() + right.g()",
+                ),
+                ("left.cu", "import base\npub f:int() { return base.one(); }"),
+                (
+                    "right.cu",
+                    "import \"base.cu\"\npub g:int() { return base.one(); }",
+                ),
+                (
+                    "base.cu",
+                    "pub one:int() { return helper(); }\nhelper:int() { return 1; }",
+                ),
+            ],
+        )
+        .unwrap();
+        let names: Vec<String> = program
+            .statements
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StmtKind::Function(decl) => decl.name.clone(),
+                StmtKind::Let { name, .. } => name.clone(),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(names, ["base.one", "base.helper", "left.f", "right.g", "x"]);
+        let (file, line) = program.sources.locate(program.statements[1].line);
+        assert!(file.unwrap().ends_with("base.cu"));
+        assert_eq!(line, 2);
+    }
+
+    #[test]
+    fn test_private_items_and_import_cycles_are_reported() {
+        let private = load(
+            "private",
+            &[
+                ("main.cu", "import util\nutil.hidden()"),
+                ("util.cu", "hidden() { }"),
+            ],
+        );
+        assert!(private
+            .unwrap_err()
+            .message
+            .contains("`hidden` is private to module `util`"));
+        let cycle = load("cycle", &[("main.cu", "import a"), ("a.cu", "import main")]).unwrap_err();
+        assert!(
+            cycle.message.starts_with("import cycle:"),
+            "{}",
+            cycle.message
+        );
+        assert!(cycle.message.ends_with("main.cu"), "{}", cycle.message);
+        assert!(cycle.file.unwrap().ends_with("a.cu"));
+    }
+
+    #[test]
+    fn test_notes_keep_the_file_they_point_into() {
+        let program = load(
+            "notes",
+            &[("main.cu", "import kk\nkk.K = 5;"), ("kk.cu", "// k\npub const K:int = 1;")],
+        )
+        .unwrap();
+        let diagnostics = crate::checker::check(&program.statements).unwrap_err();
+        let diagnostic = program.sources.relocate(diagnostics[0].clone());
+        assert!(diagnostic.file.unwrap().ends_with("main.cu"));
+        assert_eq!(diagnostic.line, 2);
+        assert!(diagnostic.notes[0].file.as_ref().unwrap().ends_with("kk.cu"));
+        assert_eq!(diagnostic.notes[0].line, 2);
+    }
+
+    #[test]
+    fn test_standard_modules_are_used_when_no_file_has_the_name() {
+        let functions = |program: &Program| -> Vec<(String, bool)> {
+            program
+                .statements
+                .iter()
+                .filter_map(|stmt| match &stmt.kind {
+                    StmtKind::Function(decl) => Some((decl.name.clone(), decl.native)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let standard = load("std", &[("main.cu", "import math\nx:int = math.gcd(4, 6)")]).unwrap();
+        assert!(functions(&standard).contains(&("math.sin".to_string(), true)));
+        assert!(functions(&standard).contains(&("math.gcd".to_string(), false)));
+        let own = load(
+            "own-math",
+            &[
+                ("main.cu", "import math\nx:int = math.twice(2)"),
+                ("math.cu", "pub twice:int(n:int) { return n * 2; }"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(functions(&own), vec![("math2.twice".to_string(), false)]);
+        assert!(load("native", &[("main.cu", "f(x:int):int;")]).is_err());
+    }
+}
@@ -1,139 +1,1596 @@
 // parser.rs
-// takes in Vec<Token> and spits out Vec<ParsedToken> where the Parsed tokens are the parsed version of the tokens
-// say a:int = 3; turns into ParsedToken::Assignment(Identifier("a"), Token::Int(3))
-use std::iter::Peekable;
-use std::str::Chars;
-
+// takes in the Vec<(Token, line)> from the lexer and spits out the statements of the program
+// say a:int = 3; turns into StmtKind::Let { name: "a", ty: int, value: Integer(3) }
 
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::format::FormatSpec;
 use crate::token::*;
 
-pub struct Parser<'a> {
-    input: Peekable<Chars<'a>>,
+pub struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // where a `>>` was split into `> >` to close two lists of type arguments, so a rewind can
+    // put it back
+    splits: Vec<usize>,
+    // whether functions can be declared without a body, only in the standard modules
+    natives: bool,
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(input: &'a str) -> Self {
-        Parser { 
-            input: input.chars().peekable(),
+type ParseResult<T> = Result<T, Diagnostic>;
+
+impl Parser {
+    pub fn new(tokens: Vec<(Token, usize)>) -> Self {
+        Parser { tokens, pos: 0, splits: Vec::new(), natives: false }
+    }
+
+    /// A parser for a standard module, which can declare functions the interpreter implements.
+    pub fn with_natives(mut self) -> Self {
+        self.natives = true;
+        self
+    }
+
+    pub fn parse_program(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut statements = Vec::new();
+        while !self.at(&Token::Eof) {
+            statements.push(self.parse_statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn peek(&self) -> &Token {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let index = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos.min(self.tokens.len() - 1)].1
+    }
+
+    fn at(&self, token: &Token) -> bool {
+        self.peek() == token
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.at(token) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token, what: &str) -> ParseResult<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", what)))
+        }
+    }
+
+    // `>` closing type arguments. in `Box<Box<int>>` the lexer saw a `>>`, which is split in two
+    fn expect_closing_angle(&mut self, what: &str) -> ParseResult<()> {
+        if self.at(&Token::ShiftRight) {
+            let line = self.line();
+            self.tokens[self.pos].0 = Token::GreaterThan;
+            self.tokens.insert(self.pos + 1, (Token::GreaterThan, line));
+            self.splits.push(self.pos);
+        }
+        self.expect(&Token::GreaterThan, what)
+    }
+
+    // goes back to `start`, joining any `>>` split up since then
+    fn rewind(&mut self, start: usize) {
+        while self.splits.last().is_some_and(|&split| split >= start) {
+            let split = self.splits.pop().unwrap();
+            self.tokens[split].0 = Token::ShiftRight;
+            self.tokens.remove(split + 1);
+        }
+        self.pos = start;
+    }
+
+    fn expect_identifier(&mut self, what: &str) -> ParseResult<String> {
+        match self.peek().clone() {
+            Token::Identifier(name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.error(&format!("expected {}", what))),
+        }
+    }
+
+    // a name, or a name from an imported module like `util.Point`
+    fn expect_path(&mut self, what: &str) -> ParseResult<String> {
+        let name = self.expect_identifier(what)?;
+        if self.at(&Token::Dot) && matches!(self.peek_at(1), Token::Identifier(_)) {
+            self.advance();
+            let item = self.expect_identifier(what)?;
+            return Ok(format!("{}.{}", name, item));
+        }
+        Ok(name)
+    }
+
+    fn error(&self, message: &str) -> Diagnostic {
+        Diagnostic::new(format!("{}, found {}", message, self.peek()), self.line())
+    }
+
+    // statements may end with a semicolon but don't have to
+    fn end_statement(&mut self) {
+        self.eat(&Token::Semicolon);
+    }
+
+    fn parse_block(&mut self) -> ParseResult<Vec<Stmt>> {
+        self.expect(&Token::LeftBrace, "`{`")?;
+        let mut statements = Vec::new();
+        while !self.at(&Token::RightBrace) {
+            if self.at(&Token::Eof) {
+                return Err(self.error("expected `}`"));
+            }
+            statements.push(self.parse_statement()?);
         }
+        self.advance();
+        Ok(statements)
     }
 
-    fn read_identifier(&mut self, first_char: char) -> String {
-        let mut ident = String::new();
-       
+    fn parse_statement(&mut self) -> ParseResult<Stmt> {
+        let line = self.line();
+        let kind = match self.peek() {
+            Token::Semicolon => {
+                self.advance();
+                StmtKind::Block(Vec::new())
+            }
+            Token::Struct => StmtKind::Struct(self.parse_struct()?),
+            Token::Trait => StmtKind::Trait(self.parse_trait()?),
+            Token::Impl => StmtKind::Impl(self.parse_impl()?),
+            Token::Print => {
+                self.advance();
+                self.expect(&Token::LeftParen, "`(` after print")?;
+                let args = self.parse_arguments()?;
+                self.end_statement();
+                StmtKind::Print(args)
+            }
+            Token::Return => {
+                self.advance();
+                let value = if matches!(self.peek(), Token::Semicolon | Token::RightBrace) {
+                    None
+                } else {
+                    Some(self.parse_expression()?)
+                };
+                self.end_statement();
+                StmtKind::Return(value)
+            }
+            Token::If => self.parse_if()?,
+            Token::While => {
+                self.advance();
+                let cond = self.parse_condition()?;
+                let body = self.parse_block()?;
+                StmtKind::While { cond, body }
+            }
+            Token::For => self.parse_for()?,
+            Token::Try => self.parse_try()?,
+            Token::Import => {
+                self.advance();
+                let path = match self.advance() {
+                    Token::String(path) => path,
+                    Token::Identifier(name) => format!("{}.cu", name),
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.error("expected a module name or a file path after `import`"));
+                    }
+                };
+                self.end_statement();
+                StmtKind::Import(path)
+            }
+            Token::Pub => {
+                self.advance();
+                let mut stmt = self.parse_statement()?;
+                match &mut stmt.kind {
+                    StmtKind::Function(decl) => decl.public = true,
+                    StmtKind::Struct(decl) => decl.public = true,
+                    StmtKind::Trait(decl) => decl.public = true,
+                    StmtKind::Const { public, .. } => *public = true,
+                    _ => {
+                        return Err(Diagnostic::new(
+                            "only functions, structs, traits and constants can be `pub`",
+                            line,
+                        ))
+                    }
+                }
+                return Ok(stmt);
+            }
+            Token::Const => self.parse_const()?,
+            Token::LeftParen if self.looks_like_destructure() => self.parse_destructure(false)?,
+            Token::Mut => {
+                self.advance();
+                if self.at(&Token::LeftParen) && self.looks_like_destructure() {
+                    return Ok(Stmt {
+                        kind: self.parse_destructure(true)?,
+                        line,
+                    });
+                }
+                if !matches!(self.peek(), Token::Identifier(_)) || self.peek_at(1) != &Token::Colon {
+                    return Err(self.error("expected a variable declaration after `mut`"));
+                }
+                match self.parse_declaration()? {
+                    StmtKind::Let { name, ty, value, .. } => StmtKind::Let {
+                        name,
+                        ty,
+                        value,
+                        mutable: true,
+                    },
+                    _ => return Err(Diagnostic::new("`mut` can only be used on variables", line)),
+                }
+            }
+            Token::LeftBrace => StmtKind::Block(self.parse_block()?),
+            Token::Identifier(_) if self.peek_at(1) == &Token::Colon => self.parse_declaration()?,
+            Token::Identifier(_) if self.looks_like_function() => {
+                StmtKind::Function(self.parse_function()?)
+            }
+            _ => {
+                let expr = self.parse_expression()?;
+                if self.eat(&Token::Assign) {
+                    if !matches!(
+                        expr.kind,
+                        ExprKind::Identifier(_) | ExprKind::Field(..) | ExprKind::Index(..)
+                    ) {
+                        return Err(Diagnostic::new("invalid left-hand side of assignment", line));
+                    }
+                    let value = self.parse_expression()?;
+                    self.end_statement();
+                    StmtKind::Assign { target: expr, value }
+                } else {
+                    self.end_statement();
+                    StmtKind::Expr(expr)
+                }
+            }
+        };
+        Ok(Stmt { kind, line })
+    }
+
+    // `try { } catch (e) { }`, the parens and the name are both optional
+    fn parse_try(&mut self) -> ParseResult<StmtKind> {
+        self.advance();
+        let body = self.parse_block()?;
+        self.expect(&Token::Catch, "`catch` after try block")?;
+        let error_name = if self.eat(&Token::LeftParen) {
+            let name = self.expect_identifier("a name for the caught error")?;
+            self.expect(&Token::RightParen, "`)` after the caught error")?;
+            Some(name)
+        } else if let Token::Identifier(name) = self.peek().clone() {
+            self.advance();
+            Some(name)
+        } else {
+            None
+        };
+        let handler = self.parse_block()?;
+        Ok(StmtKind::Try {
+            body,
+            error_name,
+            handler,
+        })
+    }
+
+    // `for x in items { }`, `for (k, v) in map { }` or `for (a, b, c) in tuples { }`
+    fn parse_for(&mut self) -> ParseResult<StmtKind> {
+        self.advance();
+        let names = if self.at(&Token::LeftParen) {
+            self.parse_names("a loop variable")?
+        } else {
+            vec![self.expect_identifier("a loop variable after `for`")?]
+        };
+        self.expect(&Token::In, "`in` after the loop variable")?;
+        let iterable = self.parse_condition()?;
+        let body = self.parse_block()?;
+        Ok(StmtKind::For {
+            names,
+            iterable,
+            body,
+        })
+    }
 
-        while let Some(&c) = self.input.peek() {
-            if c.is_alphanumeric() || c == '_' {
-                ident.push(c);
-                self.input.next();
+    fn parse_if(&mut self) -> ParseResult<StmtKind> {
+        self.advance();
+        let cond = self.parse_condition()?;
+        let then_branch = self.parse_block()?;
+        let else_branch = if self.eat(&Token::Else) {
+            if self.at(&Token::If) {
+                let line = self.line();
+                let kind = self.parse_if()?;
+                Some(vec![Stmt { kind, line }])
             } else {
-                break;
+                Some(self.parse_block()?)
+            }
+        } else {
+            None
+        };
+        Ok(StmtKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    // `if` where a value is expected, the `if` itself is already eaten
+    fn parse_if_expression(&mut self) -> ParseResult<ExprKind> {
+        let cond = self.parse_condition()?;
+        let then_branch = value_block(self.parse_block()?);
+        let else_branch = if self.eat(&Token::Else) {
+            if self.at(&Token::If) {
+                let line = self.line();
+                self.advance();
+                let kind = self.parse_if_expression()?;
+                Some(vec![Stmt {
+                    kind: StmtKind::Expr(Expr { kind, line }),
+                    line,
+                }])
+            } else {
+                Some(value_block(self.parse_block()?))
+            }
+        } else {
+            None
+        };
+        Ok(ExprKind::If {
+            cond: Box::new(cond),
+            then_branch,
+            else_branch,
+        })
+    }
+
+    // conditions are followed by a block, so `x {` must not be read as a struct literal
+    fn parse_condition(&mut self) -> ParseResult<Expr> {
+        self.parse_expression_with(false)
+    }
+
+    // `(a, b)` at the start of a statement is only a declaration when a `:` follows it
+    fn looks_like_destructure(&self) -> bool {
+        let mut offset = 1;
+        loop {
+            if !matches!(self.peek_at(offset), Token::Identifier(_)) {
+                return false;
+            }
+            match self.peek_at(offset + 1) {
+                Token::Comma => offset += 2,
+                Token::RightParen => return self.peek_at(offset + 2) == &Token::Colon,
+                _ => return false,
+            }
+        }
+    }
+
+    // (a, b) := value;  or  (a, b):(int, str) = value;
+    fn parse_destructure(&mut self, mutable: bool) -> ParseResult<StmtKind> {
+        let names = self.parse_names("a variable name")?;
+        self.expect(&Token::Colon, "`:` after the names")?;
+        let ty = if self.eat(&Token::Assign) {
+            None
+        } else {
+            let ty = self.parse_type()?;
+            self.expect(&Token::Assign, "`=` in declaration")?;
+            Some(ty)
+        };
+        let value = self.parse_expression()?;
+        self.end_statement();
+        Ok(StmtKind::Destructure {
+            names,
+            ty,
+            value,
+            mutable,
+        })
+    }
+
+    // `(a, b, c)`, the names a tuple is taken apart into
+    fn parse_names(&mut self, what: &str) -> ParseResult<Vec<String>> {
+        self.expect(&Token::LeftParen, "`(`")?;
+        let mut names = vec![self.expect_identifier(what)?];
+        while self.eat(&Token::Comma) {
+            names.push(self.expect_identifier(what)?);
+        }
+        self.expect(&Token::RightParen, "`)` after the names")?;
+        Ok(names)
+    }
+
+    // name:type = value;  or  name:type(params) { body }
+    fn parse_declaration(&mut self) -> ParseResult<StmtKind> {
+        let name = self.expect_identifier("a name")?;
+        let line = self.line();
+        self.expect(&Token::Colon, "`:`")?;
+        // x := 5 takes its type from the value
+        if self.eat(&Token::Assign) {
+            let value = self.parse_expression()?;
+            self.end_statement();
+            return Ok(StmtKind::Let {
+                name,
+                ty: None,
+                value,
+                mutable: false,
+            });
+        }
+        let ty = self.parse_type()?;
+        if self.at(&Token::LeftParen) {
+            let params = self.parse_params()?;
+            let (body, native) = self.parse_function_body(&params, &ty, line)?;
+            return Ok(StmtKind::Function(FunctionDecl {
+                name,
+                type_params: Vec::new(),
+                params,
+                ret: ty,
+                body,
+                public: false,
+                native,
+                line,
+            }));
+        }
+        self.expect(&Token::Assign, "`=` in declaration")?;
+        let value = self.parse_expression()?;
+        self.end_statement();
+        Ok(StmtKind::Let {
+            name,
+            ty: Some(ty),
+            value,
+            mutable: false,
+        })
+    }
+
+    // const NAME:type = value
+    fn parse_const(&mut self) -> ParseResult<StmtKind> {
+        self.advance();
+        let name = self.expect_identifier("a name after `const`")?;
+        self.expect(&Token::Colon, "`:` after the constant's name")?;
+        let ty = self.parse_type()?;
+        self.expect(&Token::Assign, "`=` in constant declaration")?;
+        let value = self.parse_expression()?;
+        self.end_statement();
+        Ok(StmtKind::Const {
+            name,
+            ty,
+            value,
+            public: false,
+        })
+    }
+
+    // a function declaration is an identifier, optional `<T, ...>`, a parameter list and then
+    // either `:` and a return type or the body. anything else starting like this is a call.
+    fn looks_like_function(&self) -> bool {
+        let mut offset = 1;
+        if self.peek_at(offset) == &Token::LessThan {
+            let mut depth = 0;
+            loop {
+                match self.peek_at(offset) {
+                    Token::LessThan => depth += 1,
+                    Token::GreaterThan | Token::ShiftRight => {
+                        depth -= if self.peek_at(offset) == &Token::ShiftRight { 2 } else { 1 };
+                        if depth <= 0 {
+                            offset += 1;
+                            break;
+                        }
+                    }
+                    Token::Identifier(_) | Token::Comma | Token::Colon | Token::Plus | Token::Dot
+                    | Token::Question | Token::LeftBracket | Token::RightBracket | Token::LeftParen
+                    | Token::RightParen => {}
+                    Token::Int | Token::Float | Token::Str | Token::Bool | Token::Void => {}
+                    _ => return false,
+                }
+                offset += 1;
+            }
+            // name<T>:ret(params)
+            if self.peek_at(offset) == &Token::Colon {
+                return true;
+            }
+        }
+        if self.peek_at(offset) != &Token::LeftParen {
+            return false;
+        }
+        let mut depth = 0;
+        loop {
+            match self.peek_at(offset) {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                Token::Eof => return false,
+                _ => {}
+            }
+            offset += 1;
+        }
+        match self.peek_at(offset + 1) {
+            Token::Colon | Token::LeftBrace => true,
+            // `exit(code:int);`, a native function that returns nothing
+            Token::Semicolon => self.natives,
+            _ => false,
+        }
+    }
+
+    // name<T>(params):ret { body }  or  name<T>:ret(params) { body }
+    fn parse_function(&mut self) -> ParseResult<FunctionDecl> {
+        let line = self.line();
+        let name = self.expect_identifier("a function name")?;
+        let type_params = self.parse_type_params()?;
+        let (params, ret) = if self.eat(&Token::Colon) {
+            let ret = self.parse_type()?;
+            (self.parse_params()?, ret)
+        } else {
+            let params = self.parse_params()?;
+            let ret = if self.eat(&Token::Colon) {
+                self.parse_type()?
+            } else {
+                TypeExpr::named("void")
+            };
+            (params, ret)
+        };
+        let (body, native) = self.parse_function_body(&params, &ret, line)?;
+        Ok(FunctionDecl {
+            name,
+            type_params,
+            params,
+            ret,
+            body,
+            public: false,
+            native,
+            line,
+        })
+    }
+
+    // the body of a function, or just `;` for a native function in a standard module
+    fn parse_function_body(&mut self, params: &[Param], ret: &TypeExpr, line: usize) -> ParseResult<(Vec<Stmt>, bool)> {
+        if self.natives && self.eat(&Token::Semicolon) {
+            return Ok((Vec::new(), true));
+        }
+        let mut body = self.parse_block()?;
+        if *ret != TypeExpr::named("void") {
+            return_tail(&mut body);
+        }
+        unpack_params(params, &mut body, line);
+        Ok((body, false))
+    }
+
+    // <T, U: Show + Eq>
+    fn parse_type_params(&mut self) -> ParseResult<Vec<TypeParam>> {
+        let mut type_params = Vec::new();
+        if self.eat(&Token::LessThan) {
+            loop {
+                let name = self.expect_identifier("a type parameter name")?;
+                let mut bounds = Vec::new();
+                if self.eat(&Token::Colon) {
+                    loop {
+                        bounds.push(self.expect_path("a trait name")?);
+                        if !self.eat(&Token::Plus) {
+                            break;
+                        }
+                    }
+                }
+                type_params.push(TypeParam { name, bounds });
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
             }
+            self.expect(&Token::GreaterThan, "`>` after type parameters")?;
         }
+        Ok(type_params)
+    }
 
-        ident
+    fn parse_params(&mut self) -> ParseResult<Vec<Param>> {
+        self.expect(&Token::LeftParen, "`(` before parameters")?;
+        let mut params = Vec::new();
+        while !self.at(&Token::RightParen) {
+            let mutable = self.eat(&Token::Mut);
+            let name = if self.at(&Token::LeftParen) {
+                let names = self.parse_names("a parameter name")?;
+                format!("({})", names.join(", "))
+            } else {
+                self.expect_identifier("a parameter name")?
+            };
+            // methods take a bare `self` whose type is the type being implemented
+            if name == "self" && !self.at(&Token::Colon) {
+                params.push(Param {
+                    name,
+                    ty: TypeExpr::named("Self"),
+                    mutable,
+                    default: None,
+                    variadic: false,
+                });
+            } else {
+                self.expect(&Token::Colon, "`:` after parameter name")?;
+                // `xs: ...int` collects the rest of the arguments into an `int[]`
+                let variadic = self.eat(&Token::Ellipsis);
+                let ty = self.parse_type()?;
+                let default = if !variadic && self.eat(&Token::Assign) {
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                params.push(Param {
+                    name,
+                    ty: if variadic { TypeExpr::List(Box::new(ty)) } else { ty },
+                    mutable,
+                    default,
+                    variadic,
+                });
+            }
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::RightParen, "`)` after parameters")?;
+        Ok(params)
     }
-    
-        fn read_number(&mut self, first_char: char) -> Token {
-            let mut num_str = String::new();
 
+    // struct Name<T> { field:type, ... }
+    fn parse_struct(&mut self) -> ParseResult<StructDecl> {
+        let line = self.line();
+        self.advance();
+        let name = self.expect_identifier("a struct name")?;
+        let type_params = self.parse_type_params()?;
+        self.expect(&Token::LeftBrace, "`{` after struct name")?;
+        let mut fields = Vec::new();
+        while !self.at(&Token::RightBrace) {
+            let field = self.expect_identifier("a field name")?;
+            self.expect(&Token::Colon, "`:` after field name")?;
+            fields.push((field, self.parse_type()?));
+            if !self.eat(&Token::Comma) {
+                self.eat(&Token::Semicolon);
+            }
+        }
+        self.advance();
+        Ok(StructDecl {
+            name,
+            type_params,
+            fields,
+            public: false,
+            line,
+        })
+    }
 
-            while let Some(&c) = self.input.peek() {
-                if c.is_numeric() || c == '.' {
-                    num_str.push(c);
-                    self.input.next();
+    // trait Name<T> { method(self, other:Self):bool; ... }
+    fn parse_trait(&mut self) -> ParseResult<TraitDecl> {
+        let line = self.line();
+        self.advance();
+        let name = self.expect_identifier("a trait name")?;
+        let type_params = self.parse_type_params()?;
+        self.expect(&Token::LeftBrace, "`{` after trait name")?;
+        let mut methods = Vec::new();
+        while !self.at(&Token::RightBrace) {
+            let line = self.line();
+            let method = self.expect_identifier("a method name")?;
+            let (params, ret) = if self.eat(&Token::Colon) {
+                let ret = self.parse_type()?;
+                (self.parse_params()?, ret)
+            } else {
+                let params = self.parse_params()?;
+                let ret = if self.eat(&Token::Colon) {
+                    self.parse_type()?
                 } else {
+                    TypeExpr::named("void")
+                };
+                (params, ret)
+            };
+            if params.first().map(|p| p.name.as_str()) != Some("self") {
+                return Err(Diagnostic::new(
+                    format!("trait method `{}` must take `self` as its first parameter", method),
+                    line,
+                ));
+            }
+            self.end_statement();
+            methods.push(TraitMethod {
+                name: method,
+                params,
+                ret,
+                line,
+            });
+        }
+        self.advance();
+        Ok(TraitDecl {
+            name,
+            type_params,
+            methods,
+            public: false,
+            line,
+        })
+    }
+
+    // impl<T> Trait<U> for Type { methods }
+    fn parse_impl(&mut self) -> ParseResult<ImplDecl> {
+        let line = self.line();
+        self.advance();
+        let type_params = self.parse_type_params()?;
+        let trait_name = self.expect_path("a trait name after `impl`")?;
+        let mut trait_args = Vec::new();
+        if self.eat(&Token::LessThan) {
+            loop {
+                trait_args.push(self.parse_type()?);
+                if !self.eat(&Token::Comma) {
                     break;
                 }
             }
-            Token::Integer(num_str.parse().unwrap())
+            self.expect_closing_angle("`>` after the trait's type arguments")?;
+        }
+        self.expect(&Token::For, "`for` after the trait name")?;
+        let target = self.parse_type()?;
+        self.expect(&Token::LeftBrace, "`{` after the impl header")?;
+        let mut methods = Vec::new();
+        while !self.at(&Token::RightBrace) {
+            if self.at(&Token::Eof) {
+                return Err(self.error("expected `}`"));
+            }
+            methods.push(self.parse_function()?);
         }
-    
-    fn read_string(&mut self, first_char: char) -> Token {
-        let mut string = String::new();
-        while let Some(&c) = self.input.peek() {
-            if c == '"' {
-                self.input.next();
+        self.advance();
+        Ok(ImplDecl {
+            trait_name,
+            trait_args,
+            type_params,
+            target,
+            methods,
+            line,
+        })
+    }
+
+    pub fn parse_type(&mut self) -> ParseResult<TypeExpr> {
+        let mut ty = match self.advance() {
+            Token::Int => TypeExpr::named("int"),
+            Token::Float => TypeExpr::named("float"),
+            Token::Str => TypeExpr::named("str"),
+            Token::Bool => TypeExpr::named("bool"),
+            Token::Void => TypeExpr::named("void"),
+            Token::LeftParen => {
+                let mut items = vec![self.parse_type()?];
+                while self.eat(&Token::Comma) {
+                    items.push(self.parse_type()?);
+                }
+                self.expect(&Token::RightParen, "`)` after the tuple's types")?;
+                match items.len() {
+                    1 => items.pop().unwrap(),
+                    _ => TypeExpr::Tuple(items),
+                }
+            }
+            Token::Identifier(name) => {
+                let name = if self.at(&Token::Dot) && matches!(self.peek_at(1), Token::Identifier(_)) {
+                    self.pos -= 1;
+                    self.expect_path("a type")?
+                } else {
+                    name
+                };
+                let mut args = Vec::new();
+                if self.eat(&Token::LessThan) {
+                    loop {
+                        args.push(self.parse_type()?);
+                        if !self.eat(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect_closing_angle("`>` after type arguments")?;
+                }
+                TypeExpr::Named(name, args)
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected a type"));
+            }
+        };
+        loop {
+            if self.at(&Token::LeftBracket) && self.peek_at(1) == &Token::RightBracket {
+                self.advance();
+                self.advance();
+                ty = TypeExpr::List(Box::new(ty));
+            } else if self.eat(&Token::Question) {
+                ty = TypeExpr::Optional(Box::new(ty));
+            } else {
+                break;
+            }
+        }
+        Ok(ty)
+    }
+
+    // tries to read `<type, ...>` at the current position, rewinding if it isn't one
+    fn try_parse_type_args(&mut self, allow_struct: bool) -> Option<Vec<TypeExpr>> {
+        let start = self.pos;
+        if !self.eat(&Token::LessThan) {
+            return None;
+        }
+        let mut args = Vec::new();
+        loop {
+            match self.parse_type() {
+                Ok(ty) => args.push(ty),
+                Err(_) => {
+                    self.rewind(start);
+                    return None;
+                }
+            }
+            if !self.eat(&Token::Comma) {
                 break;
+            }
+        }
+        let followed_by_call = self.at(&Token::GreaterThan)
+            && (self.peek_at(1) == &Token::LeftParen
+                || (allow_struct && self.peek_at(1) == &Token::LeftBrace));
+        if followed_by_call {
+            self.advance();
+            Some(args)
+        } else {
+            self.rewind(start);
+            None
+        }
+    }
+
+    pub fn parse_expression(&mut self) -> ParseResult<Expr> {
+        self.parse_expression_with(true)
+    }
+
+    fn parse_expression_with(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        self.parse_range(allow_struct)
+    }
+
+    fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        let line = left.line;
+        Expr {
+            kind: ExprKind::Binary(op, Box::new(left), Box::new(right)),
+            line,
+        }
+    }
+
+    // `a..b`, `a..=b` and `a..b step n` bind looser than any operator, so `0..n + 1` counts to n.
+    // `step` is only a keyword right after a range
+    fn parse_range(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let start = self.parse_coalesce(allow_struct)?;
+        let inclusive = match self.peek() {
+            Token::DotDot => false,
+            Token::DotDotEq => true,
+            _ => return Ok(start),
+        };
+        self.advance();
+        let end = self.parse_coalesce(allow_struct)?;
+        let step = if self.peek() == &Token::Identifier("step".to_string()) {
+            self.advance();
+            Some(Box::new(self.parse_coalesce(allow_struct)?))
+        } else {
+            None
+        };
+        let line = start.line;
+        Ok(Expr {
+            kind: ExprKind::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+                inclusive,
+                step,
+            },
+            line,
+        })
+    }
+
+    // a ?? b, binds looser than everything else and groups to the right
+    fn parse_coalesce(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let left = self.parse_or(allow_struct)?;
+        if self.eat(&Token::Coalesce) {
+            let right = self.parse_coalesce(allow_struct)?;
+            return Ok(Self::binary(BinaryOp::Coalesce, left, right));
+        }
+        Ok(left)
+    }
+
+    fn parse_or(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_and(allow_struct)?;
+        while self.eat(&Token::Or) {
+            let right = self.parse_and(allow_struct)?;
+            left = Self::binary(BinaryOp::Or, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_equality(allow_struct)?;
+        while self.eat(&Token::And) {
+            let right = self.parse_equality(allow_struct)?;
+            left = Self::binary(BinaryOp::And, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_equality(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_comparison(allow_struct)?;
+        loop {
+            let op = match self.peek() {
+                Token::Equals => BinaryOp::Eq,
+                Token::NotEquals => BinaryOp::Ne,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_comparison(allow_struct)?;
+            left = Self::binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_comparison(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_bit_or(allow_struct)?;
+        loop {
+            let op = match self.peek() {
+                Token::LessThan => BinaryOp::Lt,
+                Token::GreaterThan => BinaryOp::Gt,
+                Token::LessThanOrEqual => BinaryOp::Le,
+                Token::GreaterThanOrEqual => BinaryOp::Ge,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_bit_or(allow_struct)?;
+            left = Self::binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    // the bit operators bind tighter than comparisons, so `x & 1 == 0` tests the low bit
+    fn parse_bit_or(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_bit_xor(allow_struct)?;
+        while self.eat(&Token::Pipe) {
+            let right = self.parse_bit_xor(allow_struct)?;
+            left = Self::binary(BinaryOp::BitOr, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_bit_xor(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_bit_and(allow_struct)?;
+        while self.eat(&Token::Caret) {
+            let right = self.parse_bit_and(allow_struct)?;
+            left = Self::binary(BinaryOp::BitXor, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_bit_and(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_shift(allow_struct)?;
+        while self.eat(&Token::Ampersand) {
+            let right = self.parse_shift(allow_struct)?;
+            left = Self::binary(BinaryOp::BitAnd, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_shift(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_additive(allow_struct)?;
+        loop {
+            let op = match self.peek() {
+                Token::ShiftLeft => BinaryOp::Shl,
+                Token::ShiftRight => BinaryOp::Shr,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_additive(allow_struct)?;
+            left = Self::binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_multiplicative(allow_struct)?;
+        loop {
+            let op = match self.peek() {
+                Token::Plus => BinaryOp::Add,
+                Token::Minus => BinaryOp::Sub,
+                Token::WrappingPlus => BinaryOp::WrappingAdd,
+                Token::WrappingMinus => BinaryOp::WrappingSub,
+                Token::SaturatingPlus => BinaryOp::SaturatingAdd,
+                Token::SaturatingMinus => BinaryOp::SaturatingSub,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_multiplicative(allow_struct)?;
+            left = Self::binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut left = self.parse_cast(allow_struct)?;
+        loop {
+            let op = match self.peek() {
+                Token::Multiply => BinaryOp::Mul,
+                Token::Divide => BinaryOp::Div,
+                Token::Modulo => BinaryOp::Mod,
+                Token::WrappingMultiply => BinaryOp::WrappingMul,
+                Token::SaturatingMultiply => BinaryOp::SaturatingMul,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_cast(allow_struct)?;
+            left = Self::binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    // `x as i64`, tighter than the operators but looser than `-x`
+    fn parse_cast(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut expr = self.parse_unary(allow_struct)?;
+        while self.eat(&Token::As) {
+            let ty = self.parse_type()?;
+            let line = expr.line;
+            expr = Expr {
+                kind: ExprKind::Cast(Box::new(expr), ty),
+                line,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let line = self.line();
+        let op = match self.peek() {
+            Token::Minus => UnaryOp::Neg,
+            Token::Not => UnaryOp::Not,
+            Token::Tilde => UnaryOp::BitNot,
+            _ => return self.parse_power(allow_struct),
+        };
+        self.advance();
+        let operand = self.parse_unary(allow_struct)?;
+        // a negative literal is one value, so `-128i8` fits in an i8
+        let kind = match (op, operand.kind) {
+            (UnaryOp::Neg, ExprKind::Integer(int, _)) => ExprKind::Integer(-int, LiteralType::default()),
+            (UnaryOp::Neg, ExprKind::SizedInteger(int, kind)) => ExprKind::SizedInteger(-int, kind),
+            (UnaryOp::Neg, ExprKind::BigInteger(int)) => ExprKind::BigInteger(-&int),
+            (op, kind) => ExprKind::Unary(
+                op,
+                Box::new(Expr {
+                    kind,
+                    line: operand.line,
+                }),
+            ),
+        };
+        Ok(Expr { kind, line })
+    }
+
+    // `a ** b` groups to the right and binds tighter than a sign in front, so `-2 ** 2` is -4
+    fn parse_power(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let base = self.parse_postfix(allow_struct)?;
+        if self.eat(&Token::Power) {
+            let exponent = self.parse_unary(allow_struct)?;
+            return Ok(Self::binary(BinaryOp::Pow, base, exponent));
+        }
+        Ok(base)
+    }
+
+    fn parse_postfix(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let mut expr = self.parse_primary(allow_struct)?;
+        loop {
+            let line = self.line();
+            if self.eat(&Token::LeftBracket) {
+                let index = self.parse_expression()?;
+                self.expect(&Token::RightBracket, "`]` after index")?;
+                expr = Expr {
+                    kind: ExprKind::Index(Box::new(expr), Box::new(index)),
+                    line,
+                };
+            } else if self.eat(&Token::Dot) {
+                if let Token::Integer(index) = self.peek().clone() {
+                    self.advance();
+                    expr = Expr {
+                        kind: ExprKind::Field(Box::new(expr), index.to_string()),
+                        line,
+                    };
+                    continue;
+                }
+                let field = self.expect_identifier("a field name after `.`")?;
+                let type_args = self.try_parse_type_args(false).unwrap_or_default();
+                let kind = if self.eat(&Token::LeftParen) {
+                    ExprKind::MethodCall {
+                        receiver: Box::new(expr),
+                        method: field,
+                        type_args,
+                        args: self.parse_arguments()?,
+                    }
+                } else {
+                    ExprKind::Field(Box::new(expr), field)
+                };
+                expr = Expr { kind, line };
+            } else if self.eat(&Token::Question) {
+                expr = Expr {
+                    kind: ExprKind::Propagate(Box::new(expr)),
+                    line,
+                };
             } else {
-                string.push(c);
-                self.input.next();
-            }
-        }
-        Token::String(string)
-    }
-    fn read_symbol(&mut self, first_char: char) -> Token {
-        match first_char {
-            ':' => Token::Colon,
-            ';' => Token::Semicolon,
-            '\n' => Token::NewLine,
-            ',' => Token::Comma,
-            '(' => Token::LeftParen,
-            ')' => Token::RightParen,
-            '{' => Token::LeftBrace,
-            '}' => Token::RightBrace,
-            _ => Token::Empty,
-        }
-    }
-    fn read_operator(&mut self, first_char: char) -> Token {
-        match first_char {
-            '+' => Token::Plus,
-            '-' => Token::Minus,
-            '*' => Token::Multiply,
-            '/' => Token::Divide,
-            '%' => Token::Modulo,
-            '=' => Token::Equals,
-            '!' => Token::Not,
-            '<' => Token::LessThan,
-            '>' => Token::GreaterThan,
-            '&' => Token::And,
-            '|' => Token::Or,
-            _ => Token::Empty,
-        }
-    }
-
-    fn read_keyword(&mut self, first_char: char) -> Token {
-        let keyword = self.read_identifier(first_char);
-        match keyword.as_str() {
-            "int" => Token::Int,
-            "str" => Token::Str,
-            "float" => Token::Float,
-            "bool" => Token::Bool,
-            "void" => Token::Void,
-            _ => Token::Identifier(keyword),
-        }
-    }
-
-    fn read_token(&mut self, first_char: char) -> Token {
-        match first_char {
-            '0'..='9' => self.read_number(first_char),
-            'a'..='z' | 'A'..='Z' => self.read_keyword(first_char),
-            '"' => self.read_string(first_char),
-            ':' | ';' | '\n' | ',' | '(' | ')' | '{' | '}' => self.read_symbol(first_char),
-            '+' | '-' | '*' | '/' | '%' | '=' | '!' | '<' | '>' | '&' | '|' => self.read_operator(first_char),
-            _ => Token::Empty,
-        }
-    }
-
-    fn skip_whitespace(&mut self) {
-        while let Some(&c) = self.input.peek() {
-            if c.is_whitespace() {
-                self.input.next();
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn parse_arguments(&mut self) -> ParseResult<Vec<Expr>> {
+        // the opening paren has already been consumed
+        let mut args = Vec::new();
+        while !self.at(&Token::RightParen) {
+            // `name: value` passes the argument by its parameter's name
+            if let (Token::Identifier(name), Token::Colon) = (self.peek().clone(), self.peek_at(1)) {
+                let line = self.line();
+                self.advance();
+                self.advance();
+                let value = self.parse_expression()?;
+                args.push(Expr {
+                    kind: ExprKind::NamedArg(name, Box::new(value)),
+                    line,
+                });
             } else {
+                args.push(self.parse_expression()?);
+            }
+            if !self.eat(&Token::Comma) {
                 break;
             }
         }
+        self.expect(&Token::RightParen, "`)` after arguments")?;
+        Ok(args)
+    }
+
+    fn starts_struct_literal(&self) -> bool {
+        self.at(&Token::LeftBrace)
+            && (self.peek_at(1) == &Token::RightBrace
+                || (matches!(self.peek_at(1), Token::Identifier(_))
+                    && self.peek_at(2) == &Token::Colon))
+    }
+
+    // reads `.item` after `name` if a struct literal follows, `util.Point { ... }`. otherwise it is
+    // a field access or a method call and the caller rewinds
+    fn qualified_name(&mut self, name: &str, allow_struct: bool) -> Option<String> {
+        if !self.eat(&Token::Dot) {
+            return None;
+        }
+        let item = match self.advance() {
+            Token::Identifier(item) => item,
+            _ => return None,
+        };
+        let after_item = self.pos;
+        self.try_parse_type_args(allow_struct);
+        let qualified = allow_struct && self.starts_struct_literal();
+        self.rewind(after_item);
+        qualified.then(|| format!("{}.{}", name, item))
+    }
+
+    fn parse_primary(&mut self, allow_struct: bool) -> ParseResult<Expr> {
+        let line = self.line();
+        let kind = match self.advance() {
+            Token::Integer(int) => ExprKind::Integer(int, LiteralType::default()),
+            Token::SizedInteger(int, kind) => ExprKind::SizedInteger(int, kind),
+            Token::BigInteger(int) => ExprKind::BigInteger(int),
+            Token::CFloat(float) => ExprKind::Float(float),
+            Token::String(string) => ExprKind::Str(string),
+            Token::Template(parts) => self.parse_template(parts, line)?,
+            Token::Unterminated(what, start) => {
+                return Err(Diagnostic::new(format!("unterminated {}", what), start))
+            }
+            Token::True => ExprKind::Bool(true),
+            Token::False => ExprKind::Bool(false),
+            Token::NoneLiteral => ExprKind::None,
+            Token::LeftParen => {
+                let expr = self.parse_expression()?;
+                if !self.eat(&Token::Comma) {
+                    self.expect(&Token::RightParen, "`)`")?;
+                    return Ok(expr);
+                }
+                let mut items = vec![expr];
+                while !self.at(&Token::RightParen) {
+                    items.push(self.parse_expression()?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(&Token::RightParen, "`)` after the tuple's items")?;
+                ExprKind::Tuple(items)
+            }
+            Token::LeftBracket => self.parse_collection()?,
+            Token::If => self.parse_if_expression()?,
+            Token::LeftBrace => {
+                self.pos -= 1;
+                ExprKind::Block(value_block(self.parse_block()?))
+            }
+            Token::Identifier(name) => {
+                // `util.Point { ... }` names a struct from an imported module. `util.add(...)` is
+                // parsed as a method call, which the module resolver turns back into a call
+                let start = self.pos;
+                let name = match self.qualified_name(&name, allow_struct) {
+                    Some(qualified) => qualified,
+                    None => {
+                        self.rewind(start);
+                        name
+                    }
+                };
+                let type_args = self.try_parse_type_args(allow_struct).unwrap_or_default();
+                if self.eat(&Token::LeftParen) {
+                    // format's template is read as it was written, its braces are for format
+                    if let (Token::Template(parts), Token::Comma | Token::RightParen) =
+                        (self.peek().clone(), self.peek_at(1))
+                    {
+                        if name == "format" {
+                            let template_line = self.line();
+                            self.advance();
+                            self.eat(&Token::Comma);
+                            let mut args = vec![Expr {
+                                kind: ExprKind::Str(raw_template(parts)),
+                                line: template_line,
+                            }];
+                            args.extend(self.parse_arguments()?);
+                            return Ok(Expr {
+                                kind: ExprKind::Call {
+                                    name,
+                                    type_args,
+                                    args,
+                                },
+                                line,
+                            });
+                        }
+                    }
+                    ExprKind::Call {
+                        name,
+                        type_args,
+                        args: self.parse_arguments()?,
+                    }
+                } else if allow_struct && self.starts_struct_literal() {
+                    self.advance();
+                    let mut fields = Vec::new();
+                    while !self.at(&Token::RightBrace) {
+                        let field = self.expect_identifier("a field name")?;
+                        self.expect(&Token::Colon, "`:` after field name")?;
+                        fields.push((field, self.parse_expression()?));
+                        if !self.eat(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(&Token::RightBrace, "`}` after struct fields")?;
+                    ExprKind::StructLiteral {
+                        name,
+                        type_args,
+                        fields,
+                    }
+                } else {
+                    ExprKind::Identifier(name)
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected an expression"));
+            }
+        };
+        Ok(Expr { kind, line })
+    }
+
+    // each `{expr:spec}` of an interpolated string is parsed on its own, on the string's line
+    fn parse_template(&mut self, parts: Vec<TemplatePart>, line: usize) -> ParseResult<ExprKind> {
+        let mut result = Vec::new();
+        for part in parts {
+            match part {
+                TemplatePart::Text(text) => {
+                    let text = text.replace("{{", "{").replace("}}", "}");
+                    match result.last_mut() {
+                        Some(StringPart::Text(previous)) => previous.push_str(&text),
+                        _ => result.push(StringPart::Text(text)),
+                    }
+                }
+                TemplatePart::Code(code) => {
+                    let (source, spec) = split_format_spec(&code);
+                    // `{}` and `{:spec}` are left alone as placeholders for `format`
+                    if source.trim().is_empty() {
+                        match result.last_mut() {
+                            Some(StringPart::Text(text)) => text.push_str(&format!("{{{}}}", code)),
+                            _ => result.push(StringPart::Text(format!("{{{}}}", code))),
+                        }
+                        continue;
+                    }
+                    let spec = match spec {
+                        Some(spec) => crate::format::parse_spec(spec)
+                            .map_err(|message| Diagnostic::new(message, line))?,
+                        None => FormatSpec::default(),
+                    };
+                    let tokens = crate::lexer::tokenize(source)
+                        .into_iter()
+                        .map(|(token, offset)| (token, line + offset - 1))
+                        .collect();
+                    let mut parser = Parser::new(tokens);
+                    let expr = parser.parse_expression()?;
+                    if !parser.at(&Token::Eof) {
+                        return Err(parser.error("expected `}` after the interpolated expression"));
+                    }
+                    result.push(StringPart::Value(expr, spec));
+                }
+            }
+        }
+        match result.as_slice() {
+            [] => Ok(ExprKind::Str(String::new())),
+            [StringPart::Text(text)] => Ok(ExprKind::Str(text.clone())),
+            _ => Ok(ExprKind::Interpolated(result)),
+        }
+    }
+
+    // [1, 2, 3] is a list, ["a": 1] is a map and [:] is an empty map
+    fn parse_collection(&mut self) -> ParseResult<ExprKind> {
+        if self.eat(&Token::Colon) {
+            self.expect(&Token::RightBracket, "`]` after `[:`")?;
+            return Ok(ExprKind::Map(Vec::new()));
+        }
+        if self.eat(&Token::RightBracket) {
+            return Ok(ExprKind::List(Vec::new()));
+        }
+        let first = self.parse_expression()?;
+        if self.eat(&Token::Colon) {
+            let mut entries = vec![(first, self.parse_expression()?)];
+            while self.eat(&Token::Comma) {
+                if self.at(&Token::RightBracket) {
+                    break;
+                }
+                let key = self.parse_expression()?;
+                self.expect(&Token::Colon, "`:` between map key and value")?;
+                entries.push((key, self.parse_expression()?));
+            }
+            self.expect(&Token::RightBracket, "`]` after map entries")?;
+            return Ok(ExprKind::Map(entries));
+        }
+        let mut items = vec![first];
+        while self.eat(&Token::Comma) {
+            if self.at(&Token::RightBracket) {
+                break;
+            }
+            items.push(self.parse_expression()?);
+        }
+        self.expect(&Token::RightBracket, "`]` after list items")?;
+        Ok(ExprKind::List(items))
+    }
+}
+
+// a parameter written `(a, b):(int, str)` is passed as one tuple, named after its pattern since
+// that can't clash with a real name, and taken apart where the body starts
+fn unpack_params(params: &[Param], body: &mut Vec<Stmt>, line: usize) {
+    let unpacked: Vec<Stmt> = params
+        .iter()
+        .filter_map(|param| {
+            let names = param.name.strip_prefix('(')?.strip_suffix(')')?;
+            Some(Stmt {
+                kind: StmtKind::Destructure {
+                    names: names.split(", ").map(String::from).collect(),
+                    ty: None,
+                    value: Expr {
+                        kind: ExprKind::Identifier(param.name.clone()),
+                        line,
+                    },
+                    mutable: param.mutable,
+                },
+                line,
+            })
+        })
+        .collect();
+    body.splice(0..0, unpacked);
+}
+
+// a function that returns a value returns its last expression, like a block used as a value. when
+// it ends in an `if`, a block or a `try` that's the last expression of each branch
+fn return_tail(statements: &mut [Stmt]) {
+    let Some(last) = statements.last_mut() else { return };
+    match &mut last.kind {
+        StmtKind::Expr(value) => {
+            let value = value.clone();
+            last.kind = StmtKind::Return(Some(value));
+        }
+        StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => {
+            return_tail(then_branch);
+            return_tail(else_branch);
+        }
+        StmtKind::Block(inner) => return_tail(inner),
+        StmtKind::Try { body, handler, .. } => {
+            return_tail(body);
+            return_tail(handler);
+        }
+        _ => {}
     }
 }
 
-impl<'a> Iterator for Parser<'a> {
-    type Item = Token;
+// a block used as a value ends in its value. an `if` with an `else` or a block in that last place
+// is parsed as a statement, so it's turned back into an expression to give the block its value
+fn value_block(mut statements: Vec<Stmt>) -> Vec<Stmt> {
+    if let Some(last) = statements.pop() {
+        let line = last.line;
+        let kind = match last.kind {
+            StmtKind::If {
+                cond,
+                then_branch,
+                else_branch: Some(else_branch),
+            } => StmtKind::Expr(Expr {
+                kind: ExprKind::If {
+                    cond: Box::new(cond),
+                    then_branch: value_block(then_branch),
+                    else_branch: Some(value_block(else_branch)),
+                },
+                line,
+            }),
+            StmtKind::Block(inner) => StmtKind::Expr(Expr {
+                kind: ExprKind::Block(value_block(inner)),
+                line,
+            }),
+            kind => kind,
+        };
+        statements.push(Stmt { kind, line });
+    }
+    statements
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
-        match self.input.peek() {
-            Some(&c) => Some(self.read_token(c)),
-            None => None,
+/// Lexes and parses a whole source file.
+fn raw_template(parts: Vec<TemplatePart>) -> String {
+    parts
+        .into_iter()
+        .map(|part| match part {
+            TemplatePart::Text(text) => text,
+            TemplatePart::Code(code) => format!("{{{}}}", code),
+        })
+        .collect()
+}
+
+// `x + 1:>8` is the expression `x + 1` and the spec `>8`. the spec starts at the last `:` that
+// isn't inside brackets or a string
+fn split_format_spec(code: &str) -> (&str, Option<&str>) {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut split = None;
+    for (i, c) in code.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ':' if depth == 0 => split = Some(i),
+            _ => {}
         }
     }
-}
\ No newline at end of file
+    match split {
+        Some(i) => (&code[..i], Some(&code[i + 1..])),
+        None => (code, None),
+    }
+}
+
+pub fn parse_source(source: &str) -> ParseResult<Vec<Stmt>> {
+    Parser::new(crate::lexer::tokenize(source)).parse_program()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unterminated_strings_are_errors_at_their_start() {
+        let error = parse_source("s := \"a{\";\nprint(s);").unwrap_err();
+        assert_eq!((error.message.as_str(), error.line), ("unterminated `{` in a string", 1));
+        let error = parse_source("x := 1;\ns := \"abc\nprint(s);").unwrap_err();
+        assert_eq!((error.message.as_str(), error.line), ("unterminated string", 2));
+    }
+
+    #[test]
+    fn test_parse_generic_function_forms() {
+        let program = parse_source(
+            "first<T>(xs:T[]):T { return xs[0]; }\nadd:int(a:int, b:int) { return a + b; }",
+        )
+        .unwrap();
+        match &program[0].kind {
+            StmtKind::Function(f) => {
+                assert_eq!(f.type_params[0].name, "T");
+                assert_eq!(f.ret, TypeExpr::named("T"));
+                assert_eq!(f.params[0].ty, TypeExpr::List(Box::new(TypeExpr::named("T"))));
+            }
+            other => panic!("expected a function, got {:?}", other),
+        }
+        assert!(matches!(&program[1].kind, StmtKind::Function(f) if f.name == "add"));
+    }
+
+    #[test]
+    fn test_explicit_type_arguments_vs_comparison() {
+        let program = parse_source("x:bool = a < b; y:int[] = empty<int>();").unwrap();
+        match &program[0].kind {
+            StmtKind::Let { value, .. } => {
+                assert!(matches!(value.kind, ExprKind::Binary(BinaryOp::Lt, _, _)))
+            }
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+        match &program[1].kind {
+            StmtKind::Let { value, .. } => match &value.kind {
+                ExprKind::Call { type_args, .. } => {
+                    assert_eq!(type_args, &vec![TypeExpr::named("int")])
+                }
+                other => panic!("expected a call, got {:?}", other),
+            },
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_double_angle_closes_type_arguments_or_shifts() {
+        let program = parse_source("b:Box<Box<int>> = make<Box<int>>(); c := a < b >> 1;").unwrap();
+        let nested = TypeExpr::Named("Box".to_string(), vec![TypeExpr::named("int")]);
+        match &program[0].kind {
+            StmtKind::Let { ty: Some(TypeExpr::Named(_, args)), value, .. } => {
+                assert_eq!(args, &vec![nested.clone()]);
+                assert!(matches!(&value.kind, ExprKind::Call { type_args, .. } if type_args == &vec![nested]));
+            }
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+        match &program[1].kind {
+            StmtKind::Let { value, .. } => match &value.kind {
+                ExprKind::Binary(BinaryOp::Lt, _, right) => {
+                    assert!(matches!(right.kind, ExprKind::Binary(BinaryOp::Shr, _, _)))
+                }
+                other => panic!("expected a comparison, got {:?}", other),
+            },
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_if_and_blocks_as_values() {
+        let program = parse_source("y := if a { 1 } else if b { 2 } else { if c { 3 } else { 4 } };").unwrap();
+        let StmtKind::Let { value, .. } = &program[0].kind else {
+            panic!("expected a declaration, got {:?}", program[0].kind);
+        };
+        let ExprKind::If { else_branch: Some(else_branch), .. } = &value.kind else {
+            panic!("expected an if expression, got {:?}", value.kind);
+        };
+        // the `else if` and the trailing `if` statement of the last branch are both values
+        let StmtKind::Expr(Expr { kind: ExprKind::If { else_branch: Some(last), .. }, .. }) = &else_branch[0].kind else {
+            panic!("expected an else if, got {:?}", else_branch[0].kind);
+        };
+        assert!(matches!(&last[0].kind, StmtKind::Expr(Expr { kind: ExprKind::If { .. }, .. })));
+    }
+
+    #[test]
+    fn test_tuples_and_destructuring() {
+        let program = parse_source("(a, b):(int, str) = t.0.1; f((x, y):(int, int)) { }").unwrap();
+        match &program[0].kind {
+            StmtKind::Destructure { names, ty: Some(TypeExpr::Tuple(types)), value, .. } => {
+                assert_eq!(names, &["a", "b"]);
+                assert_eq!(types.len(), 2);
+                // `.0.1` is two tuple indexes, not the float `0.1`
+                assert!(matches!(&value.kind, ExprKind::Field(inner, one) if one == "1"
+                    && matches!(&inner.kind, ExprKind::Field(_, zero) if zero == "0")));
+            }
+            other => panic!("expected a destructuring declaration, got {:?}", other),
+        }
+        match &program[1].kind {
+            StmtKind::Function(decl) => {
+                assert_eq!(decl.params[0].name, "(x, y)");
+                assert!(matches!(&decl.body[0].kind, StmtKind::Destructure { names, .. } if names == &["x", "y"]));
+            }
+            other => panic!("expected a function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_default_variadic_and_named_arguments() {
+        let program = parse_source("f(port:int = 80, xs: ...str) { } f(port: 1);").unwrap();
+        let StmtKind::Function(decl) = &program[0].kind else {
+            panic!("expected a function, got {:?}", program[0].kind);
+        };
+        assert_eq!(decl.params[0].default.as_ref().map(|d| &d.kind), Some(&ExprKind::Integer(80, LiteralType::default())));
+        assert!(decl.params[1].variadic);
+        assert_eq!(decl.params[1].ty, TypeExpr::List(Box::new(TypeExpr::named("str"))));
+        match &program[1].kind {
+            StmtKind::Expr(Expr { kind: ExprKind::Call { args, .. }, .. }) => {
+                assert!(matches!(&args[0].kind, ExprKind::NamedArg(name, _) if name == "port"))
+            }
+            other => panic!("expected a call, got {:?}", other),
+        }
+    }
+}
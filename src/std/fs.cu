@@ -0,0 +1,32 @@
+// fs.cu, the `fs` standard module for files and directories. everything that can fail gives a
+// Result holding the error's message, so `text := fs.read("notes.txt")?;` passes it on.
+
+// `modified` is in seconds since 1970-01-01 UTC
+pub struct Metadata { size:u64, is_file:bool, is_dir:bool, readonly:bool, modified:float }
+
+// the whole file as a string
+pub read(path:str):Result<str, str>;
+// the lines of the file without their line endings
+pub read_lines(path:str):Result<str[], str>;
+// replaces the file, creating it if it isn't there
+pub write(path:str, text:str):Result<void, str>;
+// adds to the end of the file, creating it if it isn't there
+pub append(path:str, text:str):Result<void, str>;
+pub remove(path:str):Result<void, str>;
+pub rename(from:str, to:str):Result<void, str>;
+pub copy(from:str, to:str):Result<void, str>;
+
+pub exists(path:str):bool;
+pub is_file(path:str):bool;
+pub is_dir(path:str):bool;
+pub metadata(path:str):Result<Metadata, str>;
+
+// the names of the entries in a directory, sorted
+pub list_dir(path:str):Result<str[], str>;
+// creates the directory and any missing parents, fine if it's already there
+pub create_dir(path:str):Result<void, str>;
+// the directory has to be empty
+pub remove_dir(path:str):Result<void, str>;
+
+// `fs.join("data", "2024", "log.txt")` is "data/2024/log.txt", an absolute part starts over
+pub join(parts: ...str):str;
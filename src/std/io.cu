@@ -0,0 +1,11 @@
+// io.cu, the `io` standard module. `input`, `read_line` and `read_all` are builtins, this adds a
+// way to loop over the lines of standard input as they come in.
+
+pub struct Lines { }
+
+impl Iterator<str> for Lines {
+    next(mut self):str? { return read_line(); }
+}
+
+// `for line in io.lines() { ... }`, stops at the end of the input
+pub lines():Lines { return Lines { }; }
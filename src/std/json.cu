@@ -0,0 +1,10 @@
+// json.cu, the `json` standard module for reading and writing JSON.
+
+// reads `text` into a value of type T, which has to be written out: `json.parse<Config>(text)`.
+// null goes into an optional, numbers into the number types, arrays into lists and tuples, and
+// objects into maps and structs. a struct needs every field that isn't optional and no others.
+// the err says what didn't fit and where, like `expected int at $.servers[2].port, found a string`
+pub parse<T>(text:str):Result<T, str>;
+
+// writes any value as JSON. `pretty` puts each item on its own line, indented by two spaces
+pub stringify<T>(value:T, pretty:bool = false):str;
@@ -0,0 +1,79 @@
+// math.cu, the `math` standard module. `import math;` and then `math.sqrt(2.0)`.
+// functions without a body are run by the interpreter itself.
+
+pub const PI:float = 3.141592653589793;
+pub const E:float = 2.718281828459045;
+pub const INF:float = 1.0 / 0.0;
+pub const NAN:float = 0.0 / 0.0;
+
+pub sin(x:float):float;
+pub cos(x:float):float;
+pub tan(x:float):float;
+pub asin(x:float):float;
+pub acos(x:float):float;
+pub atan(x:float):float;
+// the angle of the point (x, y), in all four quadrants
+pub atan2(y:float, x:float):float;
+pub sinh(x:float):float;
+pub cosh(x:float):float;
+pub tanh(x:float):float;
+
+pub exp(x:float):float;
+// the natural logarithm
+pub log(x:float):float;
+pub log2(x:float):float;
+pub log10(x:float):float;
+pub sqrt(x:float):float;
+pub cbrt(x:float):float;
+pub pow(base:float, exponent:float):float;
+pub hypot(x:float, y:float):float;
+
+pub floor(x:float):float { return x.floor(); }
+pub ceil(x:float):float { return x.ceil(); }
+pub round(x:float):float { return x.round(); }
+// drops the fraction, rounding toward zero
+pub trunc(x:float):float;
+
+pub is_nan(x:float):bool { return x != x; }
+pub is_finite(x:float):bool { return x == x && x != INF && x != -INF; }
+
+// these work on any number type, ints, sized ints, bigints and floats
+pub min<T: Ord>(a:T, b:T):T { return if a <= b { a } else { b }; }
+pub max<T: Ord>(a:T, b:T):T { return if a >= b { a } else { b }; }
+pub clamp<T: Ord>(x:T, low:T, high:T):T { return max(low, min(x, high)); }
+pub abs<T: Ord + Neg>(x:T):T {
+    negated := -x;
+    return if x < negated { negated } else { x };
+}
+
+// `/` and `%` on ints round toward zero, so `-7 / 2` is -3 and `-7 % 2` is -1, the remainder
+// has the sign of the left side. these round toward negative infinity instead, `div_floor(-7, 2)`
+// is -4 and `mod_floor(-7, 2)` is 1, the remainder has the sign of the right side.
+pub div_floor(a:int, b:int):int {
+    q := a / b;
+    return if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q };
+}
+pub mod_floor(a:int, b:int):int {
+    r := a % b;
+    return if r != 0 && (r < 0) != (b < 0) { r + b } else { r };
+}
+
+// the greatest common divisor, never negative. gcd(0, 0) is 0
+pub gcd(a:int, b:int):int {
+    mut x := abs(a);
+    mut y := abs(b);
+    while y != 0 {
+        next := x % y;
+        x = y;
+        y = next;
+    }
+    return x;
+}
+
+// the least common multiple, never negative. lcm(n, 0) is 0
+pub lcm(a:int, b:int):int {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    return abs(a / gcd(a, b) * b);
+}
@@ -0,0 +1,21 @@
+// process.cu, the `process` standard module: the command line, environment variables, the working
+// directory and running other programs.
+
+// what a program that ran printed and the code it exited with, -1 if a signal killed it
+pub struct Output { code:int, stdout:str, stderr:str }
+
+// the arguments after the script's name, `cube script.cu a b` gives ["a", "b"]
+pub args():str[];
+// none when the variable isn't set
+pub env(name:str):str?;
+// an err for a name that is empty or has a `=` in it, or a name or value with a NUL
+pub set_env(name:str, value:str):Result<void, str>;
+// stops the program with the exit code, from 0 to 255, once what it printed is written out
+pub exit(code:int);
+
+pub cwd():Result<str, str>;
+pub set_cwd(path:str):Result<void, str>;
+
+// runs a program and waits for it to finish, `process.run("git", "status")`. an err when it
+// can't be started at all, a program that fails still gives an ok with its code
+pub run(program:str, args: ...str):Result<Output, str>;
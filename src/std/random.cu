@@ -0,0 +1,26 @@
+// random.cu, the `random` standard module. the numbers come from one generator that starts
+// somewhere different every run, unless `cube --seed 42 file.cu` or `random.seed(42)` starts it
+// from a fixed point so the run can be repeated exactly. it's not good enough for passwords or keys.
+
+// starts the generator over from `n`
+pub seed(n:i64);
+
+// a float from 0.0 up to but not including 1.0
+pub unit():float;
+// an int from `low` up to but not including `high`, like `low..high`
+pub range(low:int, high:int):int;
+pub range_float(low:float, high:float):float { return low + unit() * (high - low); }
+// true with probability `p`
+pub chance(p:float):bool { return unit() < p; }
+
+// one of the items, none if there aren't any
+pub choice<T>(items:T[]):T? {
+    if len(items) == 0 {
+        return none;
+    }
+    return items[range(0, len(items))];
+}
+// puts the items in a random order, in place
+pub shuffle<T>(mut items:T[]);
+// `count` different items in a random order
+pub sample<T>(items:T[], count:int):T[];
@@ -0,0 +1,79 @@
+// time.cu, the `time` standard module: measuring how long things take, the current date and time,
+// sleeping, and reading and writing dates.
+
+// a length of time, negative if it goes backwards
+pub struct Duration { nanos:i64 }
+
+impl Add for Duration { add(self, other:Duration):Duration { return Duration { nanos: self.nanos + other.nanos }; } }
+impl Sub for Duration { sub(self, other:Duration):Duration { return Duration { nanos: self.nanos - other.nanos }; } }
+impl Neg for Duration { neg(self):Duration { return Duration { nanos: -self.nanos }; } }
+impl Eq for Duration { eq(self, other:Duration):bool { return self.nanos == other.nanos; } }
+impl Ord for Duration {
+    cmp(self, other:Duration):int { return if self.nanos < other.nanos { -1 } else if self.nanos > other.nanos { 1 } else { 0 }; }
+}
+// `1.5s`
+impl Show for Duration { show(self):str { return "{as_seconds(self)}s"; } }
+
+pub nanoseconds(n:i64):Duration { return Duration { nanos: n }; }
+pub microseconds(n:i64):Duration { return Duration { nanos: n * 1000 }; }
+pub milliseconds(n:i64):Duration { return Duration { nanos: n * 1000000 }; }
+pub seconds(n:float):Duration { return Duration { nanos: (n * 1000000000.0) as i64 }; }
+pub minutes(n:i64):Duration { return Duration { nanos: n * 60000000000 }; }
+pub hours(n:i64):Duration { return Duration { nanos: n * 3600000000000 }; }
+
+pub as_seconds(d:Duration):float { return d.nanos as float / 1000000000.0; }
+pub as_millis(d:Duration):i64 { return d.nanos / 1000000; }
+// a duration `factor` times as long
+pub scale(d:Duration, factor:float):Duration { return Duration { nanos: (d.nanos as float * factor) as i64 }; }
+
+// a reading of the monotonic clock, which never goes backwards but only means something compared
+// to another reading
+pub struct Instant { nanos:i64 }
+
+pub instant():Instant;
+// how long it's been since `start`, `t := time.instant(); work(); print(time.elapsed(t));`
+pub elapsed(start:Instant):Duration { return Duration { nanos: instant().nanos - start.nanos }; }
+
+pub sleep(d:Duration);
+
+// a moment of wall clock time, `unix` seconds and `nanos` since 1970-01-01 UTC. it's shown in the
+// fixed time zone `offset` minutes east of UTC, 0 for UTC itself
+pub struct DateTime { unix:i64, nanos:int, offset:int }
+
+// the date and time a DateTime shows. `weekday` is 1 for monday through 7 for sunday
+pub struct Parts { year:int, month:int, day:int, hour:int, minute:int, second:int, nanos:int, weekday:int }
+
+// the current time in UTC
+pub now():DateTime;
+pub from_unix(seconds:i64):DateTime { return DateTime { unix: seconds, nanos: 0, offset: 0 }; }
+pub from_parts(year:int, month:int, day:int, hour:int = 0, minute:int = 0, second:int = 0, offset:int = 0):Result<DateTime, str>;
+pub parts(t:DateTime):Parts;
+// the same moment shown `minutes` east of UTC, less than a day either way
+pub with_offset(t:DateTime, minutes:int):DateTime;
+
+// the moment `d` after `t`
+pub shift(t:DateTime, d:Duration):DateTime {
+    total := t.nanos as i64 + d.nanos;
+    mut seconds := total / 1000000000;
+    mut rest := total % 1000000000;
+    if rest < 0 {
+        seconds = seconds - 1;
+        rest = rest + 1000000000;
+    }
+    return DateTime { unix: t.unix + seconds, nanos: rest as int, offset: t.offset };
+}
+// how long after `earlier` `later` is
+pub since(later:DateTime, earlier:DateTime):Duration {
+    return Duration { nanos: (later.unix - earlier.unix) * 1000000000 + (later.nanos - earlier.nanos) as i64 };
+}
+
+// writes the time with `%Y` year, `%m` month, `%d` day, `%H` hour, `%M` minute, `%S` second,
+// `%f` milliseconds, `%z` the offset as `+05:30` or `Z` for UTC, and `%%` for a `%`
+pub format(t:DateTime, pattern:str):str;
+// reads a time written the way `pattern` says, see `format`. `%S` can have a fraction after it and
+// `%z` can be `Z`, `+05:30` or `+0530`. without `%z` it's UTC
+pub parse(text:str, pattern:str):Result<DateTime, str>;
+
+// `2024-05-01T12:30:00Z`
+pub to_iso(t:DateTime):str { return format(t, "%Y-%m-%dT%H:%M:%S%z"); }
+pub parse_iso(text:str):Result<DateTime, str> { return parse(text, "%Y-%m-%dT%H:%M:%S%z"); }
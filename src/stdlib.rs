@@ -0,0 +1,837 @@
+// stdlib.rs
+// the standard modules that come with cube. each one is a cube source file built into the
+// binary, `import math;` loads it when the program has no math.cu of its own. the functions they
+// declare without a body are native, the interpreter hands their calls to `call`.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::ints::IntKind;
+use crate::json;
+use crate::value::Value;
+
+/// The names of the standard modules.
+pub const MODULES: [&str; 7] = ["math", "fs", "io", "process", "json", "time", "random"];
+
+/// The source of the standard module imported as `path`, e.g. `math.cu`.
+pub fn source(path: &str) -> Option<&'static str> {
+    match path {
+        "math.cu" => Some(include_str!("std/math.cu")),
+        "fs.cu" => Some(include_str!("std/fs.cu")),
+        "io.cu" => Some(include_str!("std/io.cu")),
+        "process.cu" => Some(include_str!("std/process.cu")),
+        "json.cu" => Some(include_str!("std/json.cu")),
+        "time.cu" => Some(include_str!("std/time.cu")),
+        "random.cu" => Some(include_str!("std/random.cu")),
+        _ => None,
+    }
+}
+
+/// What the natives need from outside the program.
+#[derive(Debug, Default)]
+pub struct Context {
+    // the command line arguments after the script's name
+    pub args: Vec<String>,
+    // where `random` gets its numbers
+    pub rng: Rng,
+}
+
+/// A xoshiro256** generator, small and fast with good statistics, but not for anything secret.
+#[derive(Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// The generator `--seed n` starts, the same every run.
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads the seed over the state, which mustn't be all zeros
+        let mut x = seed;
+        let mut state = [0; 4];
+        for word in &mut state {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *word = z ^ (z >> 31);
+        }
+        Rng { state }
+    }
+
+    fn next(&mut self) -> u64 {
+        let [a, b, c, d] = &mut self.state;
+        let result = b.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *b << 17;
+        *c ^= *a;
+        *d ^= *b;
+        *b ^= *c;
+        *a ^= *d;
+        *c ^= t;
+        *d = d.rotate_left(45);
+        result
+    }
+
+    // from 0 up to but not including `n`, throwing away the draws that would favour the small
+    // numbers when 2^64 isn't a multiple of `n`
+    fn below(&mut self, n: u64) -> u64 {
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let x = self.next();
+            if x >= threshold {
+                return x % n;
+            }
+        }
+    }
+
+    // 53 random bits, all a float between 0 and 1 can hold
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Rng {
+    // without a seed every run starts from the clock and the process id
+    fn default() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        Rng::new(nanos ^ (std::process::id() as u64).rotate_left(32))
+    }
+}
+
+/// Runs a native function, named as it is in the combined program like `math.sin`. The checker
+/// has already made sure the arguments have the parameters' types. `process.exit` and
+/// `json.parse` are left to the interpreter, which has to stop the program for one and knows the
+/// structs for the other.
+pub fn call(name: &str, args: &[Value], context: &mut Context) -> Result<Value, String> {
+    let value = match name.split_once('.') {
+        Some(("math", function)) => math(function, args),
+        Some(("fs", function)) => fs(function, args),
+        Some(("process", function)) => process(function, args, context),
+        Some(("time", function)) => return time(function, args),
+        Some(("random", function)) => return random(function, args, &mut context.rng),
+        Some(("json", "stringify")) => match args {
+            [value, Value::Bool(pretty)] => Some(Value::Str(json::stringify(value, *pretty))),
+            _ => None,
+        },
+        _ => None,
+    };
+    value.ok_or_else(|| format!("there is no native function `{}`", name))
+}
+
+fn math(function: &str, args: &[Value]) -> Option<Value> {
+    if let (Some(function), [Value::Float(x)]) = (float_function(function), args) {
+        return Some(Value::Float(function(*x)));
+    }
+    let value = match (function, args) {
+        ("atan2", [Value::Float(y), Value::Float(x)]) => Value::Float(y.atan2(*x)),
+        ("pow", [Value::Float(base), Value::Float(exponent)]) => Value::Float(base.powf(*exponent)),
+        ("hypot", [Value::Float(x), Value::Float(y)]) => Value::Float(x.hypot(*y)),
+        _ => return None,
+    };
+    Some(value)
+}
+
+// the natives of `math` that take a float and give a float
+fn float_function(name: &str) -> Option<fn(f64) -> f64> {
+    let function: fn(f64) -> f64 = match name {
+        "sin" => f64::sin,
+        "cos" => f64::cos,
+        "tan" => f64::tan,
+        "asin" => f64::asin,
+        "acos" => f64::acos,
+        "atan" => f64::atan,
+        "sinh" => f64::sinh,
+        "cosh" => f64::cosh,
+        "tanh" => f64::tanh,
+        "exp" => f64::exp,
+        "log" => f64::ln,
+        "log2" => f64::log2,
+        "log10" => f64::log10,
+        "sqrt" => f64::sqrt,
+        "cbrt" => f64::cbrt,
+        "trunc" => f64::trunc,
+        _ => return None,
+    };
+    Some(function)
+}
+
+fn fs(function: &str, args: &[Value]) -> Option<Value> {
+    use std::fs;
+    use std::io::Write;
+
+    let value = match (function, args) {
+        ("read", [Value::Str(path)]) => {
+            result(fs::read_to_string(path).map(Value::Str).map_err(|e| io_error("read", path, e)))
+        }
+        ("read_lines", [Value::Str(path)]) => result(
+            fs::read_to_string(path)
+                .map(|text| Value::list(text.lines().map(|line| Value::Str(line.to_string())).collect()))
+                .map_err(|e| io_error("read", path, e)),
+        ),
+        ("write", [Value::Str(path), Value::Str(text)]) => {
+            done(fs::write(path, text).map_err(|e| io_error("write", path, e)))
+        }
+        ("append", [Value::Str(path), Value::Str(text)]) => done(
+            fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(text.as_bytes()))
+                .map_err(|e| io_error("append to", path, e)),
+        ),
+        ("remove", [Value::Str(path)]) => done(fs::remove_file(path).map_err(|e| io_error("remove", path, e))),
+        ("rename", [Value::Str(from), Value::Str(to)]) => {
+            done(fs::rename(from, to).map_err(|e| io_error("rename", from, e)))
+        }
+        ("copy", [Value::Str(from), Value::Str(to)]) => {
+            done(fs::copy(from, to).map(|_| ()).map_err(|e| io_error("copy", from, e)))
+        }
+        ("exists", [Value::Str(path)]) => Value::Bool(fs::metadata(path).is_ok()),
+        ("is_file", [Value::Str(path)]) => Value::Bool(fs::metadata(path).map(|m| m.is_file()).unwrap_or(false)),
+        ("is_dir", [Value::Str(path)]) => Value::Bool(fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)),
+        ("metadata", [Value::Str(path)]) => result(
+            fs::metadata(path).map(|m| {
+                let modified = m
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|since| since.as_secs_f64())
+                    .unwrap_or(0.0);
+                structure(
+                    "fs.Metadata",
+                    vec![
+                        ("size", Value::Sized(IntKind::U64, m.len() as i128)),
+                        ("is_file", Value::Bool(m.is_file())),
+                        ("is_dir", Value::Bool(m.is_dir())),
+                        ("readonly", Value::Bool(m.permissions().readonly())),
+                        ("modified", Value::Float(modified)),
+                    ],
+                )
+            })
+            .map_err(|e| io_error("read the metadata of", path, e)),
+        ),
+        ("list_dir", [Value::Str(path)]) => {
+            let names = fs::read_dir(path).and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+                    .collect::<Result<Vec<String>, _>>()
+            });
+            result(
+                names
+                    .map(|mut names| {
+                        names.sort();
+                        Value::list(names.into_iter().map(Value::Str).collect())
+                    })
+                    .map_err(|e| io_error("list", path, e)),
+            )
+        }
+        ("create_dir", [Value::Str(path)]) => {
+            done(fs::create_dir_all(path).map_err(|e| io_error("create the directory", path, e)))
+        }
+        ("remove_dir", [Value::Str(path)]) => {
+            done(fs::remove_dir(path).map_err(|e| io_error("remove the directory", path, e)))
+        }
+        ("join", [Value::List(parts)]) => {
+            let mut path = PathBuf::new();
+            for part in parts.borrow().iter() {
+                path.push(part.to_string());
+            }
+            Value::Str(path.to_string_lossy().to_string())
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn process(function: &str, args: &[Value], context: &mut Context) -> Option<Value> {
+    let value = match (function, args) {
+        ("args", []) => Value::list(context.args.iter().cloned().map(Value::Str).collect()),
+        ("env", [Value::Str(name)]) => std::env::var(name).map(Value::Str).unwrap_or(Value::None),
+        // std::env::set_var panics on these instead
+        ("set_env", [Value::Str(name), Value::Str(value)]) => done(if name.is_empty() || name.contains('=') {
+            Err(Value::Str(format!("`{}` cannot be the name of an environment variable", name)))
+        } else if name.contains('\0') || value.contains('\0') {
+            Err(Value::Str(format!("the environment variable `{}` cannot have a NUL in it", name.escape_default())))
+        } else {
+            std::env::set_var(name, value);
+            Ok(())
+        }),
+        ("cwd", []) => result(
+            std::env::current_dir()
+                .map(|dir| Value::Str(dir.to_string_lossy().to_string()))
+                .map_err(|e| Value::Str(format!("cannot get the working directory: {}", e))),
+        ),
+        ("set_cwd", [Value::Str(path)]) => {
+            done(std::env::set_current_dir(path).map_err(|e| io_error("change to", path, e)))
+        }
+        ("run", [Value::Str(program), Value::List(arguments)]) => {
+            let arguments: Vec<String> = arguments.borrow().iter().map(|a| a.to_string()).collect();
+            let output = std::process::Command::new(program).args(&arguments).output();
+            result(
+                output
+                    .map(|output| {
+                        structure(
+                            "process.Output",
+                            vec![
+                                ("code", Value::Int(output.status.code().unwrap_or(-1))),
+                                ("stdout", Value::Str(String::from_utf8_lossy(&output.stdout).to_string())),
+                                ("stderr", Value::Str(String::from_utf8_lossy(&output.stderr).to_string())),
+                            ],
+                        )
+                    })
+                    .map_err(|e| io_error("run", program, e)),
+            )
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn time(function: &str, args: &[Value]) -> Result<Value, String> {
+    let field = |value: &Value, name: &str| {
+        integer_field(value, name).ok_or_else(|| format!("`time.{}` needs a value with an int `{}`", function, name))
+    };
+    let value = match (function, args) {
+        ("instant", []) => {
+            // readings count from the first one, an Instant can't be turned into a number by itself
+            static START: OnceLock<Instant> = OnceLock::new();
+            let nanos = START.get_or_init(Instant::now).elapsed().as_nanos();
+            structure("time.Instant", vec![("nanos", Value::Sized(IntKind::I64, nanos as i128))])
+        }
+        ("sleep", [duration]) => {
+            let nanos = field(duration, "nanos")?;
+            std::thread::sleep(std::time::Duration::from_nanos(nanos.max(0) as u64));
+            Value::Void
+        }
+        ("now", []) => {
+            let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            date_time(since.as_secs() as i64, since.subsec_nanos() as i64, 0)
+        }
+        ("from_parts", [year, month, day, hour, minute, second, offset]) => {
+            let [year, month, day, hour, minute, second, offset] =
+                [year, month, day, hour, minute, second, offset].map(|n| n.as_integer().map(|(_, n)| n as i64));
+            let missing = || format!("`time.{}` needs ints", function);
+            let civil = Civil {
+                year: year.ok_or_else(missing)?,
+                month: month.ok_or_else(missing)?,
+                day: day.ok_or_else(missing)?,
+                hour: hour.ok_or_else(missing)?,
+                minute: minute.ok_or_else(missing)?,
+                second: second.ok_or_else(missing)?,
+                nanos: 0,
+                offset: offset.ok_or_else(missing)?,
+            };
+            result(civil.to_date_time().map_err(Value::Str))
+        }
+        ("parts", [t]) => {
+            let civil = Civil::of(t)?;
+            structure(
+                "time.Parts",
+                vec![
+                    ("year", Value::Int(civil.year as i32)),
+                    ("month", Value::Int(civil.month as i32)),
+                    ("day", Value::Int(civil.day as i32)),
+                    ("hour", Value::Int(civil.hour as i32)),
+                    ("minute", Value::Int(civil.minute as i32)),
+                    ("second", Value::Int(civil.second as i32)),
+                    ("nanos", Value::Int(civil.nanos as i32)),
+                    ("weekday", Value::Int(civil.weekday() as i32)),
+                ],
+            )
+        }
+        ("format", [t, Value::Str(pattern)]) => Value::Str(Civil::of(t)?.format(pattern)),
+        ("parse", [Value::Str(text), Value::Str(pattern)]) => result(Civil::parse(text, pattern).map_err(Value::Str)),
+        ("with_offset", [t, Value::Int(minutes)]) => {
+            check_offset(*minutes as i64)?;
+            date_time(field(t, "unix")?, field(t, "nanos")?, *minutes as i64)
+        }
+        _ => return Err(format!("there is no native function `time.{}`", function)),
+    };
+    Ok(value)
+}
+
+fn random(function: &str, args: &[Value], rng: &mut Rng) -> Result<Value, String> {
+    let value = match (function, args) {
+        ("seed", [seed]) => {
+            *rng = Rng::new(seed.as_integer().map(|(_, n)| n as u64).unwrap_or(0));
+            Value::Void
+        }
+        ("unit", []) => Value::Float(rng.unit()),
+        ("range", [Value::Int(low), Value::Int(high)]) => {
+            if low >= high {
+                return Err(format!("`random.range` cannot pick from the empty range {}..{}", low, high));
+            }
+            Value::Int((*low as i64 + rng.below((*high as i64 - *low as i64) as u64) as i64) as i32)
+        }
+        ("shuffle", [Value::List(items)]) => {
+            let mut items = items.borrow_mut();
+            for i in (1..items.len()).rev() {
+                let j = rng.below(i as u64 + 1) as usize;
+                items.swap(i, j);
+            }
+            Value::Void
+        }
+        ("sample", [Value::List(items), Value::Int(count)]) => {
+            let mut items = items.borrow().clone();
+            if *count < 0 || *count as usize > items.len() {
+                return Err(format!("`random.sample` cannot pick {} items from a list of {}", count, items.len()));
+            }
+            // the front of a shuffle that stops after `count` steps
+            for i in 0..*count as usize {
+                let j = i + rng.below((items.len() - i) as u64) as usize;
+                items.swap(i, j);
+            }
+            items.truncate(*count as usize);
+            Value::list(items)
+        }
+        _ => return Err(format!("there is no native function `random.{}`", function)),
+    };
+    Ok(value)
+}
+
+// a DateTime taken apart into the calendar date and clock time it shows at its offset
+struct Civil {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    nanos: i64,
+    // minutes east of UTC
+    offset: i64,
+}
+
+impl Civil {
+    fn of(t: &Value) -> Result<Civil, String> {
+        let field = |name| integer_field(t, name).ok_or_else(|| format!("{} is not a time.DateTime", t));
+        let (unix, offset) = (field("unix")?, field("offset")?);
+        // a DateTime can be built with any fields, not only through this module
+        check_offset(offset)?;
+        let too_far = || format!("{} seconds from 1970 is too far away to show as a date", unix);
+        let local = unix.checked_add(offset * 60).ok_or_else(too_far)?;
+        let (year, month, day) = civil_from_days(local.div_euclid(86400));
+        if i32::try_from(year).is_err() {
+            return Err(too_far());
+        }
+        let seconds = local.rem_euclid(86400);
+        Ok(Civil {
+            year,
+            month,
+            day,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+            nanos: field("nanos")?,
+            offset,
+        })
+    }
+
+    fn to_date_time(&self) -> Result<Value, String> {
+        if !(1..=12).contains(&self.month) {
+            return Err(format!("there is no month {}", self.month));
+        }
+        if !(1..=days_in_month(self.year, self.month)).contains(&self.day) {
+            return Err(format!("there is no day {} in {}-{:02}", self.day, self.year, self.month));
+        }
+        if !(0..24).contains(&self.hour) || !(0..60).contains(&self.minute) || !(0..60).contains(&self.second) {
+            return Err(format!("{:02}:{:02}:{:02} is not a time of day", self.hour, self.minute, self.second));
+        }
+        check_offset(self.offset)?;
+        let local = days_from_civil(self.year, self.month, self.day) * 86400
+            + self.hour * 3600
+            + self.minute * 60
+            + self.second;
+        Ok(date_time(local - self.offset * 60, self.nanos, self.offset))
+    }
+
+    // 1 for monday through 7 for sunday, 1970-01-01 was a thursday
+    fn weekday(&self) -> i64 {
+        (days_from_civil(self.year, self.month, self.day) + 3).rem_euclid(7) + 1
+    }
+
+    fn format(&self, pattern: &str) -> String {
+        let mut out = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => out.push_str(&format!("{:04}", self.year)),
+                Some('m') => out.push_str(&format!("{:02}", self.month)),
+                Some('d') => out.push_str(&format!("{:02}", self.day)),
+                Some('H') => out.push_str(&format!("{:02}", self.hour)),
+                Some('M') => out.push_str(&format!("{:02}", self.minute)),
+                Some('S') => out.push_str(&format!("{:02}", self.second)),
+                Some('f') => out.push_str(&format!("{:03}", self.nanos / 1_000_000)),
+                Some('z') if self.offset == 0 => out.push('Z'),
+                Some('z') => {
+                    let sign = if self.offset < 0 { '-' } else { '+' };
+                    out.push_str(&format!("{}{:02}:{:02}", sign, self.offset.abs() / 60, self.offset.abs() % 60));
+                }
+                Some('%') => out.push('%'),
+                // anything else is written as it is
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+
+    fn parse(text: &str, pattern: &str) -> Result<Value, String> {
+        let mut civil = Civil { year: 1970, month: 1, day: 1, hour: 0, minute: 0, second: 0, nanos: 0, offset: 0 };
+        let text: Vec<char> = text.chars().collect();
+        let mut pos = 0;
+        let mut chars = pattern.chars();
+        let mismatch = |pos: usize, wanted: &str| {
+            let found = text.get(pos).map(|c| format!("`{}`", c)).unwrap_or("the end".to_string());
+            format!("expected {} at column {}, found {}", wanted, pos + 1, found)
+        };
+        // up to `max` digits, at least `min`
+        let digits = |pos: &mut usize, min: usize, max: usize, what: &str| {
+            let start = *pos;
+            while *pos < text.len() && *pos - start < max && text[*pos].is_ascii_digit() {
+                *pos += 1;
+            }
+            if *pos - start < min {
+                return Err(mismatch(start, what));
+            }
+            Ok(text[start..*pos].iter().collect::<String>().parse::<i64>().unwrap())
+        };
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                if text.get(pos) != Some(&c) {
+                    return Err(mismatch(pos, &format!("`{}`", c)));
+                }
+                pos += 1;
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => civil.year = digits(&mut pos, 4, 4, "a year")?,
+                Some('m') => civil.month = digits(&mut pos, 1, 2, "a month")?,
+                Some('d') => civil.day = digits(&mut pos, 1, 2, "a day")?,
+                Some('H') => civil.hour = digits(&mut pos, 1, 2, "an hour")?,
+                Some('M') => civil.minute = digits(&mut pos, 2, 2, "minutes")?,
+                Some('S') => {
+                    civil.second = digits(&mut pos, 2, 2, "seconds")?;
+                    if text.get(pos) == Some(&'.') && text.get(pos + 1).is_some_and(|c| c.is_ascii_digit()) {
+                        pos += 1;
+                        let start = pos;
+                        let fraction = digits(&mut pos, 1, 9, "a fraction")?;
+                        civil.nanos = fraction * 10_i64.pow(9 - (pos - start) as u32);
+                        // digits past nanoseconds are dropped
+                        while text.get(pos).is_some_and(|c| c.is_ascii_digit()) {
+                            pos += 1;
+                        }
+                    }
+                }
+                Some('f') => civil.nanos = digits(&mut pos, 3, 3, "milliseconds")? * 1_000_000,
+                Some('z') => match text.get(pos) {
+                    Some('Z') | Some('z') => {
+                        pos += 1;
+                        civil.offset = 0;
+                    }
+                    Some(&sign @ ('+' | '-')) => {
+                        let start = pos;
+                        pos += 1;
+                        let hours = digits(&mut pos, 2, 2, "an offset")?;
+                        if text.get(pos) == Some(&':') {
+                            pos += 1;
+                        }
+                        let minutes = digits(&mut pos, 2, 2, "an offset")?;
+                        if hours > 23 || minutes >= 60 {
+                            return Err(mismatch(start, "an offset"));
+                        }
+                        civil.offset = (hours * 60 + minutes) * if sign == '-' { -1 } else { 1 };
+                    }
+                    _ => return Err(mismatch(pos, "an offset")),
+                },
+                Some('%') => {
+                    if text.get(pos) != Some(&'%') {
+                        return Err(mismatch(pos, "`%`"));
+                    }
+                    pos += 1;
+                }
+                Some(other) => return Err(format!("`%{}` is not something a time pattern can have", other)),
+                None => return Err("a time pattern cannot end with `%`".to_string()),
+            }
+        }
+        if pos < text.len() {
+            return Err(format!("unexpected `{}` at column {}", text[pos..].iter().collect::<String>(), pos + 1));
+        }
+        civil.to_date_time()
+    }
+}
+
+fn date_time(unix: i64, nanos: i64, offset: i64) -> Value {
+    structure(
+        "time.DateTime",
+        vec![
+            ("unix", Value::Sized(IntKind::I64, unix as i128)),
+            ("nanos", Value::Int(nanos as i32)),
+            ("offset", Value::Int(offset as i32)),
+        ],
+    )
+}
+
+fn check_offset(minutes: i64) -> Result<(), String> {
+    if minutes.abs() >= 24 * 60 {
+        return Err(format!("an offset of {} minutes is more than a day", minutes));
+    }
+    Ok(())
+}
+
+// the days since 1970-01-01 of a date in the gregorian calendar, carried on back before it
+// began. this and `civil_from_days` are howard hinnant's algorithms, which work in 400 year eras
+// that always have the same number of days, counted from a march 1st so leap days come last
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// an integer field of a value of a struct declared in a standard module
+fn integer_field(value: &Value, name: &str) -> Option<i64> {
+    match value {
+        Value::Struct(_, fields) => {
+            let fields = fields.borrow();
+            let (_, field) = fields.iter().find(|(field, _)| field == name)?;
+            field.as_integer().map(|(_, n)| n as i64)
+        }
+        _ => None,
+    }
+}
+
+// `cannot read `notes.txt`: No such file or directory (os error 2)`
+fn io_error(action: &str, path: &str, error: std::io::Error) -> Value {
+    Value::Str(format!("cannot {} `{}`: {}", action, path, error))
+}
+
+fn result(result: Result<Value, Value>) -> Value {
+    match result {
+        Ok(value) => Value::Ok(Box::new(value)),
+        Err(error) => Value::Err(Box::new(error)),
+    }
+}
+
+// the Result of something that gives nothing back when it works
+fn done(outcome: Result<(), Value>) -> Value {
+    result(outcome.map(|()| Value::Void))
+}
+
+// a value of a struct declared in a standard module
+fn structure(name: &str, fields: Vec<(&str, Value)>) -> Value {
+    let fields = fields.into_iter().map(|(field, value)| (field.to_string(), value)).collect();
+    Value::Struct(name.to_string(), Rc::new(RefCell::new(fields)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Context, Rng};
+    use crate::checker::check;
+    use crate::interpriter::Interpreter;
+    use crate::modules::load_source;
+
+    fn run(source: &str) -> String {
+        run_with_input(source, "")
+    }
+
+    fn run_with_input(source: &str, input: &str) -> String {
+        let program = load_source(source).unwrap();
+        check(&program.statements).unwrap();
+        let mut interpreter = Interpreter::new(Vec::new()).with_input(input);
+        interpreter.run(&program.statements).unwrap();
+        String::from_utf8(interpreter.into_output()).unwrap()
+    }
+
+    #[test]
+    fn test_math_module() {
+        let source = "import math;\n\
+            print(math.sqrt(2.0), math.sin(math.PI / 2.0), math.pow(2.0, 10.0), math.log(math.E), math.INF)\n\
+            print(math.min(3, 7), math.max(2.5, 1.5), math.clamp(15, 0, 10), math.abs(-4), math.abs(-3i8))\n\
+            print(math.gcd(-12, 18), math.lcm(4, 6), math.div_floor(-7, 2), math.mod_floor(-7, 2), -7 / 2, -7 % 2)\n\
+            print(math.floor(-2.5), math.trunc(-2.5), math.is_nan(math.NAN), math.sqrt(9 as float))";
+        assert_eq!(
+            run(source),
+            "1.4142135623730951 1.0 1024.0 1.0 inf\n3 2.5 10 4 3\n6 12 -4 1 -3 -1\n-3.0 -2.0 true 3.0\n"
+        );
+    }
+
+    #[test]
+    fn test_fs_module_reports_failures_as_errs() {
+        let dir = std::env::temp_dir().join(format!("cube-fs-{}", std::process::id()));
+        let source = format!(
+            "import fs;\n\
+             dir := fs.join({:?}, \"sub\");\n\
+             file := fs.join(dir, \"a.txt\");\n\
+             save(text:str):Result<void, str> {{ fs.create_dir(dir)?; fs.write(file, text)?; return ok(); }}\n\
+             print(save(\"one\\n\"), fs.append(file, \"two\"), fs.read_lines(file), fs.is_dir(dir))\n\
+             print(fs.metadata(file).unwrap().size, fs.list_dir(dir), fs.remove_dir(dir).is_err())\n\
+             print(fs.remove(file), fs.exists(file), fs.read(file).is_err())",
+            dir.display().to_string()
+        );
+        let output = run(&source);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            output,
+            "ok(void) ok(void) ok([\"one\", \"two\"]) true\n7 ok([\"a.txt\"]) true\nok(void) false true\n"
+        );
+    }
+
+    #[test]
+    fn test_io_lines_loops_over_standard_input() {
+        let source = "import io;\n\
+            mut total := 0;\n\
+            for line in io.lines() { total = total + line.parse_int().unwrap_or(0); }\n\
+            print(total, read_line())";
+        assert_eq!(run_with_input(source, "1\n2\r\nx\n39"), "42 none\n");
+    }
+
+    #[test]
+    fn test_process_module_and_exit() {
+        let source = "import process;\n\
+            print(process.args(), process.env(\"CUBE_TEST_UNSET\"))\n\
+            out := process.run(\"sh\", \"-c\", \"echo out; echo err >&2; exit 3\").unwrap();\n\
+            print(out.code, out.stdout, out.stderr.trim(), process.run(\"/no/such/program\").is_err())\n\
+            print(process.set_env(\"CUBE_TEST_SET\", \"1\"), process.env(\"CUBE_TEST_SET\"), process.set_env(\"A=B\", \"1\"))\n\
+            process.exit(4);\n\
+            print(\"not reached\")";
+        let program = load_source(source).unwrap();
+        check(&program.statements).unwrap();
+        let context = Context { args: vec!["a".to_string(), "b c".to_string()], ..Context::default() };
+        let mut interpreter = Interpreter::new(Vec::new()).with_context(context);
+        assert_eq!(interpreter.run(&program.statements).unwrap(), 4);
+        let output = String::from_utf8(interpreter.into_output()).unwrap();
+        assert_eq!(
+            output,
+            "[\"a\", \"b c\"] none\n3 out\n err true\nok(void) 1 err(\"`A=B` cannot be the name of an environment variable\")\n"
+        );
+    }
+
+    #[test]
+    fn test_json_module_decodes_into_structs_and_reports_paths() {
+        let source = r#"import json;
+            struct Server { host:str, port:u16, backup:Server? }
+            struct Config { name:str, servers:Server[], limits:map<str, int> }
+            c := json.parse<Config>("{{\"name\": \"prod\", \"limits\": {{}}, \"servers\": [{{\"host\": \"a\", \"port\": 80}}]}}").unwrap();
+            print(c.servers[0].port, c.servers[0].backup, json.stringify(c))
+            print(json.stringify([(1, "a")], pretty: true))
+            print(json.parse<Server[]>("[{{\"host\": \"a\", \"port\": 70000}}]"))
+            print(json.parse<Server>("{{\"host\": \"a\", \"port\": 1, \"prot\": 2}}"))
+            print(json.parse<Config>("{{\"name\": 1}}"), json.parse<int>("[1"))"#;
+        assert_eq!(
+            run(source),
+            "80 none {\"name\":\"prod\",\"servers\":[{\"host\":\"a\",\"port\":80,\"backup\":null}],\"limits\":{}}\n\
+             [\n  [\n    1,\n    \"a\"\n  ]\n]\n\
+             err(\"70000 at $[0].port does not fit in u16\")\n\
+             err(\"unknown field `prot` of Server at $\")\n\
+             err(\"expected str at $.name, found a number\") err(\"invalid JSON at line 1, column 3: expected `]`\")\n"
+        );
+        let errors = check(&load_source("import json;\nx := json.parse(\"1\");\nf<T>(s:str):T { return json.parse<T>(s).unwrap(); }").unwrap().statements).unwrap_err();
+        assert!(errors[0].message.starts_with("`json.parse` needs its type written out"));
+        assert!(errors.iter().any(|e| e.message.starts_with("`json.parse` cannot be given a type parameter")));
+    }
+
+    #[test]
+    fn test_time_module_durations_and_dates() {
+        let source = "import time;\n\
+            start := time.instant();\n\
+            time.sleep(time.milliseconds(5));\n\
+            print(time.elapsed(start) >= time.milliseconds(5), time.seconds(1.5) + time.milliseconds(250) - time.minutes(1))\n\
+            t := time.from_parts(2024, 2, 29, 23, 30).unwrap();\n\
+            later := time.shift(t, time.hours(1));\n\
+            print(time.to_iso(later), time.parts(t).weekday, time.to_iso(time.with_offset(later, 330)), time.since(later, t))\n\
+            p := time.parse_iso(\"2024-03-01T05:00:00.5+05:30\").unwrap();\n\
+            print(time.format(p, \"%d/%m/%Y %H:%M:%S.%f %z\"), p.unix == t.unix, time.to_iso(time.from_unix(-1)))\n\
+            print(time.from_parts(2023, 2, 29), time.parse(\"2024-01-01x\", \"%Y-%m-%d\"))\n\
+            print(time.parse_iso(\"2024-03-01T05:00:00+05:75\"), time.parse_iso(\"2024-03-01T05:00:00-24:00\"))";
+        assert_eq!(
+            run(source),
+            "true -58.25s\n\
+             2024-03-01T00:30:00Z 4 2024-03-01T06:00:00+05:30 3600.0s\n\
+             01/03/2024 05:00:00.500 +05:30 true 1969-12-31T23:59:59Z\n\
+             err(\"there is no day 29 in 2023-02\") err(\"unexpected `x` at column 11\")\n\
+             err(\"expected an offset at column 20, found `+`\") err(\"expected an offset at column 20, found `-`\")\n"
+        );
+    }
+
+    #[test]
+    fn test_time_module_rejects_offsets_and_times_it_cannot_show() {
+        let source = "import time;\n\
+            try { time.with_offset(time.from_unix(0), -1440); } catch (e) { print(e); }\n\
+            far := time.DateTime { unix: 9223372036854775807, nanos: 0, offset: 60 };\n\
+            try { print(time.parts(far)); } catch (e) { print(e); }\n\
+            try { print(time.format(time.from_unix(-9000000000000000000), \"%Y\")); } catch (e) { print(e); }\n\
+            try { print(time.to_iso(time.DateTime { unix: 0, nanos: 0, offset: 5000 })); } catch (e) { print(e); }";
+        assert_eq!(
+            run(source),
+            "an offset of -1440 minutes is more than a day\n\
+             9223372036854775807 seconds from 1970 is too far away to show as a date\n\
+             -9000000000000000000 seconds from 1970 is too far away to show as a date\n\
+             an offset of 5000 minutes is more than a day\n"
+        );
+    }
+
+    #[test]
+    fn test_random_module_repeats_with_a_seed() {
+        let source = "import random;\n\
+            mut xs := [1, 2, 3, 4, 5, 6, 7, 8];\n\
+            random.shuffle(xs);\n\
+            print(random.range(0, 100), random.choice([\"a\", \"b\", \"c\"]), random.sample(xs, 3), xs)\n\
+            random.seed(7);\n\
+            a := random.unit();\n\
+            random.seed(7);\n\
+            again := a == random.unit();\n\
+            mut seen := [false, false, false];\n\
+            for i in 0..100 { seen[random.range(-1, 2) + 1] = true; }\n\
+            empty:int[] = [];\n\
+            sort(xs);\n\
+            print(again, seen, xs, random.choice(empty))";
+        let program = load_source(source).unwrap();
+        check(&program.statements).unwrap();
+        let seeded = |seed| {
+            let context = Context { rng: Rng::new(seed), ..Context::default() };
+            let mut interpreter = Interpreter::new(Vec::new()).with_context(context);
+            interpreter.run(&program.statements).unwrap();
+            String::from_utf8(interpreter.into_output()).unwrap()
+        };
+        let output = seeded(42);
+        assert_eq!(output, seeded(42));
+        assert_ne!(output, seeded(43));
+        assert!(output.ends_with("\ntrue [true, true, true] [1, 2, 3, 4, 5, 6, 7, 8] none\n"), "{}", output);
+        let errors = check(&load_source("import random;\nxs := [1];\nrandom.shuffle(xs);").unwrap().statements).unwrap_err();
+        assert_eq!(errors[0].message, "cannot shuffle `xs` because it is not declared `mut`");
+    }
+}
@@ -1,6 +1,8 @@
-
 use std::{iter::Peekable, str::Chars};
 
+use crate::bigint::BigInt;
+use crate::ints::IntKind;
+
 
 
 #[derive(Debug, PartialEq, Clone)]
@@ -16,13 +18,44 @@ pub enum Token {
     Else,
     While,
     For,
+    In,
     Print,
+    Return,
+    Struct,
+    Trait,
+    Impl,
+    Try,
+    Catch,
+    Import,
+    Pub,
+    Mut,
+    Const,
+    As,
+    True,
+    False,
+    NoneLiteral,
     // Operators
     Plus,
     Minus,
     Multiply,
     Divide,
     Modulo,
+    // `+%` `-%` `*%` wrap around on overflow, `+|` `-|` `*|` saturate
+    WrappingPlus,
+    WrappingMinus,
+    WrappingMultiply,
+    SaturatingPlus,
+    SaturatingMinus,
+    SaturatingMultiply,
+    // `&` `|` `^` `~` `<<` `>>` work on the bits of an integer, `**` raises to a power
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    Power,
+    Assign,
     Equals,
     NotEquals,
     LessThan,
@@ -31,6 +64,8 @@ pub enum Token {
     GreaterThanOrEqual,
     And,
     Or,
+    Question,
+    Coalesce,
     Empty,
     Not,
     // Symbols
@@ -39,23 +74,49 @@ pub enum Token {
     NewLine,
 
     Comma,
+    Dot,
+    DotDot,
+    DotDotEq,
+    // `...`, before the type of a variadic parameter
+    Ellipsis,
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     // Literals
     Identifier(String),
-    Integer(i32),
+    Integer(i128),
+    // A literal with a type suffix, like `255u8`
+    SizedInteger(i128, IntKind),
+    // `123n`, a bigint literal
+    BigInteger(BigInt),
     CFloat(f64),
     String(String),
+    // A string with `{expression}` parts in it
+    Template(Vec<TemplatePart>),
+    // A character the lexer does not understand
+    Unknown(char),
+    // A string, or a `{` in one, that the file ends inside of, with the line the string starts on
+    Unterminated(&'static str, usize),
     // End of file
     Eof,
 }
+/// A piece of an interpolated string: text, or the source of an expression between braces
+/// (still with its `:spec`, if any).
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePart {
+    Text(String),
+    Code(String),
+}
+
 pub struct Tokenizer<'a> {
     input: Peekable<Chars<'a>>,
 }
 
 
+#[allow(dead_code)]
 impl<'a> Tokenizer<'a> {
     pub fn new(input: &'a str) -> Self {
         Tokenizer {
@@ -63,7 +124,7 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
-    fn read_identifier(&mut self, first: char) -> String {
+    fn read_identifier(&mut self, _first: char) -> String {
         let mut ident = String::new();
 
         while let Some(&c) = self.input.peek() {
@@ -81,7 +142,6 @@ impl<'a> Tokenizer<'a> {
 
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let binding: &str = "binding";
         let token_str = match self {
             Token::Int => "Int",
             Token::Float => "Float",
@@ -92,12 +152,41 @@ impl std::fmt::Display for Token {
             Token::Else => "Else",
             Token::While => "While",
             Token::For => "For",
+            Token::In => "In",
             Token::Print => "Print",
+            Token::Return => "Return",
+            Token::Struct => "Struct",
+            Token::Trait => "Trait",
+            Token::Impl => "Impl",
+            Token::Try => "Try",
+            Token::Catch => "Catch",
+            Token::Import => "Import",
+            Token::Pub => "Pub",
+            Token::Mut => "Mut",
+            Token::Const => "Const",
+            Token::As => "As",
+            Token::True => "True",
+            Token::False => "False",
+            Token::NoneLiteral => "None",
             Token::Plus => "Plus",
             Token::Minus => "Minus",
             Token::Multiply => "Multiply",
             Token::Divide => "Divide",
             Token::Modulo => "Modulo",
+            Token::WrappingPlus => "WrappingPlus",
+            Token::WrappingMinus => "WrappingMinus",
+            Token::WrappingMultiply => "WrappingMultiply",
+            Token::SaturatingPlus => "SaturatingPlus",
+            Token::SaturatingMinus => "SaturatingMinus",
+            Token::SaturatingMultiply => "SaturatingMultiply",
+            Token::Ampersand => "Ampersand",
+            Token::Pipe => "Pipe",
+            Token::Caret => "Caret",
+            Token::Tilde => "Tilde",
+            Token::ShiftLeft => "ShiftLeft",
+            Token::ShiftRight => "ShiftRight",
+            Token::Power => "Power",
+            Token::Assign => "Assign",
             Token::Equals => "Equals",
             Token::NotEquals => "NotEquals",
             Token::LessThan => "LessThan",
@@ -106,21 +195,33 @@ impl std::fmt::Display for Token {
             Token::GreaterThanOrEqual => "GreaterThanOrEqual",
             Token::And => "And",
             Token::Or => "Or",
+            Token::Question => "Question",
+            Token::Coalesce => "Coalesce",
             Token::Empty => "Empty",
             Token::Not => "Not",
             Token::Colon => "Colon",
             Token::Semicolon => "Semicolon",
             Token::NewLine => "NewLine",
             Token::Comma => "Comma",
+            Token::Dot => "Dot",
+            Token::DotDot => "DotDot",
+            Token::DotDotEq => "DotDotEq",
+            Token::Ellipsis => "Ellipsis",
             Token::LeftParen => "LeftParen",
             Token::RightParen => "RightParen",
             Token::LeftBrace => "LeftBrace",
             Token::RightBrace => "RightBrace",
+            Token::LeftBracket => "LeftBracket",
+            Token::RightBracket => "RightBracket",
             Token::Identifier(ident) => ident,
-            Token::Integer(i) => &binding,
-            Token::CFloat(f) => &binding,
-            
-            Token::String(s) => s,
+            Token::Integer(i) => return write!(f, "{}", i),
+            Token::SizedInteger(i, kind) => return write!(f, "{}{}", i, kind),
+            Token::BigInteger(i) => return write!(f, "{}n", i),
+            Token::CFloat(float) => return write!(f, "{:?}", float),
+            Token::String(s) => return write!(f, "\"{}\"", s),
+            Token::Template(_) => "String",
+            Token::Unknown(c) => return write!(f, "{}", c),
+            Token::Unterminated(what, _) => return write!(f, "an unterminated {}", what),
             Token::Eof => "Eof",
         };
 
@@ -128,27 +229,6 @@ impl std::fmt::Display for Token {
     }
 }
 
-
-pub enum ParsedToken {
-    Int(i32),
-    Float(f64),
-    Str(String),
-    Bool(bool),
-    Void,
-    Identifier(String),
-    Function(String, Vec<Token>),
-    FunctionCall(String, Vec<Token>),
-    If(Vec<Token>, Vec<Token>),
-    Else(Vec<Token>),
-    While(Vec<Token>),
-    For(Vec<Token>, Vec<Token>, Vec<Token>, Vec<Token>),
-    Print(String),
-    Return(Token),
-    Assignment(String, Token),
-    Expression(Vec<Token>),
-    Empty,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,4 +241,3 @@ mod tests {
         assert_eq!(ident, "hello");
     }
 }
-
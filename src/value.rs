@@ -0,0 +1,223 @@
+// value.rs
+// the values a running cube program works with. lists, maps and structs are shared by reference
+// so passing one to a function and pushing onto it is visible to the caller, like in python.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bigint::BigInt;
+use crate::ints::IntKind;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i32),
+    // an integer of one of the sized types other than `int`
+    Sized(IntKind, i128),
+    Big(BigInt),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Void,
+    None,
+    List(Rc<RefCell<Vec<Value>>>),
+    // tuples can't be changed, so unlike lists they're copied rather than shared
+    Tuple(Vec<Value>),
+    Map(Rc<RefCell<Vec<(Value, Value)>>>),
+    Struct(String, Rc<RefCell<Vec<(String, Value)>>>),
+    Ok(Box<Value>),
+    Err(Box<Value>),
+    Range {
+        start: i32,
+        end: i32,
+        inclusive: bool,
+        step: i32,
+    },
+}
+
+impl Value {
+    pub fn list(items: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn map(entries: Vec<(Value, Value)>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// An integer of the given kind, an `Int` for `int`.
+    pub fn integer(kind: IntKind, n: i128) -> Self {
+        match kind {
+            IntKind::I32 => Value::Int(n as i32),
+            kind => Value::Sized(kind, n),
+        }
+    }
+
+    /// An unsuffixed integer literal. One too big for an `int` can only be stored as a sized
+    /// type, so it's kept as a 64 bit integer until then.
+    pub fn literal(n: i128) -> Self {
+        if IntKind::I32.fits(n) {
+            Value::Int(n as i32)
+        } else if IntKind::I64.fits(n) {
+            Value::Sized(IntKind::I64, n)
+        } else {
+            Value::Sized(IntKind::U64, n)
+        }
+    }
+
+    /// A copy that shares nothing with this value, for a `mut` variable that takes its value
+    /// from another one. Lists, maps and structs are otherwise shared, not copied.
+    pub fn deep_copy(&self) -> Value {
+        match self {
+            Value::List(items) => Value::list(items.borrow().iter().map(Value::deep_copy).collect()),
+            Value::Map(entries) => {
+                Value::map(entries.borrow().iter().map(|(k, v)| (k.deep_copy(), v.deep_copy())).collect())
+            }
+            Value::Struct(name, fields) => {
+                let fields = fields.borrow().iter().map(|(field, value)| (field.clone(), value.deep_copy())).collect();
+                Value::Struct(name.clone(), Rc::new(RefCell::new(fields)))
+            }
+            Value::Tuple(items) => Value::Tuple(items.iter().map(Value::deep_copy).collect()),
+            Value::Ok(value) => Value::Ok(Box::new(value.deep_copy())),
+            Value::Err(error) => Value::Err(Box::new(error.deep_copy())),
+            other => other.clone(),
+        }
+    }
+
+    /// The kind and value of an integer.
+    pub fn as_integer(&self) -> Option<(IntKind, i128)> {
+        match self {
+            Value::Int(i) => Some((IntKind::I32, *i as i128)),
+            Value::Sized(kind, n) => Some((*kind, *n)),
+            _ => None,
+        }
+    }
+
+    /// Any integer as a bigint, for mixing unsuffixed literals into bigint arithmetic.
+    pub fn as_bigint(&self) -> Option<BigInt> {
+        match self {
+            Value::Big(n) => Some(n.clone()),
+            _ => self.as_integer().map(|(_, n)| BigInt::from_i128(n)),
+        }
+    }
+
+    /// The kind and numbers of two integers an operator is applied to. A plain `int` next to a
+    /// sized integer is an unsuffixed literal the checker gave the sized type.
+    pub fn integers(left: &Value, right: &Value) -> Option<(IntKind, i128, i128)> {
+        let ((left_kind, a), (right_kind, b)) = (left.as_integer()?, right.as_integer()?);
+        let kind = if left_kind == IntKind::I32 { right_kind } else { left_kind };
+        Some((kind, a, b))
+    }
+
+    /// The name `impl` blocks for this value's type are registered under.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Sized(kind, _) => kind.name(),
+            Value::Big(_) => "bigint",
+            Value::Float(_) => "float",
+            Value::Str(_) => "str",
+            Value::Bool(_) => "bool",
+            Value::Void => "void",
+            Value::None => "none",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Map(_) => "map",
+            Value::Struct(name, _) => name,
+            Value::Ok(_) | Value::Err(_) => "Result",
+            Value::Range { .. } => "range",
+        }
+    }
+
+    // how the value looks when it is nested inside another one, strings get their quotes back
+    fn repr(&self) -> String {
+        match self {
+            Value::Str(s) => format!("{:?}", s),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            // an unsuffixed literal stays an `Int` where it isn't stored with a declared type, so
+            // compare sized integers by their number
+            (Value::Sized(_, a), Value::Sized(_, b)) => a == b,
+            (Value::Sized(_, a), Value::Int(b)) | (Value::Int(b), Value::Sized(_, a)) => *a == *b as i128,
+            (Value::Big(a), Value::Big(b)) => a == b,
+            (Value::Big(a), other) | (other, Value::Big(a)) => other.as_bigint().as_ref() == Some(a),
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Void, Value::Void) => true,
+            (Value::None, Value::None) => true,
+            (Value::List(a), Value::List(b)) => *a.borrow() == *b.borrow(),
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => *a.borrow() == *b.borrow(),
+            (Value::Struct(n1, a), Value::Struct(n2, b)) => n1 == n2 && *a.borrow() == *b.borrow(),
+            (Value::Ok(a), Value::Ok(b)) => a == b,
+            (Value::Err(a), Value::Err(b)) => a == b,
+            (
+                Value::Range { start, end, inclusive, step },
+                Value::Range { start: s2, end: e2, inclusive: i2, step: st2 },
+            ) => start == s2 && end == e2 && inclusive == i2 && step == st2,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Sized(_, n) => write!(f, "{}", n),
+            Value::Big(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Void => write!(f, "void"),
+            Value::None => write!(f, "none"),
+            Value::List(items) => {
+                let items: Vec<String> = items.borrow().iter().map(|v| v.repr()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.repr()).collect();
+                write!(f, "({})", items.join(", "))
+            }
+            Value::Map(entries) => {
+                if entries.borrow().is_empty() {
+                    return write!(f, "[:]");
+                }
+                let entries: Vec<String> = entries
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.repr(), v.repr()))
+                    .collect();
+                write!(f, "[{}]", entries.join(", "))
+            }
+            Value::Struct(name, fields) => {
+                let fields: Vec<String> = fields
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v.repr()))
+                    .collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Value::Ok(value) => write!(f, "ok({})", value.repr()),
+            Value::Err(error) => write!(f, "err({})", error.repr()),
+            Value::Range {
+                start,
+                end,
+                inclusive,
+                step,
+            } => {
+                write!(f, "{}{}{}", start, if *inclusive { "..=" } else { ".." }, end)?;
+                if *step != 1 {
+                    write!(f, " step {}", step)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
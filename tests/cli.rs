@@ -0,0 +1,58 @@
+// tests/cli.rs
+// runs the cube binary on small programs and looks at what a shell would see
+
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+// writes `source` to a file of its own and runs `cube` on it with `args` before the file name
+fn cube(name: &str, source: &str, args: &[&str]) -> Output {
+    let path: PathBuf = std::env::temp_dir().join(format!("cube-cli-{}-{}.cu", std::process::id(), name));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_Cube")).args(args).arg(&path).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn test_programs_that_do_not_load_or_check_exit_with_1() {
+    let parse_error = cube("parse", "x := ;", &[]);
+    assert_eq!(parse_error.status.code(), Some(1));
+    let type_error = cube("check", "x:int = \"one\";", &[]);
+    assert_eq!(type_error.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&type_error.stderr).contains("mismatched types"));
+    let missing = Command::new(env!("CARGO_BIN_EXE_Cube")).arg("/no/such/file.cu").output().unwrap();
+    assert_eq!(missing.status.code(), Some(1));
+    assert_eq!(cube("fine", "print(1)", &[]).status.code(), Some(0));
+}
+
+#[test]
+fn test_runtime_errors_exit_with_1_and_process_exit_with_its_code() {
+    let error = cube("error", "print(\"before\")\nxs := [1];\nprint(xs[5])", &[]);
+    assert_eq!(error.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&error.stdout), "before\n");
+    assert!(String::from_utf8_lossy(&error.stderr).starts_with("runtime error:"));
+    let exit = cube("exit", "import process;\nprint(process.args())\nprocess.exit(3);", &[]);
+    assert_eq!(exit.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&exit.stdout), "[]\n");
+    let too_big = cube("exit-range", "import process;\nprocess.exit(256);", &[]);
+    assert_eq!(too_big.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&too_big.stderr).contains("exit code 256 is not from 0 to 255"));
+}
+
+#[test]
+fn test_a_closed_pipe_ends_the_program_quietly() {
+    let path: PathBuf = std::env::temp_dir().join(format!("cube-cli-{}-pipe.cu", std::process::id()));
+    std::fs::write(&path, "for i in 0..1000000 { print(i); }").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_Cube"))
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // like `cube x.cu | head -0`
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}